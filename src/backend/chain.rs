@@ -0,0 +1,457 @@
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, GenericImage, GenericImageView, RgbaImage};
+
+use super::*;
+
+/// Failure of one step in an effect chain
+#[derive(Debug)]
+pub struct ChainError {
+    /// Zero-based position of the failing effect
+    pub index: usize,
+    pub effect: &'static str,
+    pub error: ServerFnError,
+}
+
+impl std::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Error applying effect {} ({}): {}",
+            self.index + 1,
+            self.effect,
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// Run a whole effect chain.
+///
+/// `scale` is the ratio between the working image and the full-resolution
+/// source; spatial parameters are scaled by it so that a downscaled proxy
+/// looks like the final render.
+pub fn apply_effect_chain(
+    image: DynamicImage,
+    effects: &[Effect],
+    scale: f64,
+) -> Result<RgbaImage, ChainError> {
+    let mut current_image = image;
+    for (index, effect) in effects.iter().enumerate() {
+        match effect.scaled(scale).apply(current_image) {
+            // Convert RgbaImage back to DynamicImage for the next effect
+            Ok(processed) => current_image = DynamicImage::ImageRgba8(processed),
+            Err(error) => {
+                return Err(ChainError {
+                    index,
+                    effect: effect.name(),
+                    error,
+                })
+            }
+        }
+    }
+    Ok(current_image.into_rgba8())
+}
+
+/// Shape constraint of the tiles a run of effects can be split into
+#[derive(Clone, Copy, PartialEq)]
+enum TileShape {
+    Any,
+    FullRows,
+    FullColumns,
+    Whole,
+}
+
+/// Consecutive effects that can be run together, tile by tile
+struct Segment {
+    /// Index of the first effect in the whole chain
+    start: usize,
+    effects: Vec<Effect>,
+    /// Sum of the halos of the effects, i.e. the padding each tile needs
+    halo: u32,
+    shape: TileShape,
+}
+
+fn split_into_segments(effects: &[Effect]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for (index, effect) in effects.iter().enumerate() {
+        let (halo, shape) = match effect.tiling() {
+            Tiling::PerPixel => (0, TileShape::Any),
+            Tiling::Neighborhood { halo } => (halo, TileShape::Any),
+            Tiling::Rows => (0, TileShape::FullRows),
+            Tiling::Columns => (0, TileShape::FullColumns),
+            Tiling::Whole => (0, TileShape::Whole),
+        };
+
+        match segments.last_mut() {
+            Some(segment)
+                if shape == TileShape::Any
+                    || segment.shape == TileShape::Any
+                    || segment.shape == shape =>
+            {
+                segment.effects.push(effect.clone());
+                segment.halo += halo;
+                if shape != TileShape::Any {
+                    segment.shape = shape;
+                }
+            }
+            _ => segments.push(Segment {
+                start: index,
+                effects: vec![effect.clone()],
+                halo,
+                shape,
+            }),
+        }
+    }
+
+    segments
+}
+
+/// A rectangle of the image, in pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Segment {
+    /// Part of its input the segment needs to compute `rect` of an image of
+    /// `width`×`height`
+    fn input(&self, rect: Rect, width: u32, height: u32) -> Rect {
+        let (x0, x1) = match self.shape {
+            TileShape::FullRows | TileShape::Whole => (0, width),
+            _ => (
+                rect.x.saturating_sub(self.halo),
+                (rect.x + rect.width).saturating_add(self.halo).min(width),
+            ),
+        };
+        let (y0, y1) = match self.shape {
+            TileShape::FullColumns | TileShape::Whole => (0, height),
+            _ => (
+                rect.y.saturating_sub(self.halo),
+                (rect.y + rect.height).saturating_add(self.halo).min(height),
+            ),
+        };
+        Rect {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
+}
+
+/// Segments run tile by tile on top of a full frame. Only the tiles being
+/// worked on are held: a tile of a later segment pulls the part of the
+/// earlier segments' output it needs, piece by piece.
+struct Stream<'a> {
+    frame: &'a DynamicImage,
+    segments: &'a [Segment],
+    tile_size: u32,
+}
+
+impl Stream<'_> {
+    /// `rect` of the output of segment `index`, rendered in pieces the size
+    /// of a tile or a strip
+    fn render(&self, index: usize, rect: Rect) -> Result<RgbaImage, ChainError> {
+        let segment = &self.segments[index];
+        let (piece_width, piece_height) = match segment.shape {
+            TileShape::FullRows => (rect.width, self.tile_size),
+            TileShape::FullColumns => (self.tile_size, rect.height),
+            _ => (self.tile_size, self.tile_size),
+        };
+        if piece_width >= rect.width && piece_height >= rect.height {
+            return self.render_piece(index, rect);
+        }
+
+        let mut output = RgbaImage::new(rect.width, rect.height);
+        for y in (0..rect.height).step_by(piece_height as usize) {
+            for x in (0..rect.width).step_by(piece_width as usize) {
+                let piece = Rect {
+                    x: rect.x + x,
+                    y: rect.y + y,
+                    width: piece_width.min(rect.width - x),
+                    height: piece_height.min(rect.height - y),
+                };
+                let rendered = self.render_piece(index, piece)?;
+                output.copy_from(&rendered, x, y).map_err(|e| ChainError {
+                    index: segment.start,
+                    effect: segment.effects[0].name(),
+                    error: ServerFnError::new(e),
+                })?;
+            }
+        }
+        Ok(output)
+    }
+
+    /// `rect` of the output of segment `index` in one go
+    fn render_piece(&self, index: usize, rect: Rect) -> Result<RgbaImage, ChainError> {
+        let segment = &self.segments[index];
+        let (width, height) = self.frame.dimensions();
+        let input = segment.input(rect, width, height);
+        let mut tile = match index {
+            0 => self
+                .frame
+                .crop_imm(input.x, input.y, input.width, input.height),
+            _ => DynamicImage::ImageRgba8(self.render(index - 1, input)?),
+        };
+
+        let area = TileArea {
+            x: input.x,
+            y: input.y,
+            full_width: width,
+            full_height: height,
+        };
+        for (offset, effect) in segment.effects.iter().enumerate() {
+            match effect.on_tile(area).apply(tile) {
+                Ok(processed) => tile = DynamicImage::ImageRgba8(processed),
+                Err(error) => {
+                    return Err(ChainError {
+                        index: segment.start + offset,
+                        effect: effect.name(),
+                        error,
+                    })
+                }
+            }
+        }
+
+        // Cut the padding off again
+        let tile = tile.into_rgba8();
+        if input == rect {
+            return Ok(tile);
+        }
+        Ok(tile
+            .view(rect.x - input.x, rect.y - input.y, rect.width, rect.height)
+            .to_image())
+    }
+}
+
+/// Run an effect chain tile by tile.
+///
+/// Produces the same result as [`apply_effect_chain`] while holding little
+/// more than the source and the result: per-pixel and neighborhood effects
+/// work on tiles of `tile_size` with the padding their neighborhoods need,
+/// and row and column effects on strips of that height or width. Where a
+/// column effect follows a row effect, each strip recomputes the rows it
+/// needs rather than keeping them all.
+///
+/// Effects that need the whole image, like Twirl, get a full frame, and the
+/// frame their output is tiled from stays until the next such effect.
+pub fn apply_effect_chain_tiled(
+    image: DynamicImage,
+    effects: &[Effect],
+    scale: f64,
+    tile_size: u32,
+) -> Result<RgbaImage, ChainError> {
+    let scaled: Vec<Effect> = effects.iter().map(|effect| effect.scaled(scale)).collect();
+    let tile_size = tile_size.max(1);
+    let (width, height) = image.dimensions();
+    let whole = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+
+    let mut frame = image;
+    let mut tiled: Vec<Segment> = Vec::new();
+    for segment in split_into_segments(&scaled) {
+        if segment.shape != TileShape::Whole {
+            tiled.push(segment);
+            continue;
+        }
+
+        let mut image = match tiled.len() {
+            0 => frame,
+            len => {
+                let stream = Stream {
+                    frame: &frame,
+                    segments: &tiled,
+                    tile_size,
+                };
+                DynamicImage::ImageRgba8(stream.render(len - 1, whole)?)
+            }
+        };
+        tiled.clear();
+        for (offset, effect) in segment.effects.iter().enumerate() {
+            match effect.apply(image) {
+                Ok(processed) => image = DynamicImage::ImageRgba8(processed),
+                Err(error) => {
+                    return Err(ChainError {
+                        index: segment.start + offset,
+                        effect: effect.name(),
+                        error,
+                    })
+                }
+            }
+        }
+        frame = image;
+    }
+
+    match tiled.len() {
+        0 => Ok(frame.into_rgba8()),
+        len => Stream {
+            frame: &frame,
+            segments: &tiled,
+            tile_size,
+        }
+        .render(len - 1, whole),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// Deterministic image with detail everywhere, so misplaced tiles show
+    fn test_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(53, 41, |x, y| {
+            let hash = (x * 7919 + y * 104_729).wrapping_mul(2_654_435_761);
+            Rgba([
+                (hash >> 24) as u8,
+                (x * 255 / 52) as u8,
+                (y * 255 / 40) as u8,
+                255,
+            ])
+        }))
+    }
+
+    fn effect(id: &str, params: &[(&str, ParamValue)]) -> Effect {
+        let mut effect = Effect::new(find_effect(id).expect("built-in effect"));
+        for (key, value) in params {
+            effect.set_param(key, value.clone());
+        }
+        effect
+    }
+
+    fn assert_tiled_matches(effects: &[Effect]) {
+        let expected = apply_effect_chain(test_image(), effects, 1.0).unwrap();
+        // Tile sizes that don't divide 53x41 evenly, down to single pixels
+        for tile_size in [1, 7, 16, 40, 64] {
+            let tiled = apply_effect_chain_tiled(test_image(), effects, 1.0, tile_size).unwrap();
+            assert!(tiled == expected, "tile size {} differs", tile_size);
+        }
+    }
+
+    #[test]
+    fn per_pixel_effects_match() {
+        assert_tiled_matches(&[effect("xor", &[]), effect("gamma", &[])]);
+    }
+
+    #[test]
+    fn neighborhood_effects_match() {
+        assert_tiled_matches(&[effect(
+            "gaussian_blur",
+            &[("sigma", ParamValue::Float(2.5))],
+        )]);
+        assert_tiled_matches(&[effect("bloom", &[("radius", ParamValue::Float(3.0))])]);
+    }
+
+    #[test]
+    fn row_and_column_effects_match() {
+        assert_tiled_matches(&[effect("sort", &[("direction", ParamValue::Choice(0))])]);
+        assert_tiled_matches(&[effect("sort", &[("direction", ParamValue::Choice(1))])]);
+    }
+
+    #[test]
+    fn whole_image_effects_match() {
+        assert_tiled_matches(&[effect("twirl", &[])]);
+    }
+
+    #[test]
+    fn position_dependent_effects_match() {
+        let effects = [
+            effect("noise", &[("distribution", ParamValue::Choice(0))]),
+            effect(
+                "noise",
+                &[
+                    ("distribution", ParamValue::Choice(1)),
+                    ("color", ParamValue::Choice(1)),
+                ],
+            ),
+            effect(
+                "noise",
+                &[
+                    ("distribution", ParamValue::Choice(2)),
+                    ("amount", ParamValue::Float(30.0)),
+                ],
+            ),
+            effect("film_grain", &[("size", ParamValue::Float(7.3))]),
+            // Small reaches keep single-pixel tiles quick
+            effect(
+                "wave",
+                &[
+                    ("amplitude_x", ParamValue::Float(2.5)),
+                    ("amplitude_y", ParamValue::Float(4.0)),
+                ],
+            ),
+            effect(
+                "wave",
+                &[
+                    ("amplitude_x", ParamValue::Float(-3.0)),
+                    ("edges", ParamValue::Choice(1)),
+                    ("sampling", ParamValue::Choice(1)),
+                ],
+            ),
+            effect(
+                "displace",
+                &[
+                    ("strength_x", ParamValue::Float(5.0)),
+                    ("strength_y", ParamValue::Float(3.0)),
+                ],
+            ),
+            effect(
+                "displace",
+                &[
+                    ("strength_x", ParamValue::Float(2.0)),
+                    ("strength_y", ParamValue::Float(-6.0)),
+                    ("x_channel", ParamValue::Choice(4)),
+                    ("edges", ParamValue::Choice(3)),
+                ],
+            ),
+            effect("rgb_split", &[("edges", ParamValue::Choice(1))]),
+            effect("dither", &[("method", ParamValue::Choice(5))]),
+            effect("dither", &[("method", ParamValue::Choice(7))]),
+            effect(
+                "expression",
+                &[(
+                    "formula",
+                    ParamValue::Text("r = x * 4 + y\ng = w - x + r(0, 2)\nb = h".into()),
+                )],
+            ),
+        ];
+        for effect in effects {
+            assert_ne!(effect.tiling(), Tiling::Whole, "{:?}", effect);
+            assert_tiled_matches(&[effect]);
+        }
+    }
+
+    #[test]
+    fn whole_image_effects_between_tiled_ones_match() {
+        assert_tiled_matches(&[
+            effect("box_blur", &[]),
+            effect("sort", &[("direction", ParamValue::Choice(1))]),
+            effect("twirl", &[]),
+            effect("wave", &[]),
+            effect("dither", &[("method", ParamValue::Choice(0))]),
+            effect("gaussian_blur", &[]),
+        ]);
+    }
+
+    #[test]
+    fn mixed_chains_match() {
+        assert_tiled_matches(&[
+            effect("or", &[]),
+            effect("box_blur", &[]),
+            effect("sort", &[("direction", ParamValue::Choice(0))]),
+            effect("emboss", &[]),
+            effect("sort", &[("direction", ParamValue::Choice(1))]),
+            effect("noise", &[]),
+            effect("gamma", &[]),
+        ]);
+    }
+}
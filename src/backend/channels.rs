@@ -0,0 +1,140 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Source of one channel of an lhs/rhs operand.
+///
+/// For lhs the channels are read from the image pixel, for rhs from the
+/// effect color.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    /// Constant 0. imgfx reads any name it doesn't know as 0, so this is the
+    /// only constant it can express.
+    Zero,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [Channel::R, Channel::G, Channel::B, Channel::Zero];
+
+    /// Single-character form, also the name imgfx expects
+    pub fn symbol(&self) -> char {
+        match self {
+            Channel::R => 'r',
+            Channel::G => 'g',
+            Channel::B => 'b',
+            Channel::Zero => '0',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::R => "Red",
+            Channel::G => "Green",
+            Channel::B => "Blue",
+            Channel::Zero => "Zero",
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<Channel> {
+        match symbol.to_ascii_lowercase() {
+            'r' => Some(Channel::R),
+            'g' => Some(Channel::G),
+            'b' => Some(Channel::B),
+            '0' => Some(Channel::Zero),
+            _ => None,
+        }
+    }
+}
+
+/// Channel selectors for the red, green and blue outputs of an effect,
+/// written like `bgr` or `rg0`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Channels(pub [Channel; 3]);
+
+impl Channels {
+    /// The channel names as passed to imgfx
+    pub fn to_imgfx(self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|channel| channel.symbol().to_string())
+            .collect()
+    }
+
+    /// Copy with one output channel replaced
+    pub fn with(self, index: usize, channel: Channel) -> Channels {
+        let mut channels = self.0;
+        channels[index] = channel;
+        Channels(channels)
+    }
+}
+
+impl fmt::Display for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for channel in self.0 {
+            write!(f, "{}", channel.symbol())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChannelParseError {
+    /// Number of channels found, when it isn't 3
+    WrongCount(usize),
+    /// Unknown character and its 1-based position
+    Unknown(char, usize),
+    /// Alpha was asked for at the given position; imgfx cannot read it
+    Alpha(usize),
+}
+
+impl fmt::Display for ChannelParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelParseError::WrongCount(count) => {
+                write!(f, "Expected 3 channels, found {}", count)
+            }
+            ChannelParseError::Unknown(symbol, position) => write!(
+                f,
+                "'{}' at position {} is not a channel (use r, g, b or 0)",
+                symbol, position
+            ),
+            ChannelParseError::Alpha(position) => write!(
+                f,
+                "Alpha at position {} is not supported by imgfx (use r, g, b or 0)",
+                position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChannelParseError {}
+
+impl FromStr for Channels {
+    type Err = ChannelParseError;
+
+    /// Parse three channel symbols; spaces and commas between them are ignored
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let symbols: Vec<char> = text
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ',')
+            .collect();
+
+        let mut channels = Vec::with_capacity(3);
+        for (index, symbol) in symbols.iter().enumerate() {
+            match Channel::from_symbol(*symbol) {
+                Some(channel) => channels.push(channel),
+                None if symbol.eq_ignore_ascii_case(&'a') => {
+                    return Err(ChannelParseError::Alpha(index + 1))
+                }
+                None => return Err(ChannelParseError::Unknown(*symbol, index + 1)),
+            }
+        }
+
+        match channels[..] {
+            [r, g, b] => Ok(Channels([r, g, b])),
+            _ => Err(ChannelParseError::WrongCount(channels.len())),
+        }
+    }
+}
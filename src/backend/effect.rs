@@ -1,110 +1,240 @@
-use image::Rgb;
-use imgfx::{Direction, SortBy};
-
-#[derive(Clone, PartialEq)]
-pub enum Effect {
-    Or {
-        color: Rgb<u8>,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    And {
-        color: Rgb<u8>,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Xor {
-        color: Rgb<u8>,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Left {
-        bits: u8,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-    },
-    Right {
-        bits: u8,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-    },
-    Add {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Sub {
-        color: Rgb<u8>,
-        negate: bool,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Mult {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Pow {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Div {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Average {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Screen {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Overlay {
-        color: Rgb<u8>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-    },
-    Bloom {
-        intensity: f64,
-        radius: f64,
-        min_threshold: u8,
-        max_threshold: Option<u8>,
-    },
-    Sort {
-        direction: Direction,
-        sort_by: SortBy,
-        min_threshold: f64,
-        max_threshold: f64,
-        reversed: bool,
-    },
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, GrayImage, Luma, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{find_effect, ImageEffect, ParamSpec, ParamValue, Params};
+
+/// How much of the image an effect needs to see to compute one output pixel
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tiling {
+    /// Only the pixel itself
+    PerPixel,
+    /// Pixels up to `halo` pixels away in any direction
+    Neighborhood { halo: u32 },
+    /// The whole row the pixel is in
+    Rows,
+    /// The whole column the pixel is in
+    Columns,
+    /// The whole image; the effect is never tiled
+    Whole,
+}
+
+/// Undeclared parameters holding the [`TileArea`] of a tile
+const TILE_X: &str = "tile_x";
+const TILE_Y: &str = "tile_y";
+const FULL_WIDTH: &str = "full_width";
+const FULL_HEIGHT: &str = "full_height";
+
+/// Where the image an effect runs on lies in the full image, for effects
+/// whose result depends on the position of a pixel
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileArea {
+    /// Position of the tile's top left pixel in the full image
+    pub x: u32,
+    pub y: u32,
+    pub full_width: u32,
+    pub full_height: u32,
+}
+
+impl TileArea {
+    /// Area of a `width`×`height` image an effect with `params` runs on: the
+    /// one set by [`Effect::on_tile`], or else the whole image
+    pub fn from_params(params: &Params, width: u32, height: u32) -> TileArea {
+        let get = |key, default| match params.get(key) {
+            Some(ParamValue::Int(value)) => *value as u32,
+            _ => default,
+        };
+        TileArea {
+            x: get(TILE_X, 0),
+            y: get(TILE_Y, 0),
+            full_width: get(FULL_WIDTH, width),
+            full_height: get(FULL_HEIGHT, height),
+        }
+    }
+}
+
+/// One step of an effect chain: a registered effect and its parameter values.
+///
+/// Serialized as `{"effect": "<id>", "params": {...}}`; parameters missing
+/// from a saved chain take their defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "SerializedEffect", into = "SerializedEffect")]
+pub struct Effect {
+    effect: Arc<dyn ImageEffect>,
+    params: Params,
 }
 
 impl Effect {
+    /// The effect with every parameter at its default
+    pub fn new(effect: Arc<dyn ImageEffect>) -> Effect {
+        let params = Params::defaults(effect.params());
+        Effect { effect, params }
+    }
+
+    pub fn id(&self) -> &'static str {
+        self.effect.id()
+    }
+
     pub fn name(&self) -> &'static str {
-        match self {
-            Effect::Or { .. } => "OR",
-            Effect::And { .. } => "AND",
-            Effect::Xor { .. } => "XOR",
-            Effect::Left { .. } => "Left",
-            Effect::Right { .. } => "Right",
-            Effect::Add { .. } => "Add",
-            Effect::Sub { .. } => "Subtract",
-            Effect::Mult { .. } => "Multiply",
-            Effect::Pow { .. } => "Power",
-            Effect::Div { .. } => "Divide",
-            Effect::Average { .. } => "Average",
-            Effect::Screen { .. } => "Screen",
-            Effect::Overlay { .. } => "Overlay",
-            Effect::Bloom { .. } => "Bloom",
-            Effect::Sort { .. } => "Sort",
+        self.effect.name()
+    }
+
+    /// Declarations of the effect's parameters
+    pub fn specs(&self) -> &'static [ParamSpec] {
+        self.effect.params()
+    }
+
+    pub fn spec(&self, key: &str) -> Option<&'static ParamSpec> {
+        self.specs().iter().find(|spec| spec.key == key)
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Set a declared parameter, clamped to its range. Unknown keys and
+    /// values of the wrong type are ignored.
+    pub fn set_param(&mut self, key: &str, value: ParamValue) {
+        let Some(spec) = self.spec(key) else {
+            return;
+        };
+        if let Some(value) = spec.validate(value) {
+            self.params.set(spec.key, value);
+        }
+    }
+
+    /// Take over the values of parameters this effect shares with `other`,
+    /// e.g. keep the color when switching from OR to XOR
+    pub fn inherit_params(&mut self, other: &Effect) {
+        for spec in self.specs() {
+            if let Some(value) = other.params.get(spec.key) {
+                self.set_param(spec.key, value.clone());
+            }
+        }
+    }
+
+    /// Copy of the effect with spatial parameters (in pixels) multiplied by
+    /// `scale`, and amounts per area by its square, for rendering on a
+    /// resized copy of the source image
+    pub fn scaled(&self, scale: f64) -> Effect {
+        let mut effect = self.clone();
+        for spec in self.specs() {
+            let factor = match (spec.in_pixels, spec.per_area) {
+                (true, _) => scale,
+                (_, true) => scale * scale,
+                _ => continue,
+            };
+            let scaled = match self.params.get(spec.key) {
+                Some(ParamValue::Float(value)) => ParamValue::Float(value * factor),
+                Some(ParamValue::Int(value)) => {
+                    ParamValue::Int((*value as f64 * factor).round() as i64)
+                }
+                _ => continue,
+            };
+            // Not clamped: a proxy may need a radius below the UI minimum
+            effect.params.set(spec.key, scaled);
+        }
+        self.effect.scale_params(&mut effect.params, scale);
+        effect
+    }
+
+    /// Problem with a parameter's value to show next to its editor
+    pub fn param_error(&self, key: &str) -> Option<String> {
+        self.effect.check_param(key, &self.params)
+    }
+
+    /// Whether the effect has thresholds to show a mask of
+    pub fn has_threshold(&self) -> bool {
+        self.effect.threshold(&self.params).is_some()
+    }
+
+    /// White where a pixel of the effect's input `image` falls within its
+    /// thresholds, black elsewhere; `None` if the effect has no thresholds
+    pub fn threshold_mask(&self, image: &RgbaImage) -> Option<GrayImage> {
+        let test = self.effect.threshold(&self.params)?;
+        Some(GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            Luma([if test(image.get_pixel(x, y)) { 255 } else { 0 }])
+        }))
+    }
+
+    pub fn tiling(&self) -> Tiling {
+        self.effect.tiling(&self.params)
+    }
+
+    /// Copy of the effect for running on a tile at `area` of the image
+    pub fn on_tile(&self, area: TileArea) -> Effect {
+        let mut effect = self.clone();
+        for (key, value) in [
+            (TILE_X, area.x),
+            (TILE_Y, area.y),
+            (FULL_WIDTH, area.full_width),
+            (FULL_HEIGHT, area.full_height),
+        ] {
+            effect.params.set(key, ParamValue::Int(value as i64));
+        }
+        effect
+    }
+
+    pub fn apply(&self, image: DynamicImage) -> Result<RgbaImage, ServerFnError> {
+        self.effect.apply(image, &self.params)
+    }
+}
+
+impl PartialEq for Effect {
+    fn eq(&self, other: &Effect) -> bool {
+        self.id() == other.id() && self.params == other.params
+    }
+}
+
+impl fmt::Debug for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Effect")
+            .field("id", &self.id())
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedEffect {
+    effect: String,
+    #[serde(default)]
+    params: BTreeMap<String, serde_json::Value>,
+}
+
+impl From<Effect> for SerializedEffect {
+    fn from(effect: Effect) -> Self {
+        let params = effect
+            .specs()
+            .iter()
+            .filter_map(|spec| {
+                let value = effect.params.get(spec.key)?;
+                Some((spec.key.to_string(), spec.serialize_value(value)))
+            })
+            .collect();
+        SerializedEffect {
+            effect: effect.id().to_string(),
+            params,
+        }
+    }
+}
+
+impl TryFrom<SerializedEffect> for Effect {
+    type Error = String;
+
+    fn try_from(serialized: SerializedEffect) -> Result<Self, Self::Error> {
+        let registered = find_effect(&serialized.effect)
+            .ok_or_else(|| format!("Unknown effect '{}'", serialized.effect))?;
+        let mut effect = Effect::new(registered);
+        for (key, json) in &serialized.params {
+            let spec = effect
+                .spec(key)
+                .ok_or_else(|| format!("Unknown parameter '{}' for {}", key, effect.name()))?;
+            effect.params.set(spec.key, spec.deserialize_value(json)?);
         }
+        Ok(effect)
     }
 }
@@ -0,0 +1,250 @@
+//! Tonal and color adjustments
+//!
+//! Most of these map every channel value independently, so they are built
+//! as lookup tables of the 256 possible values.
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, Rgb, RgbaImage};
+use std::f32::consts::FRAC_PI_4;
+
+use crate::backend::{per_pixel, EffectDefinition, ParamSpec, Params};
+use crate::utils::{
+    hsl_to_rgb, interpolate_curve, linear_to_srgb, luminance, rgb_to_hsl, srgb_to_linear,
+};
+
+/// Channels a levels or curves step applies to
+const CHANNEL: ParamSpec =
+    ParamSpec::choice("channel", "Channel", &["RGB", "Red", "Green", "Blue"], 0)
+        .with_help("Add one step per channel to adjust them separately");
+
+pub const LEVELS: EffectDefinition = EffectDefinition {
+    id: "levels",
+    name: "Levels",
+    params: &[
+        CHANNEL,
+        ParamSpec::int("input_black", "Input Black", 0, 255, 0),
+        ParamSpec::int("input_white", "Input White", 0, 255, 255),
+        ParamSpec::float("gamma", "Gamma", 0.1, 10.0, 0.01, 1.0)
+            .with_help("Values above 1 brighten the midtones"),
+        ParamSpec::int("output_black", "Output Black", 0, 255, 0),
+        ParamSpec::int("output_white", "Output White", 0, 255, 255),
+    ],
+    apply: apply_levels,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const CURVES: EffectDefinition = EffectDefinition {
+    id: "curves",
+    name: "Curves",
+    params: &[
+        CHANNEL,
+        ParamSpec::curve("curve", "Curve").with_help(
+            "Drag points to move them, click to add one, double-click a point to remove it",
+        ),
+    ],
+    apply: apply_curves,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const HUE_SATURATION: EffectDefinition = EffectDefinition {
+    id: "hue_saturation",
+    name: "Hue / Saturation",
+    params: &[
+        ParamSpec::float("hue", "Hue Rotation", -180.0, 180.0, 1.0, 0.0),
+        ParamSpec::float("saturation", "Saturation (%)", -100.0, 100.0, 1.0, 0.0),
+        ParamSpec::float("vibrance", "Vibrance (%)", -100.0, 100.0, 1.0, 0.0)
+            .with_help("Like saturation, but mostly affects colors that are still muted"),
+    ],
+    apply: apply_hue_saturation,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const BRIGHTNESS_CONTRAST: EffectDefinition = EffectDefinition {
+    id: "brightness_contrast",
+    name: "Brightness / Contrast",
+    params: &[
+        ParamSpec::float("brightness", "Brightness", -100.0, 100.0, 1.0, 0.0),
+        ParamSpec::float("contrast", "Contrast", -100.0, 100.0, 1.0, 0.0)
+            .with_help("-100 flattens the image to gray, 100 thresholds it"),
+    ],
+    apply: apply_brightness_contrast,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const EXPOSURE: EffectDefinition = EffectDefinition {
+    id: "exposure",
+    name: "Exposure",
+    params: &[
+        ParamSpec::float("stops", "Exposure (stops)", -10.0, 10.0, 0.1, 0.0)
+            .with_help("Each stop doubles or halves the light, in linear color"),
+    ],
+    apply: apply_exposure,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const GAMMA: EffectDefinition = EffectDefinition {
+    id: "gamma",
+    name: "Gamma",
+    params: &[ParamSpec::float("gamma", "Gamma", 0.1, 10.0, 0.01, 1.0)
+        .with_help("Values above 1 brighten the midtones, values below darken them")],
+    apply: apply_gamma,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+type Table = [u8; 256];
+
+/// Lookup table of `map`, which takes and returns values in 0-1
+fn table(map: impl Fn(f32) -> f32) -> Table {
+    std::array::from_fn(|value| {
+        (map(value as f32 / 255.0) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    })
+}
+
+/// Tables for the channels chosen by `channel`, leaving the others as they
+/// are
+fn channel_tables(params: &Params, table: Table) -> [Table; 3] {
+    let identity = std::array::from_fn(|value| value as u8);
+    match params.choice(CHANNEL.key) {
+        0 => [table; 3],
+        channel => std::array::from_fn(|index| {
+            if index + 1 == channel {
+                table
+            } else {
+                identity
+            }
+        }),
+    }
+}
+
+fn apply_tables(image: DynamicImage, tables: &[Table; 3]) -> RgbaImage {
+    let mut image = image.into_rgba8();
+    for pixel in image.pixels_mut() {
+        for (value, table) in pixel.0.iter_mut().zip(tables) {
+            *value = table[*value as usize];
+        }
+    }
+    image
+}
+
+fn apply_levels(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let input_black = params.int("input_black") as f32 / 255.0;
+    let input_white = params.int("input_white") as f32 / 255.0;
+    let gamma = params.float("gamma").max(0.01) as f32;
+    let output_black = params.int("output_black") as f32 / 255.0;
+    let output_white = params.int("output_white") as f32 / 255.0;
+
+    let levels = table(|value| {
+        let range = input_white - input_black;
+        let t = if range > 0.0 {
+            ((value - input_black) / range).clamp(0.0, 1.0)
+        } else if value >= input_black {
+            1.0
+        } else {
+            0.0
+        };
+        output_black + t.powf(1.0 / gamma) * (output_white - output_black)
+    });
+    Ok(apply_tables(image, &channel_tables(params, levels)))
+}
+
+fn apply_curves(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let points = params.curve("curve");
+    let curve = table(|value| interpolate_curve(points, value as f64) as f32);
+    Ok(apply_tables(image, &channel_tables(params, curve)))
+}
+
+fn apply_hue_saturation(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let rotation = params.float("hue") as f32;
+    let saturation = 1.0 + params.float("saturation") as f32 / 100.0;
+    let vibrance = params.float("vibrance") as f32 / 100.0;
+
+    let mut image = image.into_rgba8();
+    for pixel in image.pixels_mut() {
+        let mut color = Rgb([pixel.0[0], pixel.0[1], pixel.0[2]]);
+        if rotation != 0.0 {
+            let (hue, s, l) = rgb_to_hsl(color);
+            color = hsl_to_rgb(hue + rotation, s, l);
+        }
+
+        // Scale the distance of each channel from the pixel's gray; vibrance
+        // scales less the more saturated the pixel already is
+        let [r, g, b] = color.0.map(f32::from);
+        let gray = luminance([r, g, b]);
+        let current = (r.max(g).max(b) - r.min(g).min(b)) / 255.0;
+        let factor = saturation * (1.0 + vibrance * (1.0 - current));
+        for (value, channel) in pixel.0.iter_mut().zip([r, g, b]) {
+            *value = (gray + (channel - gray) * factor).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(image)
+}
+
+fn apply_brightness_contrast(
+    image: DynamicImage,
+    params: &Params,
+) -> Result<RgbaImage, ServerFnError> {
+    let table = brightness_contrast_table(params.float("brightness"), params.float("contrast"));
+    Ok(apply_tables(image, &[table; 3]))
+}
+
+/// Table for brightness and contrast, both in -100 to 100
+fn brightness_contrast_table(brightness: f64, contrast: f64) -> Table {
+    // A vertical slope thresholds at mid-gray, where brightness no longer
+    // matters; the tangent would overflow and turn negative instead
+    if contrast >= 100.0 {
+        return table(|value| if value >= 0.5 { 1.0 } else { 0.0 });
+    }
+    let brightness = brightness as f32 / 100.0;
+    // Slope around mid-gray: 0 at -100, 1 at 0, vertical at 100
+    let slope = ((contrast as f32 / 100.0 + 1.0) * FRAC_PI_4).tan();
+    table(|value| (value - 0.5) * slope + 0.5 + brightness)
+}
+
+fn apply_exposure(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let scale = 2f32.powf(params.float("stops") as f32);
+    let tables = [table(|value| linear_to_srgb(srgb_to_linear(value * 255.0) * scale) / 255.0); 3];
+    Ok(apply_tables(image, &tables))
+}
+
+fn apply_gamma(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let gamma = params.float("gamma").max(0.01) as f32;
+    let tables = [table(|value| value.powf(1.0 / gamma)); 3];
+    Ok(apply_tables(image, &tables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contrast_extremes() {
+        // -100 flattens to mid-gray, shifted by the brightness
+        assert!(brightness_contrast_table(0.0, -100.0)
+            .iter()
+            .all(|&value| value == 128));
+        assert!(brightness_contrast_table(20.0, -100.0)
+            .iter()
+            .all(|&value| value == 179));
+
+        let identity: Table = std::array::from_fn(|value| value as u8);
+        assert_eq!(brightness_contrast_table(0.0, 0.0), identity);
+
+        for brightness in [-100.0, 0.0, 100.0] {
+            let threshold = brightness_contrast_table(brightness, 100.0);
+            assert!(threshold[..128].iter().all(|&value| value == 0));
+            assert!(threshold[128..].iter().all(|&value| value == 255));
+        }
+        // Just below the threshold the slope is steep but still rising
+        let steep = brightness_contrast_table(0.0, 99.0);
+        assert!(steep.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!((steep[0], steep[255]), (0, 255));
+    }
+}
@@ -0,0 +1,546 @@
+//! Convolution effects: blurs, sharpening, emboss and custom kernels
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+
+use super::edges::{Edges, EDGES};
+use super::mark_error;
+use crate::backend::{EffectDefinition, ImageEffect, ParamSpec, Params, Tiling};
+use crate::utils::luminance;
+
+/// Largest custom kernel, so a typo can't make a step take minutes
+const MAX_KERNEL_SIZE: usize = 31;
+
+const DEFAULT_KERNEL: &str = "\
+# Sharpen
+ 0 -1  0
+-1  5 -1
+ 0 -1  0";
+
+const KERNEL_HELP: &str = "One row of weights per line, separated by spaces or commas. \
+The kernel must be square with an odd size, up to 31×31. Weights can be fractions like 1/16. \
+`#` starts a comment.";
+
+pub const GAUSSIAN_BLUR: EffectDefinition = EffectDefinition {
+    id: "gaussian_blur",
+    name: "Gaussian Blur",
+    params: &[
+        ParamSpec::float("sigma", "Sigma", 0.1, 200.0, 0.1, 2.0).in_pixels(),
+        EDGES,
+    ],
+    apply: apply_gaussian_blur,
+    tiling: gaussian_tiling,
+    threshold: None,
+};
+
+pub const BOX_BLUR: EffectDefinition = EffectDefinition {
+    id: "box_blur",
+    name: "Box Blur",
+    params: &[
+        ParamSpec::int("radius", "Radius", 1, 500, 2).in_pixels(),
+        EDGES,
+    ],
+    apply: apply_box_blur,
+    tiling: box_tiling,
+    threshold: None,
+};
+
+pub const UNSHARP_MASK: EffectDefinition = EffectDefinition {
+    id: "unsharp_mask",
+    name: "Unsharp Mask",
+    params: &[
+        ParamSpec::float("sigma", "Sigma", 0.1, 200.0, 0.1, 2.0).in_pixels(),
+        ParamSpec::float("amount", "Amount", 0.0, 10.0, 0.1, 1.0),
+        ParamSpec::int("threshold", "Threshold", 0, 255, 0)
+            .with_help("Only sharpen where a channel differs from its blur by at least this much"),
+        EDGES,
+    ],
+    apply: apply_unsharp_mask,
+    tiling: gaussian_tiling,
+    threshold: None,
+};
+
+pub const EMBOSS: EffectDefinition = EffectDefinition {
+    id: "emboss",
+    name: "Emboss",
+    params: &[
+        ParamSpec::float("angle", "Light Angle", 0.0, 360.0, 1.0, 135.0),
+        ParamSpec::float("strength", "Strength", 0.1, 10.0, 0.1, 1.0),
+        ParamSpec::bool("keep_colors", "Keep Colors", false),
+        EDGES,
+    ],
+    apply: apply_emboss,
+    tiling: emboss_tiling,
+    threshold: None,
+};
+
+const KERNEL_PARAMS: &[ParamSpec] = &[
+    ParamSpec::text("kernel", "Kernel", 5, DEFAULT_KERNEL)
+        .with_help(KERNEL_HELP)
+        .saveable(),
+    ParamSpec::bool("normalize", "Normalize", true)
+        .with_help("Divide by the sum of the weights, unless it is 0"),
+    ParamSpec::float("bias", "Bias", -255.0, 255.0, 1.0, 0.0),
+    ParamSpec::bool("alpha", "Convolve Alpha", false),
+    EDGES,
+];
+
+/// Convolution with a kernel typed in by the user
+pub struct KernelEffect;
+
+impl ImageEffect for KernelEffect {
+    fn id(&self) -> &'static str {
+        "convolve"
+    }
+
+    fn name(&self) -> &'static str {
+        "Convolve"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        KERNEL_PARAMS
+    }
+
+    fn apply(&self, image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+        let mut kernel = Kernel::parse(params.text("kernel"))
+            .map_err(|e| ServerFnError::new(format!("Invalid kernel: {}", e.message)))?;
+        if params.bool("normalize") {
+            kernel.normalize();
+        }
+
+        let image = image.into_rgba8();
+        let source = Buffer::from_image(&image, false);
+        let mut output = source.convolve(&kernel, Edges::from_params(params));
+        let bias = params.float("bias") as f32;
+        let alpha = params.bool("alpha");
+        for (pixel, original) in output.pixels.iter_mut().zip(&source.pixels) {
+            for channel in &mut pixel[..3] {
+                *channel += bias;
+            }
+            if !alpha {
+                pixel[3] = original[3];
+            }
+        }
+        Ok(output.to_image(false))
+    }
+
+    fn tiling(&self, params: &Params) -> Tiling {
+        match Kernel::parse(params.text("kernel")) {
+            Ok(kernel) => neighborhood(params, kernel.radius() as u32),
+            Err(_) => Tiling::Whole,
+        }
+    }
+
+    fn check_param(&self, key: &str, params: &Params) -> Option<String> {
+        if key != "kernel" {
+            return None;
+        }
+        let source = params.text(key);
+        let error = Kernel::parse(source).err()?;
+        Some(mark_error(source, error.line, error.column, &error.message))
+    }
+}
+
+fn apply_gaussian_blur(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let kernel = gaussian_kernel(params.float("sigma"));
+    let image = image.into_rgba8();
+    // Premultiplied, so transparent pixels don't bleed their color
+    let output = Buffer::from_image(&image, true).blur(&kernel, Edges::from_params(params));
+    Ok(output.to_image(true))
+}
+
+fn apply_box_blur(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let size = params.int("radius").max(0) as usize * 2 + 1;
+    let kernel = vec![1.0 / size as f32; size];
+    let image = image.into_rgba8();
+    let output = Buffer::from_image(&image, true).blur(&kernel, Edges::from_params(params));
+    Ok(output.to_image(true))
+}
+
+fn apply_unsharp_mask(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let kernel = gaussian_kernel(params.float("sigma"));
+    let amount = params.float("amount") as f32;
+    let threshold = params.int("threshold") as f32;
+
+    let image = image.into_rgba8();
+    let mut output = Buffer::from_image(&image, false);
+    let blurred = output.blur(&kernel, Edges::from_params(params));
+    for (pixel, blurred) in output.pixels.iter_mut().zip(&blurred.pixels) {
+        for (channel, blurred) in pixel[..3].iter_mut().zip(blurred) {
+            let detail = *channel - blurred;
+            if detail.abs() >= threshold {
+                *channel += amount * detail;
+            }
+        }
+    }
+    Ok(output.to_image(false))
+}
+
+fn apply_emboss(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let angle = params.float("angle").to_radians();
+    let strength = params.float("strength");
+    let keep_colors = params.bool("keep_colors");
+
+    // Weigh each neighbor by how far it lies towards the light (y points down)
+    let (light_x, light_y) = (angle.cos(), -angle.sin());
+    let mut weights = Vec::with_capacity(9);
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            weights.push((strength * (dx as f64 * light_x + dy as f64 * light_y)) as f32);
+        }
+    }
+    weights[4] = if keep_colors { 1.0 } else { 0.0 };
+    let kernel = Kernel { size: 3, weights };
+
+    let image = image.into_rgba8();
+    let source = Buffer::from_image(&image, false);
+    let mut output = source.convolve(&kernel, Edges::from_params(params));
+    for (pixel, original) in output.pixels.iter_mut().zip(&source.pixels) {
+        if !keep_colors {
+            // A gray relief, lit from the chosen angle
+            let relief = luminance([pixel[0], pixel[1], pixel[2]]) + 128.0;
+            pixel[..3].fill(relief);
+        }
+        pixel[3] = original[3];
+    }
+    Ok(output.to_image(false))
+}
+
+fn gaussian_tiling(params: &Params) -> Tiling {
+    neighborhood(params, gaussian_radius(params.float("sigma")) as u32)
+}
+
+fn box_tiling(params: &Params) -> Tiling {
+    neighborhood(params, params.int("radius").max(0) as u32)
+}
+
+fn emboss_tiling(params: &Params) -> Tiling {
+    neighborhood(params, 1)
+}
+
+/// Tiling for a kernel reaching `radius` pixels out
+fn neighborhood(params: &Params, radius: u32) -> Tiling {
+    if Edges::from_params(params).needs_whole_image() {
+        Tiling::Whole
+    } else {
+        Tiling::Neighborhood { halo: radius }
+    }
+}
+
+/// Past three sigmas the weights are negligible
+fn gaussian_radius(sigma: f64) -> usize {
+    if sigma > 0.0 {
+        (sigma * 3.0).ceil() as usize
+    } else {
+        0
+    }
+}
+
+/// Normalized one-dimensional Gaussian kernel
+fn gaussian_kernel(sigma: f64) -> Vec<f32> {
+    let radius = gaussian_radius(sigma) as i64;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f64 / (2.0 * sigma * sigma)).exp() as f32)
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= sum);
+    kernel
+}
+
+/// Square kernel with an odd size, weights row by row
+#[derive(Clone, PartialEq, Debug)]
+struct Kernel {
+    size: usize,
+    weights: Vec<f32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct KernelError {
+    message: String,
+    /// 1-based position of the problem in the kernel text
+    line: usize,
+    column: usize,
+}
+
+impl Kernel {
+    fn parse(source: &str) -> Result<Kernel, KernelError> {
+        let mut rows: Vec<(usize, Vec<f32>)> = Vec::new();
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut row = Vec::new();
+            let mut start = 0;
+            for (index, c) in line.char_indices().chain([(line.len(), ' ')]) {
+                if !(c.is_whitespace() || c == ',') {
+                    continue;
+                }
+                let token = &line[start..index];
+                if !token.is_empty() {
+                    row.push(parse_weight(token).ok_or_else(|| KernelError {
+                        message: format!("Expected a number, found '{}'", token),
+                        line: line_index + 1,
+                        column: line[..start].chars().count() + 1,
+                    })?);
+                }
+                start = index + c.len_utf8();
+            }
+            if !row.is_empty() {
+                rows.push((line_index + 1, row));
+            }
+        }
+
+        let Some((_, first)) = rows.first() else {
+            return Err(KernelError {
+                message: "The kernel has no weights".to_string(),
+                line: 1,
+                column: 1,
+            });
+        };
+        let size = first.len();
+        for (line, row) in &rows {
+            if row.len() != size {
+                return Err(KernelError {
+                    message: format!(
+                        "Every row needs {} weights like the first one, this one has {}",
+                        size,
+                        row.len()
+                    ),
+                    line: *line,
+                    column: 1,
+                });
+            }
+        }
+
+        let (last_line, _) = rows[rows.len() - 1];
+        let problem = if rows.len() != size {
+            Some(format!(
+                "The kernel has {} rows of {} weights, but it must be square",
+                rows.len(),
+                size
+            ))
+        } else if size % 2 == 0 {
+            Some(format!(
+                "The kernel is {}×{}, but it needs an odd size to have a center",
+                size, size
+            ))
+        } else if size > MAX_KERNEL_SIZE {
+            Some(format!(
+                "The kernel is {}×{}, larger than the maximum of {}×{}",
+                size, size, MAX_KERNEL_SIZE, MAX_KERNEL_SIZE
+            ))
+        } else {
+            None
+        };
+        if let Some(message) = problem {
+            return Err(KernelError {
+                message,
+                line: last_line,
+                column: 1,
+            });
+        }
+
+        Ok(Kernel {
+            size,
+            weights: rows.into_iter().flat_map(|(_, row)| row).collect(),
+        })
+    }
+
+    fn radius(&self) -> usize {
+        self.size / 2
+    }
+
+    /// Scale the weights to sum to 1, unless they sum to 0 (as for edge
+    /// detection)
+    fn normalize(&mut self) {
+        let sum: f32 = self.weights.iter().sum();
+        if sum.abs() > f32::EPSILON {
+            self.weights.iter_mut().for_each(|weight| *weight /= sum);
+        }
+    }
+}
+
+/// A number or a fraction like `1/16`
+fn parse_weight(token: &str) -> Option<f32> {
+    let weight = match token.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.parse::<f32>().ok()? / denominator.parse::<f32>().ok()?
+        }
+        None => token.parse::<f32>().ok()?,
+    };
+    weight.is_finite().then_some(weight)
+}
+
+/// RGBA values as floats on the 0-255 scale, row by row
+struct Buffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Buffer {
+    fn from_image(image: &RgbaImage, premultiply: bool) -> Buffer {
+        let pixels = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0.map(f32::from);
+                if premultiply {
+                    let alpha = a / 255.0;
+                    [r * alpha, g * alpha, b * alpha, a]
+                } else {
+                    [r, g, b, a]
+                }
+            })
+            .collect();
+        Buffer {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        }
+    }
+
+    fn to_image(&self, unpremultiply: bool) -> RgbaImage {
+        let mut raw = Vec::with_capacity(self.pixels.len() * 4);
+        for &[r, g, b, a] in &self.pixels {
+            let a = a.clamp(0.0, 255.0);
+            let color = if unpremultiply {
+                if a > 0.0 {
+                    let scale = 255.0 / a;
+                    [r * scale, g * scale, b * scale]
+                } else {
+                    [0.0; 3]
+                }
+            } else {
+                [r, g, b]
+            };
+            raw.extend(color.map(|channel| channel.round().clamp(0.0, 255.0) as u8));
+            raw.push(a.round() as u8);
+        }
+        RgbaImage::from_raw(self.width, self.height, raw).expect("buffer matches its size")
+    }
+
+    fn pixel(&self, x: i64, y: i64, edges: Edges) -> [f32; 4] {
+        match (edges.index(x, self.width), edges.index(y, self.height)) {
+            (Some(x), Some(y)) => self.pixels[y * self.width as usize + x],
+            _ => [0.0; 4],
+        }
+    }
+
+    /// Separable convolution with the same centered kernel along rows and
+    /// columns
+    fn blur(&self, kernel: &[f32], edges: Edges) -> Buffer {
+        self.convolve_line(kernel, true, edges)
+            .convolve_line(kernel, false, edges)
+    }
+
+    fn convolve_line(&self, kernel: &[f32], horizontal: bool, edges: Edges) -> Buffer {
+        let radius = (kernel.len() / 2) as i64;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let mut sum = [0.0f32; 4];
+                for (offset, weight) in (-radius..=radius).zip(kernel) {
+                    let sample = if horizontal {
+                        self.pixel(x + offset, y, edges)
+                    } else {
+                        self.pixel(x, y + offset, edges)
+                    };
+                    for (total, value) in sum.iter_mut().zip(sample) {
+                        *total += weight * value;
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+        Buffer {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    fn convolve(&self, kernel: &Kernel, edges: Edges) -> Buffer {
+        let radius = kernel.radius() as i64;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for y in 0..self.height as i64 {
+            for x in 0..self.width as i64 {
+                let mut sum = [0.0f32; 4];
+                for (index, weight) in kernel.weights.iter().enumerate() {
+                    if *weight == 0.0 {
+                        continue;
+                    }
+                    let dx = (index % kernel.size) as i64 - radius;
+                    let dy = (index / kernel.size) as i64 - radius;
+                    let sample = self.pixel(x + dx, y + dy, edges);
+                    for (total, value) in sum.iter_mut().zip(sample) {
+                        *total += weight * value;
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+        Buffer {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> KernelError {
+        Kernel::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn parses_numbers_fractions_and_comments() {
+        let kernel =
+            Kernel::parse("1/16 2/16, 1/16\n2/16 4/16 2/16 # center\n\n1/16 2/16 1/16").unwrap();
+        assert_eq!(kernel.size, 3);
+        assert_eq!(kernel.weights[4], 0.25);
+        assert_eq!(kernel.weights.iter().sum::<f32>(), 1.0);
+
+        let kernel = Kernel::parse("-1 -1 -1\n-1 8 -1\n-1 -1 -1").unwrap();
+        assert_eq!(kernel.radius(), 1);
+        assert_eq!(kernel.weights[4], 8.0);
+        assert_eq!(Kernel::parse("2.5").unwrap().weights, [2.5]);
+    }
+
+    #[test]
+    fn rejects_bad_shapes() {
+        let uneven = error("1 2 1\n2 4\n1 2 1");
+        assert!(uneven.message.contains("needs 3 weights"));
+        assert_eq!((uneven.line, uneven.column), (2, 1));
+
+        let not_square = error("1 2 1\n2 4 2");
+        assert!(not_square.message.contains("must be square"));
+        assert_eq!(not_square.line, 2);
+
+        let even = error("1 1\n1 1");
+        assert!(even.message.contains("odd size"));
+
+        let row = vec!["1"; MAX_KERNEL_SIZE + 2].join(" ");
+        let too_large = error(&vec![row; MAX_KERNEL_SIZE + 2].join("\n"));
+        assert!(too_large.message.contains("larger than the maximum"));
+
+        let empty = error("  \n# only a comment\n");
+        assert_eq!(empty.message, "The kernel has no weights");
+    }
+
+    #[test]
+    fn points_at_the_bad_weight() {
+        let bad = error("1 2 1\n2 four 2\n1 2 1");
+        assert_eq!(bad.message, "Expected a number, found 'four'");
+        assert_eq!((bad.line, bad.column), (2, 3));
+
+        let bad = error("1 1 1\n1 1 1\n1 1 1/0");
+        assert_eq!((bad.line, bad.column), (3, 5));
+        // Columns count characters, not bytes
+        let bad = error("1\u{3000}x");
+        assert_eq!((bad.line, bad.column), (1, 3));
+        let bad = error("1, 1/x, 1");
+        assert_eq!((bad.line, bad.column), (1, 4));
+    }
+}
@@ -0,0 +1,377 @@
+//! Effects that work on encoded bytes rather than pixels: lossy
+//! recompression and seeded corruption of an encoded stream
+
+use dioxus::prelude::ServerFnError;
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, RgbImage, RgbaImage};
+use std::io::Cursor;
+
+use super::random::{Rng, SEED};
+use crate::backend::{whole_image, EffectDefinition, ParamSpec, Params};
+
+/// Bytes per pixel of the RGB data that gets encoded
+const BYTES_PER_PIXEL: usize = 3;
+
+pub const JPEG_RECOMPRESS: EffectDefinition = EffectDefinition {
+    id: "jpeg_recompress",
+    name: "JPEG Recompress",
+    params: &[
+        ParamSpec::int("quality", "Quality", 1, 100, 20),
+        ParamSpec::int("iterations", "Iterations", 1, 50, 5)
+            .with_help("Times the image is encoded and decoded again"),
+    ],
+    apply: apply_jpeg_recompress,
+    tiling: whole_image,
+    threshold: None,
+};
+
+pub const DATABEND: EffectDefinition = EffectDefinition {
+    id: "databend",
+    name: "Databend",
+    params: &[
+        ParamSpec::choice("format", "Stream", &["JPEG", "BMP", "PNG Filtered"], 0).with_help(
+            "JPEG corrupts the compressed scan data, BMP the raw pixels, and PNG Filtered \
+             the filtered rows, so errors smear along the row",
+        ),
+        ParamSpec::choice(
+            "corruption",
+            "Corruption",
+            &["Random Byte", "Bit Flip", "Zero"],
+            0,
+        ),
+        ParamSpec::int("bytes", "Corrupted Bytes", 1, 10_000, 10)
+            .per_area()
+            .with_help(
+                "Counted at full resolution. Previews corrupt proportionally fewer bytes at \
+                 the same relative places, so they only approximate the export.",
+            ),
+        ParamSpec::int("quality", "JPEG Quality", 1, 100, 80),
+        ParamSpec::choice(
+            "png_filter",
+            "PNG Filter",
+            &["Sub", "Up", "Average", "Paeth"],
+            3,
+        ),
+        SEED,
+    ],
+    apply: apply_databend,
+    tiling: whole_image,
+    threshold: None,
+};
+
+fn apply_jpeg_recompress(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let source = image.into_rgba8();
+    let quality = params.int("quality") as u8;
+    let mut rgb = DynamicImage::ImageRgba8(source.clone()).into_rgb8();
+    for _ in 0..params.int("iterations") {
+        let bytes = encode_jpeg(&rgb, quality)?;
+        rgb = decode(&bytes, ImageFormat::Jpeg, &rgb)?;
+    }
+    Ok(with_alpha(&rgb, &source))
+}
+
+fn apply_databend(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let source = image.into_rgba8();
+    // Nothing to corrupt, and PNG rows of no bytes can't be split up
+    if source.is_empty() {
+        return Ok(source);
+    }
+    let rgb = DynamicImage::ImageRgba8(source.clone()).into_rgb8();
+    let mut rng = Rng::new(params.int("seed"));
+    let corruption = params.choice("corruption");
+    let count = params.int("bytes").max(1) as usize;
+
+    let output = match params.choice("format") {
+        0 => {
+            let mut bytes = encode_jpeg(&rgb, params.int("quality") as u8)?;
+            // Keep the headers intact so the decoder still knows the layout
+            let start = jpeg_scan_start(&bytes)
+                .ok_or_else(|| ServerFnError::new("Encoded JPEG has no scan data"))?;
+            let end = bytes.len().saturating_sub(2);
+            corrupt_jpeg_scan(&mut bytes[start..end], count, corruption, &mut rng);
+            decode(&bytes, ImageFormat::Jpeg, &rgb)?
+        }
+        1 => {
+            let mut bytes = Vec::new();
+            BmpEncoder::new(&mut bytes)
+                .encode(
+                    rgb.as_raw(),
+                    rgb.width(),
+                    rgb.height(),
+                    image::ExtendedColorType::Rgb8,
+                )
+                .map_err(|e| ServerFnError::new(format!("Failed to encode BMP: {}", e)))?;
+            let start = bmp_pixel_offset(&bytes)
+                .ok_or_else(|| ServerFnError::new("Encoded BMP has no pixel data"))?;
+            corrupt(&mut bytes[start..], count, corruption, &mut rng);
+            decode(&bytes, ImageFormat::Bmp, &rgb)?
+        }
+        _ => {
+            let filter = params.choice("png_filter");
+            let row_length = rgb.width() as usize * BYTES_PER_PIXEL;
+            let mut filtered = png_filter(rgb.as_raw(), row_length, filter);
+            corrupt(&mut filtered, count, corruption, &mut rng);
+            let raw = png_unfilter(&filtered, row_length, filter);
+            RgbImage::from_raw(rgb.width(), rgb.height(), raw)
+                .ok_or_else(|| ServerFnError::new("Unfiltered data has the wrong size"))?
+        }
+    };
+    Ok(with_alpha(&output, &source))
+}
+
+fn encode_jpeg(image: &RgbImage, quality: u8) -> Result<Vec<u8>, ServerFnError> {
+    let mut bytes = Vec::new();
+    JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100))
+        .encode_image(image)
+        .map_err(|e| ServerFnError::new(format!("Failed to encode JPEG: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Decode possibly corrupted bytes, making sure the result has the size of
+/// `original`. The corruption leaves headers alone, and the decoder is
+/// limited to the original size so damaged data can't make it allocate more.
+fn decode(
+    bytes: &[u8],
+    format: ImageFormat,
+    original: &RgbImage,
+) -> Result<RgbImage, ServerFnError> {
+    let name = format!("{:?}", format).to_uppercase();
+    let failed = |reason: String| {
+        ServerFnError::new(format!(
+            "The corrupted {} could not be decoded ({}). Try another seed or fewer corrupted bytes.",
+            name, reason
+        ))
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(original.width());
+    limits.max_image_height = Some(original.height());
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let decoded = reader
+        .decode()
+        .map_err(|e| failed(e.to_string()))?
+        .into_rgb8();
+
+    if decoded.dimensions() != original.dimensions() {
+        return Err(failed(format!(
+            "it decoded to {}×{} instead of {}×{}",
+            decoded.width(),
+            decoded.height(),
+            original.width(),
+            original.height()
+        )));
+    }
+    Ok(decoded)
+}
+
+/// Put the alpha channel of `source` back on processed RGB data
+fn with_alpha(rgb: &RgbImage, source: &RgbaImage) -> RgbaImage {
+    let mut output = source.clone();
+    for (pixel, color) in output.pixels_mut().zip(rgb.pixels()) {
+        pixel.0[..3].copy_from_slice(&color.0);
+    }
+    output
+}
+
+/// Damage `count` seeded positions of `bytes`, in the way chosen by the
+/// `corruption` option. Positions are picked as fractions of the length, so
+/// streams of different sizes are damaged in the same relative places.
+fn corrupt(bytes: &mut [u8], count: usize, corruption: usize, rng: &mut Rng) {
+    corrupt_where(bytes, count, corruption, rng, false);
+}
+
+/// Like [`corrupt`] for entropy-coded JPEG data, where 0xFF starts a marker:
+/// bytes that are or follow 0xFF are left alone and 0xFF is never written,
+/// so the decoder still finds the structure of the file
+fn corrupt_jpeg_scan(bytes: &mut [u8], count: usize, corruption: usize, rng: &mut Rng) {
+    corrupt_where(bytes, count, corruption, rng, true);
+}
+
+fn corrupt_where(
+    bytes: &mut [u8],
+    count: usize,
+    corruption: usize,
+    rng: &mut Rng,
+    keep_markers: bool,
+) {
+    if bytes.is_empty() {
+        return;
+    }
+    for _ in 0..count {
+        let index = ((rng.next_f64() * bytes.len() as f64) as usize).min(bytes.len() - 1);
+        let random = rng.next_u64();
+        if keep_markers && (bytes[index] == 0xff || index > 0 && bytes[index - 1] == 0xff) {
+            continue;
+        }
+        let value = match corruption {
+            0 => random as u8,
+            1 => bytes[index] ^ (1 << (random % 8)),
+            _ => 0,
+        };
+        bytes[index] = if keep_markers && value == 0xff {
+            0xfe
+        } else {
+            value
+        };
+    }
+}
+
+/// Offset just past the start-of-scan header, where the entropy-coded data
+/// begins
+fn jpeg_scan_start(bytes: &[u8]) -> Option<usize> {
+    let marker = bytes.windows(2).position(|pair| pair == [0xff, 0xda])?;
+    let length = u16::from_be_bytes([*bytes.get(marker + 2)?, *bytes.get(marker + 3)?]);
+    let start = marker + 2 + length as usize;
+    (start < bytes.len()).then_some(start)
+}
+
+/// Offset of the pixel array, from the BMP file header
+fn bmp_pixel_offset(bytes: &[u8]) -> Option<usize> {
+    let offset = u32::from_le_bytes(bytes.get(10..14)?.try_into().ok()?) as usize;
+    (offset < bytes.len()).then_some(offset)
+}
+
+/// Apply one PNG filter type to every row, like a PNG encoder does before
+/// compressing. `filter` is 0 for Sub, 1 for Up, 2 for Average, 3 for Paeth.
+fn png_filter(raw: &[u8], row_length: usize, filter: usize) -> Vec<u8> {
+    let mut filtered = Vec::with_capacity(raw.len());
+    for (row_index, row) in raw.chunks(row_length).enumerate() {
+        let prior = row_index
+            .checked_sub(1)
+            .map(|previous| &raw[previous * row_length..row_index * row_length]);
+        for (i, &value) in row.iter().enumerate() {
+            let predicted = predict(row, prior, i, filter);
+            filtered.push(value.wrapping_sub(predicted));
+        }
+    }
+    filtered
+}
+
+/// Undo [`png_filter`]; errors in the filtered data carry over to the
+/// following pixels
+fn png_unfilter(filtered: &[u8], row_length: usize, filter: usize) -> Vec<u8> {
+    let mut raw = vec![0u8; filtered.len()];
+    for row_index in 0..filtered.len().div_ceil(row_length.max(1)) {
+        let start = row_index * row_length;
+        let end = (start + row_length).min(filtered.len());
+        let (done, rest) = raw.split_at_mut(start);
+        let prior = row_index
+            .checked_sub(1)
+            .map(|previous| &done[previous * row_length..]);
+        let row = &mut rest[..end - start];
+        for i in 0..row.len() {
+            let predicted = predict(row, prior, i, filter);
+            row[i] = filtered[start + i].wrapping_add(predicted);
+        }
+    }
+    raw
+}
+
+/// The PNG predictor for byte `i` of `row` from the bytes before it
+fn predict(row: &[u8], prior: Option<&[u8]>, i: usize, filter: usize) -> u8 {
+    let left = i.checked_sub(BYTES_PER_PIXEL).map_or(0, |left| row[left]) as i16;
+    let up = prior.map_or(0, |prior| prior[i]) as i16;
+    let up_left = match (prior, i.checked_sub(BYTES_PER_PIXEL)) {
+        (Some(prior), Some(left)) => prior[left] as i16,
+        _ => 0,
+    };
+    let predicted = match filter {
+        0 => left,
+        1 => up,
+        2 => (left + up) / 2,
+        _ => {
+            let estimate = left + up - up_left;
+            let (to_left, to_up, to_up_left) = (
+                (estimate - left).abs(),
+                (estimate - up).abs(),
+                (estimate - up_left).abs(),
+            );
+            if to_left <= to_up && to_left <= to_up_left {
+                left
+            } else if to_up <= to_up_left {
+                up
+            } else {
+                up_left
+            }
+        }
+    };
+    predicted as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ParamValue;
+    use image::Rgba;
+
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            let hash = (x * 7919 + y * 104_729).wrapping_mul(2_654_435_761);
+            Rgba([(hash >> 24) as u8, (x * 8) as u8, (y * 8) as u8, 200])
+        }))
+    }
+
+    #[test]
+    fn corrupted_streams_decode_or_fail_cleanly() {
+        for format in 0..3 {
+            for corruption in 0..3 {
+                for seed in 0..40 {
+                    let mut params = Params::defaults(DATABEND.params);
+                    params.set("format", ParamValue::Choice(format));
+                    params.set("corruption", ParamValue::Choice(corruption));
+                    params.set("bytes", ParamValue::Int(50));
+                    params.set("seed", ParamValue::Int(seed));
+                    // Errors are fine as long as they are errors, not panics
+                    if let Ok(output) = apply_databend(test_image(31, 23), &params) {
+                        assert_eq!(output.dimensions(), (31, 23));
+                        assert!(output.pixels().all(|pixel| pixel.0[3] == 200));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_images_are_left_alone() {
+        for format in 0..3 {
+            let mut params = Params::defaults(DATABEND.params);
+            params.set("format", ParamValue::Choice(format));
+            for (width, height) in [(0, 5), (5, 0), (0, 0)] {
+                let output = apply_databend(test_image(width, height), &params).unwrap();
+                assert_eq!(output.dimensions(), (width, height));
+            }
+        }
+    }
+
+    #[test]
+    fn jpeg_corruption_keeps_markers() {
+        let mut bytes = [0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56, 0x78];
+        let original = bytes;
+        let mut rng = Rng::new(7);
+        corrupt_jpeg_scan(&mut bytes, 1000, 0, &mut rng);
+        for (index, (&before, &after)) in original.iter().zip(&bytes).enumerate() {
+            if before == 0xff || index > 0 && original[index - 1] == 0xff {
+                assert_eq!(before, after);
+            } else {
+                assert_ne!(after, 0xff);
+            }
+        }
+        assert_ne!(bytes, original);
+    }
+
+    #[test]
+    fn positions_are_relative_to_the_length() {
+        let mut short = vec![0xaa; 100];
+        let mut long = vec![0xaa; 400];
+        corrupt(&mut short, 5, 2, &mut Rng::new(3));
+        corrupt(&mut long, 5, 2, &mut Rng::new(3));
+        let zeros =
+            |bytes: &[u8]| -> Vec<usize> { (0..bytes.len()).filter(|&i| bytes[i] == 0).collect() };
+        let scaled: Vec<usize> = zeros(&short).iter().map(|i| i * 4).collect();
+        for (long_index, short_index) in zeros(&long).into_iter().zip(scaled) {
+            assert!(long_index.abs_diff(short_index) < 4);
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Spatial distortions: every output pixel is read from a moved position
+//! of the input
+
+use dioxus::prelude::ServerFnError;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use std::f32::consts::TAU;
+
+use super::edges::{Edges, Sampler, EDGES, SAMPLING};
+use crate::backend::{whole_image, EffectDefinition, ParamSpec, Params, TileArea, Tiling};
+use crate::utils::luminance;
+
+const CENTER_X: ParamSpec = ParamSpec::float("center_x", "Center X", 0.0, 1.0, 0.01, 0.5)
+    .with_help("As a fraction of the width");
+const CENTER_Y: ParamSpec = ParamSpec::float("center_y", "Center Y", 0.0, 1.0, 0.01, 0.5)
+    .with_help("As a fraction of the height");
+const RADIUS: ParamSpec = ParamSpec::float("radius", "Radius", 0.01, 2.0, 0.01, 0.5)
+    .with_help("As a fraction of the shorter side");
+
+/// Map channels in the order of the `x_channel`/`y_channel` choices
+const MAP_CHANNELS: &[&str] = &["None", "Red", "Green", "Blue", "Luminance", "Alpha"];
+
+pub const WAVE: EffectDefinition = EffectDefinition {
+    id: "wave",
+    name: "Wave",
+    params: &[
+        ParamSpec::float(
+            "amplitude_x",
+            "Horizontal Amplitude",
+            -1000.0,
+            1000.0,
+            0.5,
+            10.0,
+        )
+        .in_pixels()
+        .with_help("How far rows shift sideways; the wavelength runs down the image"),
+        ParamSpec::float(
+            "wavelength_x",
+            "Horizontal Wavelength",
+            1.0,
+            5000.0,
+            1.0,
+            60.0,
+        )
+        .in_pixels(),
+        ParamSpec::float(
+            "amplitude_y",
+            "Vertical Amplitude",
+            -1000.0,
+            1000.0,
+            0.5,
+            0.0,
+        )
+        .in_pixels()
+        .with_help("How far columns shift up and down; the wavelength runs across"),
+        ParamSpec::float(
+            "wavelength_y",
+            "Vertical Wavelength",
+            1.0,
+            5000.0,
+            1.0,
+            60.0,
+        )
+        .in_pixels(),
+        ParamSpec::float("phase", "Phase", 0.0, 360.0, 1.0, 0.0),
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_wave,
+    tiling: wave_tiling,
+    threshold: None,
+};
+
+pub const TWIRL: EffectDefinition = EffectDefinition {
+    id: "twirl",
+    name: "Twirl",
+    params: &[
+        ParamSpec::float("angle", "Angle", -1080.0, 1080.0, 1.0, 90.0)
+            .with_help("Rotation at the center, in degrees; it fades out towards the radius"),
+        CENTER_X,
+        CENTER_Y,
+        RADIUS,
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_twirl,
+    tiling: whole_image,
+    threshold: None,
+};
+
+pub const PINCH: EffectDefinition = EffectDefinition {
+    id: "pinch",
+    name: "Pinch / Bulge",
+    params: &[
+        ParamSpec::float("amount", "Amount", -1.0, 1.0, 0.01, 0.5)
+            .with_help("Positive values pinch towards the center, negative values bulge out"),
+        CENTER_X,
+        CENTER_Y,
+        RADIUS,
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_pinch,
+    tiling: whole_image,
+    threshold: None,
+};
+
+pub const POLAR: EffectDefinition = EffectDefinition {
+    id: "polar",
+    name: "Polar Coordinates",
+    params: &[
+        ParamSpec::choice(
+            "direction",
+            "Direction",
+            &["Rectangular to Polar", "Polar to Rectangular"],
+            0,
+        ),
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_polar,
+    tiling: whole_image,
+    threshold: None,
+};
+
+pub const DISPLACE: EffectDefinition = EffectDefinition {
+    id: "displace",
+    name: "Displace",
+    params: &[
+        ParamSpec::choice("map_source", "Map", &["This Image", "Uploaded Image"], 0),
+        ParamSpec::image("map", "Map Image")
+            .with_help("Stretched to the size of the image being displaced"),
+        ParamSpec::choice("x_channel", "Horizontal Channel", MAP_CHANNELS, 1),
+        ParamSpec::choice("y_channel", "Vertical Channel", MAP_CHANNELS, 2),
+        ParamSpec::float(
+            "strength_x",
+            "Horizontal Strength",
+            -2000.0,
+            2000.0,
+            1.0,
+            20.0,
+        )
+        .in_pixels()
+        .with_help("Offset at full channel value; mid-gray doesn't move"),
+        ParamSpec::float(
+            "strength_y",
+            "Vertical Strength",
+            -2000.0,
+            2000.0,
+            1.0,
+            20.0,
+        )
+        .in_pixels(),
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_displace,
+    tiling: displace_tiling,
+    threshold: None,
+};
+
+/// Build the output by reading each pixel from `source(x, y)` in the input;
+/// positions are in the full image when the input is a tile
+fn remap(
+    image: DynamicImage,
+    params: &Params,
+    source: impl Fn(f32, f32) -> (f32, f32),
+) -> RgbaImage {
+    let input = image.into_rgba8();
+    let sampler = Sampler::new(&input, params);
+    let area = TileArea::from_params(params, input.width(), input.height());
+    let mut output = RgbaImage::new(input.width(), input.height());
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let (sx, sy) = source((x + area.x) as f32, (y + area.y) as f32);
+        pixel.0 = sampler
+            .sample(sx, sy)
+            .map(|channel| channel.round().clamp(0.0, 255.0) as u8);
+    }
+    output
+}
+
+/// Tiles of an effect that moves pixels at most `reach` pixels, one more for
+/// bilinear sampling; wrapped edges read from the far side of the image
+fn reach_tiling(params: &Params, reach: f64) -> Tiling {
+    if !reach.is_finite() || Edges::from_params(params).needs_whole_image() {
+        return Tiling::Whole;
+    }
+    Tiling::Neighborhood {
+        halo: reach.ceil() as u32 + 1,
+    }
+}
+
+/// Center and radius in pixels from the shared parameters
+fn circle(image: &DynamicImage, params: &Params) -> (f32, f32, f32) {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    (
+        params.float(CENTER_X.key) as f32 * (width - 1.0),
+        params.float(CENTER_Y.key) as f32 * (height - 1.0),
+        (params.float(RADIUS.key) as f32 * width.min(height)).max(1.0),
+    )
+}
+
+fn apply_wave(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let amplitude_x = params.float("amplitude_x") as f32;
+    let wavelength_x = params.float("wavelength_x").max(1.0) as f32;
+    let amplitude_y = params.float("amplitude_y") as f32;
+    let wavelength_y = params.float("wavelength_y").max(1.0) as f32;
+    let phase = (params.float("phase") as f32).to_radians();
+
+    // Rows move sideways with a wave running down the image, and columns
+    // move up and down with one running across it
+    Ok(remap(image, params, |x, y| {
+        (
+            x + amplitude_x * (TAU * y / wavelength_x + phase).sin(),
+            y + amplitude_y * (TAU * x / wavelength_y + phase).sin(),
+        )
+    }))
+}
+
+fn wave_tiling(params: &Params) -> Tiling {
+    let reach = params.float("amplitude_x").abs();
+    reach_tiling(params, reach.max(params.float("amplitude_y").abs()))
+}
+
+fn apply_twirl(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (cx, cy, radius) = circle(&image, params);
+    let angle = (params.float("angle") as f32).to_radians();
+
+    Ok(remap(image, params, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance >= radius {
+            return (x, y);
+        }
+        let falloff = 1.0 - distance / radius;
+        let (sin, cos) = (angle * falloff * falloff).sin_cos();
+        (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+    }))
+}
+
+fn apply_pinch(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (cx, cy, radius) = circle(&image, params);
+    // Distances inside the radius are raised to this power: below 1 reads
+    // from further out (pinch), above 1 from closer in (bulge)
+    let exponent = 2.0f32.powf(-params.float("amount") as f32);
+
+    Ok(remap(image, params, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance >= radius || distance == 0.0 {
+            return (x, y);
+        }
+        let scale = (distance / radius).powf(exponent) * radius / distance;
+        (cx + dx * scale, cy + dy * scale)
+    }))
+}
+
+fn apply_polar(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let (half_width, half_height) = (width / 2.0, height / 2.0);
+    let to_polar = params.choice("direction") == 0;
+
+    // In polar form the image's x runs around the center and its y runs out
+    // to the ellipse inscribed in the image
+    Ok(remap(image, params, |x, y| {
+        if to_polar {
+            let u = (x + 0.5 - half_width) / half_width;
+            let v = (y + 0.5 - half_height) / half_height;
+            let angle = v.atan2(u).rem_euclid(TAU);
+            (
+                angle / TAU * width - 0.5,
+                (u * u + v * v).sqrt() * height - 0.5,
+            )
+        } else {
+            let angle = (x + 0.5) / width * TAU;
+            let distance = (y + 0.5) / height;
+            (
+                half_width + distance * angle.cos() * half_width - 0.5,
+                half_height + distance * angle.sin() * half_height - 0.5,
+            )
+        }
+    }))
+}
+
+fn apply_displace(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (width, height) = (image.width(), image.height());
+    let map = match params.choice("map_source") {
+        0 => image.to_rgba8(),
+        _ => {
+            let map = params.image("map").ok_or_else(|| {
+                ServerFnError::new("Displace needs a map image; upload one or use this image")
+            })?;
+            if map.image.dimensions() == (width, height) {
+                (*map.image).clone()
+            } else {
+                imageops::resize(&*map.image, width, height, FilterType::Triangle)
+            }
+        }
+    };
+
+    let x_channel = params.choice("x_channel");
+    let y_channel = params.choice("y_channel");
+    let strength_x = params.float("strength_x") as f32;
+    let strength_y = params.float("strength_y") as f32;
+
+    // Map value around -1..1, with 128 as the neutral mid-gray. An uploaded
+    // map is only used on the whole image, so it needs no tile offset.
+    let area = TileArea::from_params(params, width, height);
+    let value = |x: f32, y: f32, channel: usize| -> f32 {
+        let (x, y) = (x as u32 - area.x, y as u32 - area.y);
+        let [r, g, b, a] = map.get_pixel(x, y).0.map(f32::from);
+        let value = match channel {
+            1 => r,
+            2 => g,
+            3 => b,
+            4 => luminance([r, g, b]),
+            5 => a,
+            _ => return 0.0,
+        };
+        (value - 128.0) / 127.0
+    };
+
+    Ok(remap(image, params, |x, y| {
+        (
+            x + value(x, y, x_channel) * strength_x,
+            y + value(x, y, y_channel) * strength_y,
+        )
+    }))
+}
+
+/// Displacing by the image itself reads as far as the strengths reach; an
+/// uploaded map is stretched to the whole image
+fn displace_tiling(params: &Params) -> Tiling {
+    if params.choice("map_source") != 0 {
+        return Tiling::Whole;
+    }
+    // Channel values of 0 are a little over -1 from the mid-gray
+    let reach = params.float("strength_x").abs();
+    reach_tiling(
+        params,
+        reach.max(params.float("strength_y").abs()) * 128.0 / 127.0,
+    )
+}
@@ -0,0 +1,416 @@
+//! Dithering to a palette, by error diffusion or ordered thresholds
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, Rgb, RgbaImage};
+use std::sync::LazyLock;
+
+use super::palette::{
+    palette, palette_from_image, Matcher, DISTANCE, PALETTE, PALETTE_SIZE, PALETTE_SOURCE,
+};
+use crate::backend::{EffectDefinition, ParamSpec, Params, TileArea, Tiling};
+
+/// Neighbors that receive a share of a pixel's error, as (dx, dy, weight)
+/// for a left-to-right scan, and the sum the weights are divided by
+struct Diffusion {
+    targets: &'static [(i64, i64, f32)],
+    divisor: f32,
+}
+
+const FLOYD_STEINBERG: Diffusion = Diffusion {
+    targets: &[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)],
+    divisor: 16.0,
+};
+
+/// Passes on only 6/8 of the error, for more contrast
+const ATKINSON: Diffusion = Diffusion {
+    targets: &[
+        (1, 0, 1.0),
+        (2, 0, 1.0),
+        (-1, 1, 1.0),
+        (0, 1, 1.0),
+        (1, 1, 1.0),
+        (0, 2, 1.0),
+    ],
+    divisor: 8.0,
+};
+
+const JARVIS_JUDICE_NINKE: Diffusion = Diffusion {
+    targets: &[
+        (1, 0, 7.0),
+        (2, 0, 5.0),
+        (-2, 1, 3.0),
+        (-1, 1, 5.0),
+        (0, 1, 7.0),
+        (1, 1, 5.0),
+        (2, 1, 3.0),
+        (-2, 2, 1.0),
+        (-1, 2, 3.0),
+        (0, 2, 5.0),
+        (1, 2, 3.0),
+        (2, 2, 1.0),
+    ],
+    divisor: 48.0,
+};
+
+const SIERRA: Diffusion = Diffusion {
+    targets: &[
+        (1, 0, 5.0),
+        (2, 0, 3.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 5.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+        (-1, 2, 2.0),
+        (0, 2, 3.0),
+        (1, 2, 2.0),
+    ],
+    divisor: 32.0,
+};
+
+/// Methods in the order of the `method` choice
+enum Method {
+    Diffusion(Diffusion),
+    Bayer(u32),
+    BlueNoise,
+}
+
+const METHODS: [Method; 8] = [
+    Method::Diffusion(FLOYD_STEINBERG),
+    Method::Diffusion(ATKINSON),
+    Method::Diffusion(JARVIS_JUDICE_NINKE),
+    Method::Diffusion(SIERRA),
+    Method::Bayer(2),
+    Method::Bayer(4),
+    Method::Bayer(8),
+    Method::BlueNoise,
+];
+
+pub const DITHER: EffectDefinition = EffectDefinition {
+    id: "dither",
+    name: "Dither",
+    params: &[
+        ParamSpec::choice(
+            "method",
+            "Method",
+            &[
+                "Floyd–Steinberg",
+                "Atkinson",
+                "Jarvis–Judice–Ninke",
+                "Sierra",
+                "Bayer 2×2",
+                "Bayer 4×4",
+                "Bayer 8×8",
+                "Blue Noise",
+            ],
+            0,
+        ),
+        PALETTE_SOURCE,
+        PALETTE,
+        PALETTE_SIZE,
+        DISTANCE,
+        ParamSpec::float("strength", "Strength", 0.0, 2.0, 0.05, 1.0)
+            .with_help("How much of the error is diffused, or how far ordered thresholds spread"),
+        ParamSpec::bool("serpentine", "Serpentine Scanning", true)
+            .with_help("Scan every other row right to left (error diffusion only)"),
+    ],
+    apply: apply_dither,
+    tiling: dither_tiling,
+    threshold: None,
+};
+
+fn apply_dither(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let image = image.into_rgba8();
+    let palette = palette(&image, params)?;
+    let matcher = Matcher::new(&palette, params);
+    let strength = params.float("strength") as f32;
+    // Thresholds repeat across the full image, not each tile
+    let area = TileArea::from_params(params, image.width(), image.height());
+
+    let output = match method(params) {
+        Method::Diffusion(diffusion) => diffuse(
+            &image,
+            &palette,
+            &matcher,
+            diffusion,
+            strength,
+            params.bool("serpentine"),
+        ),
+        Method::Bayer(size) => {
+            let matrix = bayer_matrix(*size);
+            ordered(&image, &palette, &matcher, strength, |x, y| {
+                let (x, y) = (x + area.x, y + area.y);
+                matrix[((y % size) * size + x % size) as usize]
+            })
+        }
+        Method::BlueNoise => ordered(&image, &palette, &matcher, strength, |x, y| {
+            let (x, y) = (x + area.x, y + area.y);
+            BLUE_NOISE[((y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE) as usize]
+        }),
+    };
+    Ok(output)
+}
+
+fn method(params: &Params) -> &'static Method {
+    &METHODS[params.choice("method").min(METHODS.len() - 1)]
+}
+
+/// Ordered thresholds only depend on a pixel's position, but diffused error
+/// runs across the whole image, as does a palette computed from it
+fn dither_tiling(params: &Params) -> Tiling {
+    match method(params) {
+        Method::Diffusion(_) => Tiling::Whole,
+        _ if palette_from_image(params) => Tiling::Whole,
+        _ => Tiling::PerPixel,
+    }
+}
+
+/// Error diffusion: each pixel takes the nearest palette color and passes
+/// the difference on to the neighbors not yet visited
+fn diffuse(
+    image: &RgbaImage,
+    palette: &[Rgb<u8>],
+    matcher: &Matcher,
+    diffusion: &Diffusion,
+    strength: f32,
+    serpentine: bool,
+) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut values: Vec<[f32; 3]> = image
+        .pixels()
+        .map(|pixel| [0, 1, 2].map(|channel| pixel.0[channel] as f32))
+        .collect();
+    let mut output = image.clone();
+
+    for y in 0..height {
+        let reversed = serpentine && y % 2 == 1;
+        for step in 0..width {
+            let x = if reversed { width - 1 - step } else { step };
+            let value = values[(y * width + x) as usize];
+            let color = palette[matcher.nearest(value)];
+            let pixel = output.get_pixel_mut(x, y);
+            pixel.0[..3].copy_from_slice(&color.0);
+
+            let error =
+                [0, 1, 2].map(|channel| (value[channel] - color.0[channel] as f32) * strength);
+            for &(dx, dy, weight) in diffusion.targets {
+                // Mirror the neighborhood when scanning right to left
+                let dx = if reversed { -dx } else { dx };
+                let (tx, ty) = (x as i64 + dx, y as i64 + dy);
+                if tx < 0 || tx >= width as i64 || ty >= height as i64 {
+                    continue;
+                }
+                let target = &mut values[(ty as u32 * width + tx as u32) as usize];
+                for (channel, error) in target.iter_mut().zip(error) {
+                    *channel += error * weight / diffusion.divisor;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Ordered dithering: offset each pixel by a position-dependent threshold in
+/// 0..1 before picking the nearest palette color
+fn ordered(
+    image: &RgbaImage,
+    palette: &[Rgb<u8>],
+    matcher: &Matcher,
+    strength: f32,
+    threshold: impl Fn(u32, u32) -> f32,
+) -> RgbaImage {
+    // Roughly the distance between neighboring colors of an evenly spread palette
+    let spread = 255.0 / (palette.len() as f32).cbrt().max(1.0) * strength;
+    let mut output = image.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let offset = (threshold(x, y) - 0.5) * spread;
+        let value = [0, 1, 2].map(|channel| pixel.0[channel] as f32 + offset);
+        pixel.0[..3].copy_from_slice(&palette[matcher.nearest(value)].0);
+    }
+    output
+}
+
+/// Bayer threshold matrix of a power-of-two size, row by row, with values
+/// evenly spread in 0..1
+fn bayer_matrix(size: u32) -> Vec<f32> {
+    let mut matrix = vec![0u32];
+    let mut current = 1;
+    while current < size {
+        let next = current * 2;
+        let mut larger = vec![0; (next * next) as usize];
+        for y in 0..next {
+            for x in 0..next {
+                let base = matrix[((y % current) * current + x % current) as usize];
+                let quadrant = match (x < current, y < current) {
+                    (true, true) => 0,
+                    (false, false) => 1,
+                    (false, true) => 2,
+                    (true, false) => 3,
+                };
+                larger[(y * next + x) as usize] = 4 * base + quadrant;
+            }
+        }
+        matrix = larger;
+        current = next;
+    }
+    let count = matrix.len() as f32;
+    matrix
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count)
+        .collect()
+}
+
+const BLUE_NOISE_SIZE: u32 = 64;
+
+/// Blue-noise thresholds, tiled over the image
+static BLUE_NOISE: LazyLock<Vec<f32>> = LazyLock::new(|| void_and_cluster(BLUE_NOISE_SIZE));
+
+/// Blue-noise threshold matrix by Ulichney's void-and-cluster method: points
+/// are ranked so that every prefix of the ranking is as evenly spread as
+/// possible
+fn void_and_cluster(size: u32) -> Vec<f32> {
+    const SIGMA: f32 = 1.5;
+    let count = (size * size) as usize;
+
+    // Gaussian falloff by wrapped offset, so the matrix tiles seamlessly
+    let falloff: Vec<f32> = (0..count)
+        .map(|index| {
+            let wrap = |d: u32| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(index as u32 % size), wrap(index as u32 / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    struct Pattern {
+        size: u32,
+        points: Vec<bool>,
+        energy: Vec<f32>,
+    }
+
+    impl Pattern {
+        fn toggle(&mut self, index: usize, falloff: &[f32]) {
+            self.points[index] = !self.points[index];
+            let sign = if self.points[index] { 1.0 } else { -1.0 };
+            let (px, py) = (index as u32 % self.size, index as u32 / self.size);
+            for (other, energy) in self.energy.iter_mut().enumerate() {
+                let dx = (other as u32 % self.size + self.size - px) % self.size;
+                let dy = (other as u32 / self.size + self.size - py) % self.size;
+                *energy += sign * falloff[(dy * self.size + dx) as usize];
+            }
+        }
+
+        /// Point in the densest cluster
+        fn tightest_cluster(&self) -> usize {
+            self.extreme(true, |a, b| a > b)
+        }
+
+        /// Empty spot in the largest void
+        fn largest_void(&self) -> usize {
+            self.extreme(false, |a, b| a < b)
+        }
+
+        fn extreme(&self, point: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+            let mut best: Option<usize> = None;
+            for (index, &energy) in self.energy.iter().enumerate() {
+                if self.points[index] == point
+                    && best.is_none_or(|best| better(energy, self.energy[best]))
+                {
+                    best = Some(index);
+                }
+            }
+            best.unwrap_or(0)
+        }
+    }
+
+    let mut pattern = Pattern {
+        size,
+        points: vec![false; count],
+        energy: vec![0.0; count],
+    };
+
+    // Start from a sparse, scrambled pattern, then even it out by moving
+    // the most crowded point into the biggest gap until that changes nothing
+    let initial = count / 10;
+    let mut seed = 0x2545_f491_u32;
+    while pattern.points.iter().filter(|point| **point).count() < initial {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let index = seed as usize % count;
+        if !pattern.points[index] {
+            pattern.toggle(index, &falloff);
+        }
+    }
+    for _ in 0..count {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster, &falloff);
+        let void = pattern.largest_void();
+        if void == cluster {
+            pattern.toggle(cluster, &falloff);
+            break;
+        }
+        pattern.toggle(void, &falloff);
+    }
+
+    let mut ranks = vec![0usize; count];
+
+    // Rank the initial points by removing them, most crowded first
+    let mut removal = Pattern {
+        size,
+        points: pattern.points.clone(),
+        energy: pattern.energy.clone(),
+    };
+    for rank in (0..initial).rev() {
+        let cluster = removal.tightest_cluster();
+        removal.toggle(cluster, &falloff);
+        ranks[cluster] = rank;
+    }
+
+    // Rank the rest by filling the largest remaining void
+    for rank in initial..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void, &falloff);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank as f32 + 0.5) / count as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ranks 0..size² the thresholds were spread from
+    fn ranks(size: u32) -> Vec<u32> {
+        let count = (size * size) as f32;
+        bayer_matrix(size)
+            .into_iter()
+            .map(|threshold| (threshold * count - 0.5).round() as u32)
+            .collect()
+    }
+
+    #[test]
+    fn bayer_ranks_are_a_permutation() {
+        for size in [1, 2, 4, 8, 16] {
+            let mut ranks = ranks(size);
+            assert_eq!(ranks.len(), (size * size) as usize);
+            ranks.sort_unstable();
+            assert!(ranks.into_iter().eq(0..size * size), "size {}", size);
+        }
+    }
+
+    #[test]
+    fn bayer_matches_the_classic_matrices() {
+        assert_eq!(ranks(2), [0, 2, 3, 1]);
+        assert_eq!(
+            ranks(4),
+            [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5]
+        );
+        assert!(bayer_matrix(8)
+            .iter()
+            .all(|threshold| (0.0..1.0).contains(threshold)));
+    }
+}
@@ -0,0 +1,123 @@
+//! What effects see outside the image, and reading between pixels
+
+use image::RgbaImage;
+
+use crate::backend::{ParamSpec, Params, TileArea};
+
+/// Edge modes in the order of the `edges` choice
+const EDGE_MODES: [Edges; 4] = [Edges::Clamp, Edges::Mirror, Edges::Wrap, Edges::Transparent];
+
+/// The `edges` parameter shared by effects that read outside the image
+pub const EDGES: ParamSpec = ParamSpec::choice(
+    "edges",
+    "Edges",
+    &["Clamp", "Mirror", "Wrap", "Transparent"],
+    0,
+);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edges {
+    /// Repeat the nearest edge pixel
+    Clamp,
+    /// Reflect the image at its edges
+    Mirror,
+    /// Continue from the opposite edge
+    Wrap,
+    /// Transparent black
+    Transparent,
+}
+
+impl Edges {
+    pub fn from_params(params: &Params) -> Edges {
+        EDGE_MODES
+            .get(params.choice(EDGES.key))
+            .copied()
+            .unwrap_or(Edges::Clamp)
+    }
+
+    /// Index in `0..len` to read for position `i`, or `None` for a
+    /// transparent pixel
+    pub fn index(self, i: i64, len: u32) -> Option<usize> {
+        let len = len as i64;
+        if (0..len).contains(&i) {
+            return Some(i as usize);
+        }
+        match self {
+            Edges::Clamp => Some(i.clamp(0, len - 1) as usize),
+            Edges::Mirror => {
+                let i = i.rem_euclid(2 * len);
+                let reflected = if i < len { i } else { 2 * len - 1 - i };
+                Some(reflected as usize)
+            }
+            Edges::Wrap => Some(i.rem_euclid(len) as usize),
+            Edges::Transparent => None,
+        }
+    }
+
+    /// Wrapping reads from the far side of the image, so tiles can't be
+    /// processed on their own
+    pub fn needs_whole_image(self) -> bool {
+        self == Edges::Wrap
+    }
+}
+
+/// The `sampling` parameter shared by effects that read between pixels
+pub const SAMPLING: ParamSpec =
+    ParamSpec::choice("sampling", "Sampling", &["Bilinear", "Nearest"], 0);
+
+/// Reads an image at fractional positions; integer positions are pixel
+/// centers.
+///
+/// Positions are in the full image, so on a tile the edges are those of the
+/// full image; pixels outside the tile read as transparent.
+pub struct Sampler<'a> {
+    image: &'a RgbaImage,
+    area: TileArea,
+    edges: Edges,
+    nearest: bool,
+}
+
+impl<'a> Sampler<'a> {
+    /// Sampler set up by the `edges` and `sampling` parameters
+    pub fn new(image: &'a RgbaImage, params: &Params) -> Sampler<'a> {
+        Sampler {
+            image,
+            area: TileArea::from_params(params, image.width(), image.height()),
+            edges: Edges::from_params(params),
+            nearest: params.choice(SAMPLING.key) == 1,
+        }
+    }
+
+    pub fn pixel(&self, x: i64, y: i64) -> [f32; 4] {
+        let area = &self.area;
+        match (
+            self.edges.index(x, area.full_width),
+            self.edges.index(y, area.full_height),
+        ) {
+            (Some(x), Some(y)) => self
+                .image
+                .get_pixel_checked(
+                    (x as u32).wrapping_sub(area.x),
+                    (y as u32).wrapping_sub(area.y),
+                )
+                .map_or([0.0; 4], |pixel| pixel.0.map(f32::from)),
+            _ => [0.0; 4],
+        }
+    }
+
+    pub fn sample(&self, x: f32, y: f32) -> [f32; 4] {
+        if self.nearest || !x.is_finite() || !y.is_finite() {
+            return self.pixel(x.round() as i64, y.round() as i64);
+        }
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = lerp(self.pixel(x0, y0), self.pixel(x0 + 1, y0), fx);
+        let bottom = lerp(self.pixel(x0, y0 + 1), self.pixel(x0 + 1, y0 + 1), fx);
+        lerp(top, bottom, fy)
+    }
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|channel| a[channel] + (b[channel] - a[channel]) * t)
+}
@@ -0,0 +1,888 @@
+//! Per-pixel expression effect: one formula per output channel, such as
+//! `r = (g ^ b) << 1` or `b = 255 - lum`.
+//!
+//! Formulas are parsed once per run into a small syntax tree and evaluated
+//! for every pixel. All assignments read the original pixel, so the order of
+//! the lines doesn't matter.
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+use std::fmt;
+
+use super::mark_error;
+use crate::backend::{ImageEffect, ParamSpec, ParamValue, Params, TileArea, Tiling};
+
+const DEFAULT_FORMULA: &str = "r = (g ^ b) << 1\ng = max(r, b)\nb = 255 - lum";
+
+const HELP: &str = "One `channel = formula` per line for r, g, b or a; unassigned channels are \
+kept. Variables: r g b a lum (0-255), x y, w h. Neighbours: r(dx, dy), g(..), b(..), a(..), \
+lum(..). Operators: + - * / % ** << >> & | ^ ~ == != < <= > >= && || !. Functions: min max \
+abs clamp sqrt pow exp log sin cos tan atan2 floor ceil round if(cond, then, else). \
+# starts a comment.";
+
+const PARAMS: &[ParamSpec] = &[ParamSpec::text("formula", "Formula", 4, DEFAULT_FORMULA)
+    .with_help(HELP)
+    .saveable()];
+
+/// Undeclared parameter holding the factor neighbour offsets are multiplied
+/// by, set when rendering on a resized copy of the image
+const OFFSET_SCALE: &str = "offset_scale";
+
+/// Deepest nesting of parentheses, calls, unary operators and chained binary
+/// operators; parsing and evaluating recurse once per level, so this keeps
+/// both off the stack limit
+const MAX_DEPTH: usize = 100;
+
+/// Factor neighbour offsets are multiplied by, 1 unless rendering on a
+/// resized copy
+fn offset_scale(params: &Params) -> f64 {
+    match params.get(OFFSET_SCALE) {
+        Some(ParamValue::Float(scale)) => *scale,
+        _ => 1.0,
+    }
+}
+
+pub struct ExpressionEffect;
+
+impl ImageEffect for ExpressionEffect {
+    fn id(&self) -> &'static str {
+        "expression"
+    }
+
+    fn name(&self) -> &'static str {
+        "Expression"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn apply(&self, image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+        let source = params.text("formula");
+        let program = Program::parse(source)
+            .map_err(|e| ServerFnError::new(format!("Formula error: {}", e.describe(source))))?;
+        let image = image.into_rgba8();
+        let area = TileArea::from_params(params, image.width(), image.height());
+        Ok(program.run(&image, offset_scale(params), area))
+    }
+
+    fn tiling(&self, params: &Params) -> Tiling {
+        match Program::parse(params.text("formula")) {
+            Ok(program) => program.tiling(offset_scale(params)),
+            // Fails in `apply` anyway
+            Err(_) => Tiling::PerPixel,
+        }
+    }
+
+    fn scale_params(&self, params: &mut Params, scale: f64) {
+        params.set(
+            OFFSET_SCALE,
+            ParamValue::Float(offset_scale(params) * scale),
+        );
+    }
+
+    fn check_param(&self, key: &str, params: &Params) -> Option<String> {
+        if key != "formula" {
+            return None;
+        }
+        let source = params.text(key);
+        Program::parse(source).err().map(|e| e.describe(source))
+    }
+}
+
+/// Syntax error and the byte offset it was found at
+#[derive(Clone, PartialEq, Debug)]
+struct ExpressionError {
+    message: String,
+    offset: usize,
+}
+
+impl ExpressionError {
+    fn new(message: impl Into<String>, offset: usize) -> ExpressionError {
+        ExpressionError {
+            message: message.into(),
+            offset,
+        }
+    }
+
+    /// The error with its position and the offending line, marked with a caret
+    fn describe(&self, source: &str) -> String {
+        let offset = self.offset.min(source.len());
+        let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let line = source[..offset].matches('\n').count() + 1;
+        let column = source[line_start..offset].chars().count() + 1;
+        mark_error(source, line, column, &self.message)
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    /// End of a statement: newline or `;`
+    Separator,
+    End,
+}
+
+/// Longer operators first so `<<` isn't read as two `<`
+const OPERATORS: [&str; 26] = [
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "^", "&", "|",
+    "~", "!", "<", ">", "=", "(", ")", ",", "?",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+
+    loop {
+        let trimmed = rest.trim_start_matches([' ', '\t', '\r']);
+        let offset = source.len() - trimmed.len();
+        rest = trimmed;
+
+        let Some(first) = rest.chars().next() else {
+            break;
+        };
+
+        if first == '#' {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        }
+        if first == '\n' || first == ';' {
+            tokens.push((Token::Separator, offset));
+            rest = &rest[1..];
+            continue;
+        }
+        if first.is_ascii_digit() || first == '.' {
+            let length = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number = rest[..length]
+                .parse::<f64>()
+                .map_err(|_| ExpressionError::new("invalid number", offset))?;
+            tokens.push((Token::Number(number), offset));
+            rest = &rest[length..];
+            continue;
+        }
+        if first.is_ascii_alphabetic() || first == '_' {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push((Token::Ident(rest[..length].to_string()), offset));
+            rest = &rest[length..];
+            continue;
+        }
+        match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            // `?` is listed only so it gets a clear error
+            Some(&"?") => {
+                return Err(ExpressionError::new(
+                    "use if(cond, then, else) instead of ?:",
+                    offset,
+                ))
+            }
+            Some(op) => {
+                tokens.push((Token::Op(op), offset));
+                rest = &rest[op.len()..];
+            }
+            None => {
+                return Err(ExpressionError::new(
+                    format!("unexpected character '{}'", first),
+                    offset,
+                ))
+            }
+        }
+    }
+
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Var {
+    R,
+    G,
+    B,
+    A,
+    Lum,
+    X,
+    Y,
+    Width,
+    Height,
+}
+
+impl Var {
+    fn from_name(name: &str) -> Option<Var> {
+        match name {
+            "r" => Some(Var::R),
+            "g" => Some(Var::G),
+            "b" => Some(Var::B),
+            "a" => Some(Var::A),
+            "lum" => Some(Var::Lum),
+            "x" => Some(Var::X),
+            "y" => Some(Var::Y),
+            "w" | "width" => Some(Var::Width),
+            "h" | "height" => Some(Var::Height),
+            _ => None,
+        }
+    }
+
+    /// Whether the variable is one that can be sampled at an offset
+    fn is_sample(&self) -> bool {
+        matches!(self, Var::R | Var::G | Var::B | Var::A | Var::Lum)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Func {
+    Min,
+    Max,
+    Abs,
+    Clamp,
+    Sqrt,
+    Pow,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Atan2,
+    Floor,
+    Ceil,
+    Round,
+    If,
+}
+
+impl Func {
+    /// Function and the number of arguments it takes
+    fn from_name(name: &str) -> Option<(Func, usize)> {
+        let func = match name {
+            "min" => (Func::Min, 2),
+            "max" => (Func::Max, 2),
+            "abs" => (Func::Abs, 1),
+            "clamp" => (Func::Clamp, 3),
+            "sqrt" => (Func::Sqrt, 1),
+            "pow" => (Func::Pow, 2),
+            "exp" => (Func::Exp, 1),
+            "log" => (Func::Log, 1),
+            "sin" => (Func::Sin, 1),
+            "cos" => (Func::Cos, 1),
+            "tan" => (Func::Tan, 1),
+            "atan2" => (Func::Atan2, 2),
+            "floor" => (Func::Floor, 1),
+            "ceil" => (Func::Ceil, 1),
+            "round" => (Func::Round, 1),
+            "if" => (Func::If, 3),
+            _ => return None,
+        };
+        Some(func)
+    }
+
+    fn call(&self, args: &[f64]) -> f64 {
+        match (self, args) {
+            (Func::Min, [a, b]) => a.min(*b),
+            (Func::Max, [a, b]) => a.max(*b),
+            (Func::Abs, [a]) => a.abs(),
+            (Func::Clamp, [value, low, high]) => value.max(*low).min(*high),
+            (Func::Sqrt, [a]) => a.sqrt(),
+            (Func::Pow, [a, b]) => a.powf(*b),
+            (Func::Exp, [a]) => a.exp(),
+            (Func::Log, [a]) => a.ln(),
+            (Func::Sin, [a]) => a.sin(),
+            (Func::Cos, [a]) => a.cos(),
+            (Func::Tan, [a]) => a.tan(),
+            (Func::Atan2, [y, x]) => y.atan2(*x),
+            (Func::Floor, [a]) => a.floor(),
+            (Func::Ceil, [a]) => a.ceil(),
+            (Func::Round, [a]) => a.round(),
+            (Func::If, [condition, then, otherwise]) => {
+                if *condition != 0.0 {
+                    *then
+                } else {
+                    *otherwise
+                }
+            }
+            _ => f64::NAN,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+impl UnaryOp {
+    fn apply(&self, value: f64) -> f64 {
+        match self {
+            UnaryOp::Neg => -value,
+            UnaryOp::Not => (value == 0.0) as u8 as f64,
+            UnaryOp::BitNot => !(value as i64) as f64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl BinaryOp {
+    /// Operator and its precedence, higher binds tighter; `**` is handled
+    /// separately because it is right-associative
+    fn from_token(op: &str) -> Option<(BinaryOp, u8)> {
+        let binary = match op {
+            "||" => (BinaryOp::Or, 1),
+            "&&" => (BinaryOp::And, 2),
+            "|" => (BinaryOp::BitOr, 3),
+            "^" => (BinaryOp::BitXor, 4),
+            "&" => (BinaryOp::BitAnd, 5),
+            "==" => (BinaryOp::Eq, 6),
+            "!=" => (BinaryOp::Ne, 6),
+            "<" => (BinaryOp::Lt, 7),
+            "<=" => (BinaryOp::Le, 7),
+            ">" => (BinaryOp::Gt, 7),
+            ">=" => (BinaryOp::Ge, 7),
+            "<<" => (BinaryOp::Shl, 8),
+            ">>" => (BinaryOp::Shr, 8),
+            "+" => (BinaryOp::Add, 9),
+            "-" => (BinaryOp::Sub, 9),
+            "*" => (BinaryOp::Mul, 10),
+            "/" => (BinaryOp::Div, 10),
+            "%" => (BinaryOp::Rem, 10),
+            _ => return None,
+        };
+        Some(binary)
+    }
+
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        // Bitwise operators work on the integer part
+        let (ia, ib) = (a as i64, b as i64);
+        let truth = |value: bool| if value { 1.0 } else { 0.0 };
+        match self {
+            BinaryOp::Or => truth(a != 0.0 || b != 0.0),
+            BinaryOp::And => truth(a != 0.0 && b != 0.0),
+            BinaryOp::BitOr => (ia | ib) as f64,
+            BinaryOp::BitXor => (ia ^ ib) as f64,
+            BinaryOp::BitAnd => (ia & ib) as f64,
+            BinaryOp::Eq => truth(a == b),
+            BinaryOp::Ne => truth(a != b),
+            BinaryOp::Lt => truth(a < b),
+            BinaryOp::Le => truth(a <= b),
+            BinaryOp::Gt => truth(a > b),
+            BinaryOp::Ge => truth(a >= b),
+            BinaryOp::Shl => ia.checked_shl(ib.clamp(0, 63) as u32).unwrap_or(0) as f64,
+            BinaryOp::Shr => ia.checked_shr(ib.clamp(0, 63) as u32).unwrap_or(0) as f64,
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a.rem_euclid(b),
+            BinaryOp::Pow => a.powf(b),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Number(f64),
+    Var(Var),
+    /// A channel of the pixel at an offset from the current one
+    Sample(Var, Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+impl Expr {
+    /// The value, if it is the same for every pixel
+    fn constant_value(&self) -> Option<f64> {
+        match self {
+            Expr::Number(value) => Some(*value),
+            Expr::Var(_) | Expr::Sample(..) => None,
+            Expr::Unary(op, operand) => Some(op.apply(operand.constant_value()?)),
+            Expr::Binary(op, lhs, rhs) => {
+                Some(op.apply(lhs.constant_value()?, rhs.constant_value()?))
+            }
+            Expr::Call(func, args) => {
+                let values: Option<Vec<f64>> = args.iter().map(Expr::constant_value).collect();
+                Some(func.call(&values?))
+            }
+        }
+    }
+
+    fn visit(&self, visitor: &mut impl FnMut(&Expr)) {
+        visitor(self);
+        match self {
+            Expr::Number(_) | Expr::Var(_) => {}
+            Expr::Sample(_, dx, dy) => {
+                dx.visit(visitor);
+                dy.visit(visitor);
+            }
+            Expr::Unary(_, operand) => operand.visit(visitor),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit(visitor);
+                rhs.visit(visitor);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.visit(visitor)),
+        }
+    }
+
+    fn eval(&self, pixel: &PixelContext) -> f64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Var(var) => pixel.var(*var),
+            Expr::Sample(var, dx, dy) => pixel.sample(*var, dx.eval(pixel), dy.eval(pixel)),
+            Expr::Unary(op, operand) => op.apply(operand.eval(pixel)),
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.eval(pixel), rhs.eval(pixel)),
+            Expr::Call(func, args) => {
+                let values: Vec<f64> = args.iter().map(|arg| arg.eval(pixel)).collect();
+                func.call(&values)
+            }
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Levels of nesting the parser is currently in
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.position].clone();
+        if token.0 != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Token::Op(found) if *found == op) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), ExpressionError> {
+        if self.eat_op(op) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", op)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ExpressionError {
+        let found = match self.peek() {
+            Token::Number(number) => format!("number {}", number),
+            Token::Ident(name) => format!("'{}'", name),
+            Token::Op(op) => format!("'{}'", op),
+            Token::Separator => "end of line".to_string(),
+            Token::End => "end of formula".to_string(),
+        };
+        ExpressionError::new(
+            format!("expected {}, found {}", expected, found),
+            self.offset(),
+        )
+    }
+
+    /// Enter one more level of nesting, failing past [`MAX_DEPTH`]
+    fn descend(&mut self) -> Result<(), ExpressionError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ExpressionError::new(
+                format!("formula is nested more than {} levels deep", MAX_DEPTH),
+                self.offset(),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, ExpressionError> {
+        let depth = self.depth;
+        let expr = self.chain(min_precedence);
+        self.depth = depth;
+        expr
+    }
+
+    /// Operators of at least `min_precedence`, grouped to the left. Each one
+    /// nests the operators before it a level deeper, so each counts against
+    /// the depth until the chain ends.
+    fn chain(&mut self, min_precedence: u8) -> Result<Expr, ExpressionError> {
+        let mut lhs = self.unary()?;
+        while let Token::Op(op) = *self.peek() {
+            let Some((binary, precedence)) = BinaryOp::from_token(op) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.descend()?;
+            self.position += 1;
+            let rhs = self.expression(precedence + 1)?;
+            lhs = Expr::Binary(binary, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// A unary expression. Every other level of nesting passes through
+    /// here, so this is where its depth is limited.
+    fn unary(&mut self) -> Result<Expr, ExpressionError> {
+        self.descend()?;
+        let expr = self.prefixed();
+        self.depth -= 1;
+        expr
+    }
+
+    fn prefixed(&mut self) -> Result<Expr, ExpressionError> {
+        let op = if self.eat_op("-") {
+            UnaryOp::Neg
+        } else if self.eat_op("!") {
+            UnaryOp::Not
+        } else if self.eat_op("~") {
+            UnaryOp::BitNot
+        } else {
+            return self.power();
+        };
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn power(&mut self) -> Result<Expr, ExpressionError> {
+        let base = self.primary()?;
+        if self.eat_op("**") {
+            // Right-associative and binds tighter than a unary minus on its left
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExpressionError> {
+        let offset = self.offset();
+        match self.peek().clone() {
+            Token::Number(value) => {
+                self.position += 1;
+                Ok(Expr::Number(value))
+            }
+            Token::Op("(") => {
+                self.position += 1;
+                let inner = self.expression(0)?;
+                self.expect_op(")")?;
+                Ok(inner)
+            }
+            Token::Ident(name) => {
+                self.position += 1;
+                if *self.peek() == Token::Op("(") {
+                    self.call(&name, offset)
+                } else if name == "pi" {
+                    Ok(Expr::Number(std::f64::consts::PI))
+                } else {
+                    Var::from_name(&name).map(Expr::Var).ok_or_else(|| {
+                        ExpressionError::new(format!("unknown variable '{}'", name), offset)
+                    })
+                }
+            }
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn call(&mut self, name: &str, offset: usize) -> Result<Expr, ExpressionError> {
+        self.expect_op("(")?;
+        let mut args = Vec::new();
+        if !self.eat_op(")") {
+            loop {
+                args.push(self.expression(0)?);
+                if self.eat_op(")") {
+                    break;
+                }
+                self.expect_op(",")?;
+            }
+        }
+
+        if let Some(var) = Var::from_name(name).filter(Var::is_sample) {
+            let [dx, dy]: [Expr; 2] = args.try_into().map_err(|_| {
+                ExpressionError::new(format!("{}(dx, dy) takes 2 offsets", name), offset)
+            })?;
+            return Ok(Expr::Sample(var, Box::new(dx), Box::new(dy)));
+        }
+
+        let (func, arity) = Func::from_name(name)
+            .ok_or_else(|| ExpressionError::new(format!("unknown function '{}'", name), offset))?;
+        if args.len() != arity {
+            return Err(ExpressionError::new(
+                format!(
+                    "{} takes {} argument{}, found {}",
+                    name,
+                    arity,
+                    if arity == 1 { "" } else { "s" },
+                    args.len()
+                ),
+                offset,
+            ));
+        }
+        Ok(Expr::Call(func, args))
+    }
+}
+
+/// Parsed formulas, one per assigned output channel (r, g, b, a)
+struct Program {
+    channels: [Option<Expr>; 4],
+}
+
+impl Program {
+    fn parse(source: &str) -> Result<Program, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        };
+        let mut channels: [Option<Expr>; 4] = Default::default();
+
+        loop {
+            let (token, offset) = parser.next();
+            let name = match token {
+                Token::End => break,
+                Token::Separator => continue,
+                Token::Ident(name) => name,
+                _ => {
+                    return Err(ExpressionError::new(
+                        "expected a channel to assign (r, g, b or a)",
+                        offset,
+                    ))
+                }
+            };
+            let index = ["r", "g", "b", "a"]
+                .iter()
+                .position(|channel| *channel == name)
+                .ok_or_else(|| {
+                    ExpressionError::new(
+                        format!("can only assign to r, g, b or a, not '{}'", name),
+                        offset,
+                    )
+                })?;
+            if channels[index].is_some() {
+                return Err(ExpressionError::new(
+                    format!("'{}' is assigned twice", name),
+                    offset,
+                ));
+            }
+            parser.expect_op("=")?;
+            channels[index] = Some(parser.expression(0)?);
+
+            if !matches!(parser.peek(), Token::Separator | Token::End) {
+                return Err(parser.unexpected("end of line"));
+            }
+        }
+
+        if channels.iter().all(Option::is_none) {
+            return Err(ExpressionError::new(
+                "assign at least one channel, e.g. r = 255 - r",
+                source.len(),
+            ));
+        }
+        Ok(Program { channels })
+    }
+
+    fn tiling(&self, offset_scale: f64) -> Tiling {
+        let mut halo = 0.0f64;
+        let mut whole = false;
+        for expr in self.channels.iter().flatten() {
+            expr.visit(&mut |expr| {
+                // Offsets computed per pixel could reach anywhere
+                if let Expr::Sample(_, dx, dy) = expr {
+                    match (dx.constant_value(), dy.constant_value()) {
+                        (Some(dx), Some(dy)) => {
+                            let (dx, dy) = (dx * offset_scale, dy * offset_scale);
+                            halo = halo.max(dx.abs().round()).max(dy.abs().round())
+                        }
+                        _ => whole = true,
+                    }
+                }
+            });
+        }
+
+        if whole || !halo.is_finite() {
+            Tiling::Whole
+        } else if halo > 0.0 {
+            Tiling::Neighborhood {
+                halo: halo.min(u32::MAX as f64) as u32,
+            }
+        } else {
+            Tiling::PerPixel
+        }
+    }
+
+    fn run(&self, image: &RgbaImage, offset_scale: f64, area: TileArea) -> RgbaImage {
+        let mut output = image.clone();
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            let context = PixelContext {
+                image,
+                area,
+                x: x + area.x,
+                y: y + area.y,
+                offset_scale,
+            };
+            for (channel, expr) in self.channels.iter().enumerate() {
+                if let Some(expr) = expr {
+                    let value = expr.eval(&context);
+                    pixel.0[channel] = if value.is_nan() {
+                        0
+                    } else {
+                        value.round().clamp(0.0, 255.0) as u8
+                    };
+                }
+            }
+        }
+        output
+    }
+}
+
+/// What a formula can see while computing one pixel
+struct PixelContext<'a> {
+    image: &'a RgbaImage,
+    /// Where `image` lies in the full image
+    area: TileArea,
+    /// Position in the full image
+    x: u32,
+    y: u32,
+    /// Factor neighbour offsets are multiplied by
+    offset_scale: f64,
+}
+
+impl PixelContext<'_> {
+    fn var(&self, var: Var) -> f64 {
+        match var {
+            Var::X => self.x as f64,
+            Var::Y => self.y as f64,
+            Var::Width => self.area.full_width as f64,
+            Var::Height => self.area.full_height as f64,
+            _ => self.sample(var, 0.0, 0.0),
+        }
+    }
+
+    /// Channel of the pixel at an offset, clamped to the edges of the full
+    /// image
+    fn sample(&self, var: Var, dx: f64, dy: f64) -> f64 {
+        let (width, height) = (self.area.full_width, self.area.full_height);
+        let (dx, dy) = (dx * self.offset_scale, dy * self.offset_scale);
+        if width == 0 || height == 0 || !dx.is_finite() || !dy.is_finite() {
+            return 0.0;
+        }
+        let x = (self.x as f64 + dx.round()).clamp(0.0, (width - 1) as f64) as u32;
+        let y = (self.y as f64 + dy.round()).clamp(0.0, (height - 1) as f64) as u32;
+        // The tile's halo covers every constant offset
+        let Some(&pixel) = self
+            .image
+            .get_pixel_checked(x.wrapping_sub(self.area.x), y.wrapping_sub(self.area.y))
+        else {
+            return 0.0;
+        };
+        match var {
+            Var::R => pixel[0] as f64,
+            Var::G => pixel[1] as f64,
+            Var::B => pixel[2] as f64,
+            Var::A => pixel[3] as f64,
+            // Same Rec. 709 weights as the Sort and Bloom thresholds
+            Var::Lum => ::imgfx::calc_luminance(pixel),
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn run(formula: &str, image: &RgbaImage, offset_scale: f64) -> RgbaImage {
+        let area = TileArea::from_params(&Params::default(), image.width(), image.height());
+        Program::parse(formula)
+            .unwrap()
+            .run(image, offset_scale, area)
+    }
+
+    #[test]
+    fn lum_matches_the_threshold_luminance() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([200, 40, 90, 255]));
+        let expected = ::imgfx::calc_luminance(Rgba([200, 40, 90, 255])).round() as u8;
+        assert_eq!(run("r = lum", &image, 1.0).get_pixel(0, 0)[0], expected);
+    }
+
+    #[test]
+    fn neighbour_offsets_follow_the_scale() {
+        let image = RgbaImage::from_fn(8, 1, |x, _| Rgba([x as u8 * 10, 0, 0, 255]));
+        assert_eq!(run("g = r(4, 0)", &image, 1.0).get_pixel(0, 0)[1], 40);
+        // On a half-size proxy an offset of 4 full-size pixels is 2 pixels
+        assert_eq!(run("g = r(4, 0)", &image, 0.5).get_pixel(0, 0)[1], 20);
+        let program = Program::parse("g = r(4, 0)").unwrap();
+        assert_eq!(program.tiling(0.5), Tiling::Neighborhood { halo: 2 });
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_at_its_position() {
+        let depth = 10_000;
+        let source = format!("r = {}1{}", "(".repeat(depth), ")".repeat(depth));
+        let error = Program::parse(&source).err().unwrap();
+        assert!(error.message.contains("nested"));
+        assert_eq!(error.offset, "r = ".len() + MAX_DEPTH);
+
+        let source = format!("r = {}1", "-".repeat(depth));
+        assert!(Program::parse(&source).is_err());
+
+        let source = format!("r = {}1{}", "(".repeat(50), ")".repeat(50));
+        assert!(Program::parse(&source).is_ok());
+    }
+
+    #[test]
+    fn long_operator_chains_are_an_error_at_their_position() {
+        let source = format!("r = 1{}", "+1".repeat(10_000));
+        let error = Program::parse(&source).err().unwrap();
+        assert!(error.message.contains("nested"));
+        // At the operand of the `MAX_DEPTH`th `+`
+        assert_eq!(error.offset, "r = ".len() + 2 * MAX_DEPTH);
+
+        let source = format!("r = 1{}", "+1".repeat(MAX_DEPTH - 1));
+        assert!(Program::parse(&source).is_ok());
+    }
+}
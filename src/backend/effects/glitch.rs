@@ -0,0 +1,141 @@
+//! Seeded block-displacement glitches
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+
+use super::edges::{Sampler, EDGES};
+use super::random::{Rng, SEED};
+use crate::backend::{whole_image, EffectDefinition, ParamSpec, Params};
+
+/// Channel orders a channel swap picks from; all but the identity
+const PERMUTATIONS: [[usize; 3]; 5] = [[0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+pub const BLOCK_GLITCH: EffectDefinition = EffectDefinition {
+    id: "block_glitch",
+    name: "Block Glitch",
+    params: &[
+        ParamSpec::choice("shape", "Shape", &["Blocks", "Bands"], 0)
+            .with_help("Bands span the full width and only shift sideways"),
+        ParamSpec::choice(
+            "operation",
+            "Operation",
+            &["Shift", "Duplicate", "Channel Swap", "Mixed"],
+            0,
+        )
+        .with_help(
+            "Shift moves the content inside each region, Duplicate pastes a copy of it \
+             elsewhere, Channel Swap reorders its color channels, Mixed picks one per region",
+        ),
+        ParamSpec::int("count", "Count", 1, 500, 12),
+        ParamSpec::int("min_size", "Min Size", 1, 4000, 10).in_pixels(),
+        ParamSpec::int("max_size", "Max Size", 1, 4000, 80).in_pixels(),
+        ParamSpec::int("max_offset", "Max Offset", 0, 4000, 40).in_pixels(),
+        SEED,
+        EDGES,
+    ],
+    apply: apply_block_glitch,
+    tiling: whole_image,
+    threshold: None,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Shift,
+    Duplicate,
+    ChannelSwap,
+}
+
+/// Operations in the order of the `operation` choice, before "Mixed"
+const OPERATIONS: [Operation; 3] = [
+    Operation::Shift,
+    Operation::Duplicate,
+    Operation::ChannelSwap,
+];
+
+/// One glitched region
+struct Region {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    dx: i64,
+    dy: i64,
+    operation: Operation,
+    permutation: [usize; 3],
+}
+
+fn apply_block_glitch(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let source = image.into_rgba8();
+    let sampler = Sampler::new(&source, params);
+    let mut output = source.clone();
+    let (width, height) = (source.width() as i64, source.height() as i64);
+
+    let bands = params.choice("shape") == 1;
+    let (min_size, max_size) = {
+        let (a, b) = (params.int("min_size").max(1), params.int("max_size").max(1));
+        (a.min(b) as f64, a.max(b) as f64)
+    };
+    let max_offset = params.int("max_offset").max(0) as f64;
+    let operation = params.choice("operation");
+
+    let mut rng = Rng::new(params.int("seed"));
+    for _ in 0..params.int("count") {
+        // Draw everything for every region, so changing one setting doesn't
+        // reshuffle the others. Positions are fractions of the image so that
+        // previews of a downscaled copy match the full render.
+        let mut size = || (min_size + rng.next_f64() * (max_size - min_size)).round() as i64;
+        let (block_width, block_height) = (size(), size());
+        let (fx, fy) = (rng.next_f64(), rng.next_f64());
+        let (ox, oy) = (rng.next_f64() * 2.0 - 1.0, rng.next_f64() * 2.0 - 1.0);
+        let mixed = OPERATIONS[rng.index(OPERATIONS.len())];
+        let permutation = PERMUTATIONS[rng.index(PERMUTATIONS.len())];
+
+        let region_width = if bands { width } else { block_width.min(width) };
+        let region_height = block_height.min(height);
+        let region = Region {
+            x: (fx * (width - region_width + 1) as f64) as i64,
+            y: (fy * (height - region_height + 1) as f64) as i64,
+            width: region_width,
+            height: region_height,
+            dx: (ox * max_offset).round() as i64,
+            dy: if bands {
+                0
+            } else {
+                (oy * max_offset).round() as i64
+            },
+            operation: OPERATIONS.get(operation).copied().unwrap_or(mixed),
+            permutation,
+        };
+        glitch(&mut output, &source, &sampler, &region);
+    }
+    Ok(output)
+}
+
+/// Apply one region's glitch, reading from the unglitched source
+fn glitch(output: &mut RgbaImage, source: &RgbaImage, sampler: &Sampler, region: &Region) {
+    let (width, height) = (output.width() as i64, output.height() as i64);
+    for y in region.y..region.y + region.height {
+        for x in region.x..region.x + region.width {
+            match region.operation {
+                Operation::Shift => {
+                    let pixel = sampler.pixel(x - region.dx, y - region.dy);
+                    output.get_pixel_mut(x as u32, y as u32).0 = pixel.map(|c| c as u8);
+                }
+                Operation::Duplicate => {
+                    let (tx, ty) = (x + region.dx, y + region.dy);
+                    if (0..width).contains(&tx) && (0..height).contains(&ty) {
+                        *output.get_pixel_mut(tx as u32, ty as u32) =
+                            *source.get_pixel(x as u32, y as u32);
+                    }
+                }
+                Operation::ChannelSwap => {
+                    let original = source.get_pixel(x as u32, y as u32).0;
+                    let pixel = output.get_pixel_mut(x as u32, y as u32);
+                    for (channel, from) in region.permutation.iter().enumerate() {
+                        pixel.0[channel] = original[*from];
+                    }
+                }
+            }
+        }
+    }
+}
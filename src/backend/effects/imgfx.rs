@@ -0,0 +1,368 @@
+//! Effects provided by the imgfx crate
+
+use ::imgfx::{BitshiftDirection, Direction, SortBy};
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, Rgb, RgbaImage};
+
+use crate::backend::{
+    per_pixel, Channel, Channels, EffectDefinition, ParamSpec, Params, ThresholdTest, Tiling,
+};
+
+const COLOR: ParamSpec = ParamSpec::color("color", "Color", Rgb([255, 0, 0]));
+const NEGATE: ParamSpec = ParamSpec::bool("negate", "Negate", false);
+const LHS: ParamSpec = ParamSpec::channels(
+    "lhs",
+    "Left-Hand Side",
+    Channels([Channel::B, Channel::B, Channel::B]),
+);
+const RHS: ParamSpec = ParamSpec::channels(
+    "rhs",
+    "Right-Hand Side",
+    Channels([Channel::G, Channel::G, Channel::B]),
+);
+const BITS: ParamSpec = ParamSpec::int("bits", "Bits", 1, 8, 1);
+
+const BITWISE_PARAMS: &[ParamSpec] = &[COLOR, NEGATE, LHS, RHS];
+const BITSHIFT_PARAMS: &[ParamSpec] = &[BITS, NEGATE, LHS];
+const BLEND_PARAMS: &[ParamSpec] = &[COLOR, LHS, RHS];
+
+/// Sort keys in the order of the `sort_by` choice
+const SORT_KEYS: [SortBy; 7] = [
+    SortBy::Red,
+    SortBy::Green,
+    SortBy::Blue,
+    SortBy::Hue,
+    SortBy::Saturation,
+    SortBy::Luminance,
+    SortBy::Value,
+];
+
+pub const OR: EffectDefinition = EffectDefinition {
+    id: "or",
+    name: "OR",
+    params: BITWISE_PARAMS,
+    apply: apply_or,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const AND: EffectDefinition = EffectDefinition {
+    id: "and",
+    name: "AND",
+    params: BITWISE_PARAMS,
+    apply: apply_and,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const XOR: EffectDefinition = EffectDefinition {
+    id: "xor",
+    name: "XOR",
+    params: BITWISE_PARAMS,
+    apply: apply_xor,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const LEFT: EffectDefinition = EffectDefinition {
+    id: "left",
+    name: "Left",
+    params: BITSHIFT_PARAMS,
+    apply: apply_left,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const RIGHT: EffectDefinition = EffectDefinition {
+    id: "right",
+    name: "Right",
+    params: BITSHIFT_PARAMS,
+    apply: apply_right,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const ADD: EffectDefinition = EffectDefinition {
+    id: "add",
+    name: "Add",
+    params: BLEND_PARAMS,
+    apply: apply_add,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const SUB: EffectDefinition = EffectDefinition {
+    id: "sub",
+    name: "Subtract",
+    params: BLEND_PARAMS,
+    apply: apply_sub,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const MULT: EffectDefinition = EffectDefinition {
+    id: "mult",
+    name: "Multiply",
+    params: BLEND_PARAMS,
+    apply: apply_mult,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const POW: EffectDefinition = EffectDefinition {
+    id: "pow",
+    name: "Power",
+    params: BLEND_PARAMS,
+    apply: apply_pow,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const DIV: EffectDefinition = EffectDefinition {
+    id: "div",
+    name: "Divide",
+    params: BLEND_PARAMS,
+    apply: apply_div,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const AVERAGE: EffectDefinition = EffectDefinition {
+    id: "average",
+    name: "Average",
+    params: BLEND_PARAMS,
+    apply: apply_average,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const SCREEN: EffectDefinition = EffectDefinition {
+    id: "screen",
+    name: "Screen",
+    params: BLEND_PARAMS,
+    apply: apply_screen,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const OVERLAY: EffectDefinition = EffectDefinition {
+    id: "overlay",
+    name: "Overlay",
+    params: BLEND_PARAMS,
+    apply: apply_overlay,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const BLOOM: EffectDefinition = EffectDefinition {
+    id: "bloom",
+    name: "Bloom",
+    params: &[
+        ParamSpec::float("intensity", "Intensity", 0.1, 100.0, 0.1, 1.0),
+        ParamSpec::float("radius", "Blur Radius", 0.1, 500.0, 0.1, 5.0).in_pixels(),
+        ParamSpec::int("min_threshold", "Min Threshold", 0, 255, 128),
+        ParamSpec::optional_int("max_threshold", "Max Threshold", 0, 255, Some(255)),
+    ],
+    apply: apply_bloom,
+    tiling: bloom_tiling,
+    threshold: Some(bloom_threshold),
+};
+
+pub const SORT: EffectDefinition = EffectDefinition {
+    id: "sort",
+    name: "Sort",
+    params: &[
+        ParamSpec::choice(
+            "sort_by",
+            "Sort By",
+            &[
+                "Red",
+                "Green",
+                "Blue",
+                "Hue",
+                "Saturation",
+                "Luminance",
+                "Value",
+            ],
+            0,
+        ),
+        ParamSpec::choice("direction", "Direction", &["Horizontal", "Vertical"], 0),
+        ParamSpec::float("min_threshold", "Min Threshold", 0.0, 255.0, 0.1, 0.0),
+        ParamSpec::float("max_threshold", "Max Threshold", 0.0, 255.0, 0.1, 255.0),
+        ParamSpec::bool("reversed", "Reversed", false),
+    ],
+    apply: apply_sort,
+    tiling: sort_tiling,
+    threshold: Some(sort_threshold),
+};
+
+/// The lhs and rhs channel names and the color, as imgfx takes them
+fn operands(params: &Params) -> (Option<Vec<String>>, Option<Vec<String>>, Rgb<u8>) {
+    (
+        Some(params.channels("lhs").to_imgfx()),
+        Some(params.channels("rhs").to_imgfx()),
+        params.color("color"),
+    )
+}
+
+fn apply_or(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    let output = ::imgfx::bitwise::or(image, lhs, rhs, color, params.bool("negate"));
+    Ok(output)
+}
+
+fn apply_and(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    let output = ::imgfx::bitwise::and(image, lhs, rhs, color, params.bool("negate"));
+    Ok(output)
+}
+
+fn apply_xor(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    let output = ::imgfx::bitwise::xor(image, lhs, rhs, color, params.bool("negate"));
+    Ok(output)
+}
+
+fn bitshift(
+    image: DynamicImage,
+    direction: BitshiftDirection,
+    params: &Params,
+) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, _, _) = operands(params);
+    let bits = params.int("bits").clamp(1, 8) as u8;
+    let output = ::imgfx::bitshift(image, direction, lhs, bits, params.bool("negate"));
+    Ok(output)
+}
+
+fn apply_left(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    bitshift(image, BitshiftDirection::LEFT, params)
+}
+
+fn apply_right(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    bitshift(image, BitshiftDirection::RIGHT, params)
+}
+
+fn apply_add(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::add(image, lhs, rhs, color))
+}
+
+fn apply_sub(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::sub(image, lhs, rhs, color, false))
+}
+
+fn apply_mult(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::mult(image, lhs, rhs, color))
+}
+
+fn apply_pow(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::pow(image, lhs, rhs, color))
+}
+
+fn apply_div(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::div(image, lhs, rhs, color))
+}
+
+fn apply_average(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::average(image, lhs, rhs, color))
+}
+
+fn apply_screen(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::screen(image, lhs, rhs, color))
+}
+
+fn apply_overlay(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let (lhs, rhs, color) = operands(params);
+    Ok(::imgfx::overlay(image, lhs, rhs, color))
+}
+
+fn apply_bloom(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let output = ::imgfx::bloom(
+        image,
+        params.float("intensity"),
+        params.float("radius"),
+        params.int("min_threshold").clamp(0, 255) as u8,
+        params
+            .optional_int("max_threshold")
+            .map(|max| max.clamp(0, 255) as u8),
+    );
+    Ok(output)
+}
+
+fn bloom_tiling(params: &Params) -> Tiling {
+    // fast_blur runs three box passes of roughly `radius` each way
+    Tiling::Neighborhood {
+        halo: (params.float("radius").max(0.0) * 3.0).ceil() as u32 + 4,
+    }
+}
+
+/// The light mask bloom blurs: luminance strictly between the thresholds
+fn bloom_threshold(params: &Params) -> ThresholdTest {
+    let min = params.int("min_threshold").clamp(0, 255) as f64;
+    let max = params
+        .optional_int("max_threshold")
+        .map(|max| max.clamp(0, 255) as f64);
+    Box::new(move |pixel| {
+        let luminance = ::imgfx::calc_luminance(*pixel);
+        luminance > min && max.is_none_or(|max| luminance < max)
+    })
+}
+
+fn sort_direction(params: &Params) -> Direction {
+    match params.choice("direction") {
+        1 => Direction::Vertical,
+        _ => Direction::Horizontal,
+    }
+}
+
+fn apply_sort(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let sort_by = SORT_KEYS
+        .get(params.choice("sort_by"))
+        .copied()
+        .unwrap_or(SortBy::Red);
+    let output = ::imgfx::sort(
+        image.into(),
+        sort_direction(params),
+        sort_by,
+        params.float("min_threshold"),
+        params.float("max_threshold"),
+        params.bool("reversed"),
+    );
+    Ok(output)
+}
+
+/// The pixels sort moves: the sort metric strictly between the thresholds,
+/// measured as imgfx does, so hue runs 0-360 and saturation and value 0-1
+fn sort_threshold(params: &Params) -> ThresholdTest {
+    let sort_by = SORT_KEYS
+        .get(params.choice("sort_by"))
+        .copied()
+        .unwrap_or(SortBy::Red);
+    let (min, max) = (params.float("min_threshold"), params.float("max_threshold"));
+    Box::new(move |pixel| {
+        let (hue, saturation, value) = ::imgfx::rgb_to_hsv(*pixel);
+        let metric = match sort_by {
+            SortBy::Luminance => ::imgfx::calc_luminance(*pixel),
+            SortBy::Red => pixel.0[0] as f64,
+            SortBy::Green => pixel.0[1] as f64,
+            SortBy::Blue => pixel.0[2] as f64,
+            SortBy::Hue => hue,
+            SortBy::Saturation => saturation,
+            SortBy::Value => value,
+        };
+        metric > min && metric < max
+    })
+}
+
+fn sort_tiling(params: &Params) -> Tiling {
+    match sort_direction(params) {
+        Direction::Horizontal => Tiling::Rows,
+        Direction::Vertical => Tiling::Columns,
+    }
+}
@@ -0,0 +1,93 @@
+//! Built-in effects. Each module declares its effects' parameters once; the
+//! UI, chain files and dispatch are generated from them.
+
+use std::sync::Arc;
+
+use super::{EffectDefinition, ImageEffect};
+
+mod adjust;
+mod convolve;
+mod databend;
+mod distort;
+mod dither;
+mod edges;
+mod expression;
+mod glitch;
+mod imgfx;
+mod noise;
+mod palette;
+mod quantize;
+mod random;
+mod rgb_split;
+mod script;
+
+/// Effects written as plain declarations
+static DEFINITIONS: &[EffectDefinition] = &[
+    imgfx::OR,
+    imgfx::AND,
+    imgfx::XOR,
+    imgfx::LEFT,
+    imgfx::RIGHT,
+    imgfx::ADD,
+    imgfx::SUB,
+    imgfx::MULT,
+    imgfx::POW,
+    imgfx::DIV,
+    imgfx::AVERAGE,
+    imgfx::SCREEN,
+    imgfx::OVERLAY,
+    imgfx::BLOOM,
+    imgfx::SORT,
+    convolve::GAUSSIAN_BLUR,
+    convolve::BOX_BLUR,
+    convolve::UNSHARP_MASK,
+    convolve::EMBOSS,
+    dither::DITHER,
+    quantize::PALETTE_MAP,
+    rgb_split::RGB_SPLIT,
+    glitch::BLOCK_GLITCH,
+    databend::JPEG_RECOMPRESS,
+    databend::DATABEND,
+    distort::WAVE,
+    distort::TWIRL,
+    distort::PINCH,
+    distort::POLAR,
+    distort::DISPLACE,
+    noise::NOISE,
+    noise::FILM_GRAIN,
+    adjust::LEVELS,
+    adjust::CURVES,
+    adjust::HUE_SATURATION,
+    adjust::BRIGHTNESS_CONTRAST,
+    adjust::EXPOSURE,
+    adjust::GAMMA,
+];
+
+/// Built-in effects, in the order they are offered in the UI
+pub fn builtin_effects() -> Vec<Arc<dyn ImageEffect>> {
+    let mut effects: Vec<Arc<dyn ImageEffect>> = DEFINITIONS
+        .iter()
+        .map(|definition| Arc::new(*definition) as Arc<dyn ImageEffect>)
+        .collect();
+    effects.push(Arc::new(convolve::KernelEffect));
+    effects.push(Arc::new(expression::ExpressionEffect));
+    effects.push(Arc::new(script::ScriptEffect));
+    effects
+}
+
+/// Error message for user-written code, followed by the offending line with
+/// a caret under the 1-based `column`
+fn mark_error(source: &str, line: usize, column: usize, message: &str) -> String {
+    let text = source
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default();
+    format!(
+        "Line {}, column {}: {}\n{}\n{}^",
+        line,
+        column,
+        message,
+        text,
+        " ".repeat(column.saturating_sub(1))
+    )
+}
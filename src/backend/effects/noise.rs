@@ -0,0 +1,225 @@
+//! Seeded noise and film grain
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+
+use super::random::{Rng, SEED};
+use crate::backend::{per_pixel, EffectDefinition, ParamSpec, Params, TileArea};
+use crate::utils::luminance;
+
+const AMOUNT: ParamSpec = ParamSpec::float("amount", "Amount (%)", 0.0, 100.0, 0.5, 10.0);
+
+const COLOR: ParamSpec = ParamSpec::choice("color", "Color", &["Monochrome", "Per Channel"], 0)
+    .with_help("Monochrome moves all channels of a pixel together; Per Channel adds colored noise");
+
+/// How strongly the noise shows depending on the brightness of the pixel
+const fn response(default: usize) -> ParamSpec {
+    ParamSpec::choice(
+        "response",
+        "Strongest In",
+        &["Everywhere", "Shadows", "Midtones", "Highlights"],
+        default,
+    )
+}
+
+pub const NOISE: EffectDefinition = EffectDefinition {
+    id: "noise",
+    name: "Noise",
+    params: &[
+        ParamSpec::choice(
+            "distribution",
+            "Distribution",
+            &["Uniform", "Gaussian", "Salt and Pepper"],
+            1,
+        )
+        .with_help(
+            "Uniform and Gaussian shift values by up to about the amount; Salt and Pepper \
+             turns that percentage of pixels black or white",
+        ),
+        AMOUNT,
+        COLOR,
+        response(0),
+        SEED,
+    ],
+    apply: apply_noise,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+pub const FILM_GRAIN: EffectDefinition = EffectDefinition {
+    id: "film_grain",
+    name: "Film Grain",
+    params: &[
+        AMOUNT,
+        ParamSpec::float("size", "Grain Size", 1.0, 50.0, 0.1, 1.5)
+            .in_pixels()
+            .with_help("Width of a grain clump"),
+        COLOR,
+        response(2),
+        SEED,
+    ],
+    apply: apply_film_grain,
+    tiling: per_pixel,
+    threshold: None,
+};
+
+/// Strength of the noise on a pixel of 0-255 `color`, from 0 to 1
+fn strength(params: &Params, color: [f32; 3]) -> f32 {
+    let luminance = luminance(color) / 255.0;
+    match params.choice("response") {
+        1 => (1.0 - luminance).powi(2),
+        2 => 4.0 * luminance * (1.0 - luminance),
+        3 => luminance.powi(2),
+        _ => 1.0,
+    }
+}
+
+/// Add `offsets`, in 0-255 units, to the color channels of every pixel
+/// scaled by its response
+fn add_offsets(
+    mut image: RgbaImage,
+    params: &Params,
+    mut offsets: impl FnMut(u32, u32) -> [f32; 3],
+) -> RgbaImage {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let color = [0, 1, 2].map(|channel| pixel.0[channel] as f32);
+        let scale = strength(params, color);
+        let offsets = offsets(x, y);
+        for channel in 0..3 {
+            let value = color[channel] + offsets[channel] * scale;
+            pixel.0[channel] = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    image
+}
+
+/// Generator for the pixel at `x`, `y` of a tile at `area`, as if `draws`
+/// numbers had been drawn for each pixel before it in the full image
+fn pixel_rng(seed: i64, area: TileArea, x: u32, y: u32, draws: u64) -> Rng {
+    let index = (area.y + y) as u64 * area.full_width as u64 + (area.x + x) as u64;
+    let mut rng = Rng::new(seed);
+    rng.skip(index * draws);
+    rng
+}
+
+fn apply_noise(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let image = image.into_rgba8();
+    let area = TileArea::from_params(params, image.width(), image.height());
+    let amount = params.float(AMOUNT.key) as f32 / 100.0;
+    let monochrome = params.choice(COLOR.key) == 0;
+    let seed = params.int("seed");
+
+    if params.choice("distribution") == 2 {
+        return Ok(salt_and_pepper(image, params, amount, monochrome, area));
+    }
+    let gaussian = params.choice("distribution") == 1;
+    // A Gaussian value takes two numbers, a uniform one a single number
+    let draws = if gaussian { 2 } else { 1 } * if monochrome { 1 } else { 3 };
+    Ok(add_offsets(image, params, |x, y| {
+        let mut rng = pixel_rng(seed, area, x, y, draws);
+        // Gaussian noise with a standard deviation of half the amount stays
+        // within the amount for 95% of pixels, close to the uniform range
+        let mut draw = || {
+            let value = if gaussian {
+                rng.gaussian() * 0.5
+            } else {
+                rng.next_f64() * 2.0 - 1.0
+            };
+            value as f32 * amount * 255.0
+        };
+        if monochrome {
+            [draw(); 3]
+        } else {
+            [draw(), draw(), draw()]
+        }
+    }))
+}
+
+fn salt_and_pepper(
+    mut image: RgbaImage,
+    params: &Params,
+    amount: f32,
+    monochrome: bool,
+    area: TileArea,
+) -> RgbaImage {
+    // Two numbers per channel, whether or not it is hit
+    let draws = if monochrome { 2 } else { 6 };
+    let seed = params.int("seed");
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let mut rng = pixel_rng(seed, area, x, y, draws);
+        let color = [0, 1, 2].map(|channel| pixel.0[channel] as f32);
+        let probability = (amount * strength(params, color)) as f64;
+        if monochrome {
+            let (hit, white) = (rng.next_f64() < probability, rng.next_f64() < 0.5);
+            if hit {
+                let value = if white { 255 } else { 0 };
+                pixel.0[..3].fill(value);
+            }
+        } else {
+            for channel in 0..3 {
+                let (hit, white) = (rng.next_f64() < probability, rng.next_f64() < 0.5);
+                if hit {
+                    pixel.0[channel] = if white { 255 } else { 0 };
+                }
+            }
+        }
+    }
+    image
+}
+
+fn apply_film_grain(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let image = image.into_rgba8();
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(image);
+    }
+    let area = TileArea::from_params(params, width, height);
+    let amount = params.float(AMOUNT.key) as f32 / 100.0 * 255.0;
+    let size = params.float("size").max(1.0) as f32;
+
+    // Grain is Gaussian noise on a grid of `size` pixels over the full
+    // image, smoothly interpolated so neighboring pixels clump together.
+    // Only the grid points around the tile are drawn.
+    let columns = (area.full_width as f32 / size).ceil() as usize + 2;
+    let rows = (area.full_height as f32 / size).ceil() as usize + 2;
+    let cell = |position: u32| (position as f32 / size) as usize;
+    let (first_column, first_row) = (cell(area.x), cell(area.y));
+    let local_columns = cell(area.x + width - 1) + 2 - first_column;
+    let local_rows = cell(area.y + height - 1) + 2 - first_row;
+    let layers = if params.choice(COLOR.key) == 0 { 1 } else { 3 };
+    let seed = params.int("seed");
+    let grids: Vec<Vec<f32>> = (0..layers)
+        .map(|layer| {
+            let mut grid = Vec::with_capacity(local_columns * local_rows);
+            for row in first_row..first_row + local_rows {
+                for column in first_column..first_column + local_columns {
+                    let index = (layer * rows + row) * columns + column;
+                    let mut rng = Rng::new(seed);
+                    rng.skip(index as u64 * 2);
+                    grid.push(rng.gaussian() as f32 * 0.5 * amount);
+                }
+            }
+            grid
+        })
+        .collect();
+
+    let sample = |grid: &[f32], x: u32, y: u32| {
+        let (gx, gy) = ((area.x + x) as f32 / size, (area.y + y) as f32 / size);
+        let (column, row) = (gx as usize - first_column, gy as usize - first_row);
+        let (tx, ty) = (smooth(gx.fract()), smooth(gy.fract()));
+        let at = |column: usize, row: usize| grid[row * local_columns + column];
+        let top = at(column, row) + (at(column + 1, row) - at(column, row)) * tx;
+        let bottom = at(column, row + 1) + (at(column + 1, row + 1) - at(column, row + 1)) * tx;
+        top + (bottom - top) * ty
+    };
+
+    Ok(add_offsets(image, params, |x, y| match grids.as_slice() {
+        [grid] => [sample(grid, x, y); 3],
+        grids => [0, 1, 2].map(|channel| sample(&grids[channel], x, y)),
+    }))
+}
+
+/// Smoothstep, so grain clumps have no visible grid lines
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,245 @@
+//! Palettes for effects that reduce an image to a few colors
+
+use dioxus::prelude::ServerFnError;
+use image::{Rgb, RgbaImage};
+
+use crate::backend::{ParamSpec, Params};
+use crate::utils::{extract_palette, rgb_to_lab, rgb_to_oklab};
+
+const DEFAULT_PALETTE: &[Rgb<u8>] = &[Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+
+/// Rounds of k-means refinement at most
+const K_MEANS_ITERATIONS: usize = 16;
+
+/// Pixels k-means looks at, spread evenly over the image
+const K_MEANS_SAMPLES: usize = 20_000;
+
+/// Where the colors come from: the `palette` parameter or the image itself
+pub const PALETTE_SOURCE: ParamSpec = ParamSpec::choice(
+    "palette_source",
+    "Palette",
+    &["Custom", "Median Cut", "K-Means"],
+    0,
+);
+
+pub const PALETTE: ParamSpec = ParamSpec::palette("palette", "Custom Palette", DEFAULT_PALETTE);
+
+pub const PALETTE_SIZE: ParamSpec = ParamSpec::int("palette_size", "Colors from Image", 2, 256, 8)
+    .with_help("Number of colors to compute when the palette comes from the image");
+
+/// Space colors are compared in
+pub const DISTANCE: ParamSpec =
+    ParamSpec::choice("distance", "Color Distance", &["RGB", "CIELAB", "OKLab"], 0)
+        .with_help("CIELAB and OKLab match colors more like the eye does");
+
+/// Color spaces in the order of the `distance` choice
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Space {
+    Rgb,
+    Lab,
+    Oklab,
+}
+
+impl Space {
+    fn from_params(params: &Params) -> Space {
+        match params.choice(DISTANCE.key) {
+            1 => Space::Lab,
+            2 => Space::Oklab,
+            _ => Space::Rgb,
+        }
+    }
+
+    /// Coordinates of a 0-255 RGB color in the space
+    fn convert(self, color: [f32; 3]) -> [f32; 3] {
+        match self {
+            Space::Rgb => color,
+            Space::Lab => rgb_to_lab(color),
+            Space::Oklab => rgb_to_oklab(color),
+        }
+    }
+}
+
+/// Whether the palette is computed from the image, so every pixel depends
+/// on the whole image
+pub fn palette_from_image(params: &Params) -> bool {
+    params.choice(PALETTE_SOURCE.key) != 0
+}
+
+/// The colors chosen by the palette parameters
+pub fn palette(image: &RgbaImage, params: &Params) -> Result<Vec<Rgb<u8>>, ServerFnError> {
+    let size = params.int(PALETTE_SIZE.key).max(1) as usize;
+    let colors = match params.choice(PALETTE_SOURCE.key) {
+        0 => params.palette(PALETTE.key).to_vec(),
+        1 => extract_palette(image, size),
+        _ => k_means(image, size, Space::from_params(params)),
+    };
+    if colors.is_empty() {
+        return Err(ServerFnError::new("The palette has no colors"));
+    }
+    Ok(colors)
+}
+
+/// Finds the closest palette color in the space chosen by `distance`
+pub struct Matcher {
+    space: Space,
+    /// Palette colors in `space`
+    colors: Vec<[f32; 3]>,
+}
+
+impl Matcher {
+    pub fn new(palette: &[Rgb<u8>], params: &Params) -> Matcher {
+        Matcher::in_space(palette, Space::from_params(params))
+    }
+
+    fn in_space(palette: &[Rgb<u8>], space: Space) -> Matcher {
+        let colors = palette
+            .iter()
+            .map(|color| space.convert(color.0.map(f32::from)))
+            .collect();
+        Matcher { space, colors }
+    }
+
+    /// Index of the palette color closest to a 0-255 RGB color
+    pub fn nearest(&self, color: [f32; 3]) -> usize {
+        self.nearest_converted(self.space.convert(color))
+    }
+
+    /// Like `nearest`, for a color already converted to the space
+    fn nearest_converted(&self, color: [f32; 3]) -> usize {
+        let distance = |candidate: &[f32; 3]| {
+            candidate
+                .iter()
+                .zip(color)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f32>()
+        };
+        self.colors
+            .iter()
+            .map(distance)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .unwrap_or(0)
+    }
+}
+
+/// `count` colors that minimize the distance of the pixels to their nearest
+/// color, refined from the median cut palette
+fn k_means(image: &RgbaImage, count: usize, space: Space) -> Vec<Rgb<u8>> {
+    let mut centers = extract_palette(image, count);
+    if centers.is_empty() {
+        return centers;
+    }
+
+    let pixel_count = image.width() as usize * image.height() as usize;
+    let step = (pixel_count / K_MEANS_SAMPLES).max(1);
+    let samples: Vec<([f32; 3], [f32; 3])> = image
+        .pixels()
+        .step_by(step)
+        .filter(|pixel| pixel.0[3] > 0)
+        .map(|pixel| {
+            let color = [0, 1, 2].map(|channel| pixel.0[channel] as f32);
+            (color, space.convert(color))
+        })
+        .collect();
+
+    let mut assignments = vec![usize::MAX; samples.len()];
+    for _ in 0..K_MEANS_ITERATIONS {
+        let matcher = Matcher::in_space(&centers, space);
+        let mut changed = false;
+        for ((_, converted), assignment) in samples.iter().zip(&mut assignments) {
+            let nearest = matcher.nearest_converted(*converted);
+            changed |= nearest != *assignment;
+            *assignment = nearest;
+        }
+        if !changed {
+            break;
+        }
+
+        // Move every center to the mean of its pixels; empty ones stay put
+        let mut sums = vec![([0.0f64; 3], 0usize); centers.len()];
+        for ((color, _), assignment) in samples.iter().zip(&assignments) {
+            let (sum, members) = &mut sums[*assignment];
+            for (total, value) in sum.iter_mut().zip(color) {
+                *total += *value as f64;
+            }
+            *members += 1;
+        }
+        for (center, (sum, members)) in centers.iter_mut().zip(sums) {
+            if members > 0 {
+                *center = Rgb(sum.map(|total| (total / members as f64).round() as u8));
+            }
+        }
+    }
+
+    let mut palette: Vec<Rgb<u8>> = Vec::with_capacity(centers.len());
+    for center in centers {
+        if !palette.contains(&center) {
+            palette.push(center);
+        }
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const SPACES: [Space; 3] = [Space::Rgb, Space::Lab, Space::Oklab];
+
+    #[test]
+    fn matcher_finds_palette_colors_in_every_space() {
+        let palette = [
+            Rgb([0, 0, 0]),
+            Rgb([255, 255, 255]),
+            Rgb([220, 30, 30]),
+            Rgb([30, 30, 220]),
+        ];
+        for space in SPACES {
+            let matcher = Matcher::in_space(&palette, space);
+            for (index, color) in palette.iter().enumerate() {
+                assert_eq!(matcher.nearest(color.0.map(f32::from)), index);
+            }
+            assert_eq!(matcher.nearest([250.0, 60.0, 40.0]), 2, "{:?}", space);
+            assert_eq!(matcher.nearest([20.0, 10.0, 15.0]), 0, "{:?}", space);
+        }
+        assert_eq!(Matcher::in_space(&[], Space::Lab).nearest([1.0; 3]), 0);
+    }
+
+    #[test]
+    fn k_means_finds_cluster_means() {
+        // Two clusters, each alternating between two shades, and a
+        // transparent pixel that must not count
+        let image = RgbaImage::from_fn(8, 2, |x, y| match (x, y) {
+            (7, 1) => Rgba([0, 255, 0, 0]),
+            (_, 0) => Rgba([240 + (x % 2) as u8 * 10, 0, 0, 255]),
+            _ => Rgba([0, 0, 200 + (x % 2) as u8 * 20, 255]),
+        });
+        for space in SPACES {
+            let mut palette = k_means(&image, 2, space);
+            palette.sort_by_key(|color| color.0);
+            // Four blues of 200 and three of 220 are left next to the
+            // transparent pixel
+            assert_eq!(palette, [Rgb([0, 0, 209]), Rgb([245, 0, 0])], "{:?}", space);
+        }
+    }
+
+    #[test]
+    fn k_means_never_repeats_colors() {
+        let image = RgbaImage::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                Rgba([10, 20, 30, 255])
+            } else {
+                Rgba([200, 100, 50, 255])
+            }
+        });
+        let palette = k_means(&image, 8, Space::Oklab);
+        assert_eq!(palette.len(), 2);
+        assert!(palette.contains(&Rgb([10, 20, 30])));
+        assert!(palette.contains(&Rgb([200, 100, 50])));
+
+        let transparent = RgbaImage::new(4, 4);
+        assert!(k_means(&transparent, 4, Space::Rgb).is_empty());
+    }
+}
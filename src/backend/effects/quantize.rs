@@ -0,0 +1,72 @@
+//! Reducing an image to a palette or to a few levels per channel
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
+
+use super::palette::{
+    palette, palette_from_image, Matcher, DISTANCE, PALETTE, PALETTE_SIZE, PALETTE_SOURCE,
+};
+use crate::backend::{EffectDefinition, ParamSpec, Params, Tiling};
+
+pub const PALETTE_MAP: EffectDefinition = EffectDefinition {
+    id: "palette_map",
+    name: "Palette Map",
+    params: &[
+        ParamSpec::choice("mode", "Mode", &["Palette", "Posterize"], 0).with_help(
+            "Palette maps every pixel to the nearest palette color; Posterize rounds each \
+             channel to a number of levels",
+        ),
+        PALETTE_SOURCE,
+        PALETTE,
+        PALETTE_SIZE,
+        DISTANCE,
+        ParamSpec::int("red_levels", "Red Levels", 2, 256, 4),
+        ParamSpec::int("green_levels", "Green Levels", 2, 256, 4),
+        ParamSpec::int("blue_levels", "Blue Levels", 2, 256, 4),
+    ],
+    apply: apply_palette_map,
+    tiling: palette_map_tiling,
+    threshold: None,
+};
+
+fn posterize(params: &Params) -> bool {
+    params.choice("mode") == 1
+}
+
+fn apply_palette_map(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let mut image = image.into_rgba8();
+
+    if posterize(params) {
+        let levels = ["red_levels", "green_levels", "blue_levels"]
+            .map(|key| (params.int(key).max(2) - 1) as f32);
+        for pixel in image.pixels_mut() {
+            for (channel, levels) in pixel.0[..3].iter_mut().zip(levels) {
+                let level = (*channel as f32 / 255.0 * levels).round();
+                *channel = (level / levels * 255.0).round() as u8;
+            }
+        }
+        return Ok(image);
+    }
+
+    let palette = palette(&image, params)?;
+    let matcher = Matcher::new(&palette, params);
+    // Images repeat colors a lot, and CIELAB/OKLab conversions aren't free
+    let mut cache: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in image.pixels_mut() {
+        let color = [pixel.0[0], pixel.0[1], pixel.0[2]];
+        let index = *cache
+            .entry(color)
+            .or_insert_with(|| matcher.nearest(color.map(f32::from)));
+        pixel.0[..3].copy_from_slice(&palette[index].0);
+    }
+    Ok(image)
+}
+
+fn palette_map_tiling(params: &Params) -> Tiling {
+    if !posterize(params) && palette_from_image(params) {
+        Tiling::Whole
+    } else {
+        Tiling::PerPixel
+    }
+}
@@ -0,0 +1,69 @@
+//! Seeded randomness for effects.
+//!
+//! A small generator of our own rather than a crate, so a seed gives the
+//! same result on every platform and in every version of the app.
+
+use crate::backend::ParamSpec;
+
+/// The `seed` parameter shared by random effects
+pub const SEED: ParamSpec = ParamSpec::int("seed", "Seed", 0, 999_999, 1);
+
+/// Step of the SplitMix64 state per number drawn
+const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 generator
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: i64) -> Rng {
+        Rng(seed as u64)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(GAMMA);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Continue as if `count` numbers had been drawn, without drawing them,
+    /// so e.g. the numbers for one pixel can be found without the ones for
+    /// the pixels before it
+    pub fn skip(&mut self, count: u64) {
+        self.0 = self.0.wrapping_add(count.wrapping_mul(GAMMA));
+    }
+
+    /// Uniform in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Normally distributed with mean 0 and standard deviation 1
+    pub fn gaussian(&mut self) -> f64 {
+        // Box-Muller; 1 - u keeps the logarithm finite
+        let (u, v) = (1.0 - self.next_f64(), self.next_f64());
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    }
+
+    /// Uniform index into a collection of `len` items
+    pub fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len.max(1) as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipping_matches_drawing() {
+        let mut drawn = Rng::new(42);
+        for _ in 0..1000 {
+            drawn.next_u64();
+        }
+        let mut skipped = Rng::new(42);
+        skipped.skip(1000);
+        assert_eq!(skipped.next_u64(), drawn.next_u64());
+    }
+}
@@ -0,0 +1,106 @@
+//! Moving the color channels apart, for channel-split and chromatic
+//! aberration looks
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, RgbaImage};
+
+use super::edges::{Edges, Sampler, EDGES, SAMPLING};
+use crate::backend::{EffectDefinition, ParamSpec, Params, TileArea, Tiling};
+
+/// Keys of the per-channel offsets, in channel order
+const OFFSET_KEYS: [(&str, &str); 3] = [
+    ("red_x", "red_y"),
+    ("green_x", "green_y"),
+    ("blue_x", "blue_y"),
+];
+
+/// Keys of the per-channel radial amounts, in channel order
+const AMOUNT_KEYS: [&str; 3] = ["red_amount", "green_amount", "blue_amount"];
+
+pub const RGB_SPLIT: EffectDefinition = EffectDefinition {
+    id: "rgb_split",
+    name: "RGB Split",
+    params: &[
+        ParamSpec::choice("mode", "Mode", &["Offset", "Radial"], 0).with_help(
+            "Offset moves each channel by its x/y vector; Radial scales each channel away \
+             from the center by its amount",
+        ),
+        ParamSpec::float("red_x", "Red X", -500.0, 500.0, 0.5, -5.0).in_pixels(),
+        ParamSpec::float("red_y", "Red Y", -500.0, 500.0, 0.5, 0.0).in_pixels(),
+        ParamSpec::float("green_x", "Green X", -500.0, 500.0, 0.5, 0.0).in_pixels(),
+        ParamSpec::float("green_y", "Green Y", -500.0, 500.0, 0.5, 0.0).in_pixels(),
+        ParamSpec::float("blue_x", "Blue X", -500.0, 500.0, 0.5, 5.0).in_pixels(),
+        ParamSpec::float("blue_y", "Blue Y", -500.0, 500.0, 0.5, 0.0).in_pixels(),
+        ParamSpec::float("center_x", "Center X", 0.0, 1.0, 0.01, 0.5)
+            .with_help("Center of the radial split, as a fraction of the width"),
+        ParamSpec::float("center_y", "Center Y", 0.0, 1.0, 0.01, 0.5)
+            .with_help("Center of the radial split, as a fraction of the height"),
+        ParamSpec::float("red_amount", "Red Amount (%)", -20.0, 20.0, 0.1, 1.0),
+        ParamSpec::float("green_amount", "Green Amount (%)", -20.0, 20.0, 0.1, 0.0),
+        ParamSpec::float("blue_amount", "Blue Amount (%)", -20.0, 20.0, 0.1, -1.0),
+        EDGES,
+        SAMPLING,
+    ],
+    apply: apply_rgb_split,
+    tiling: rgb_split_tiling,
+    threshold: None,
+};
+
+fn radial(params: &Params) -> bool {
+    params.choice("mode") == 1
+}
+
+fn apply_rgb_split(image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+    let source = image.into_rgba8();
+    let (width, height) = source.dimensions();
+    let sampler = Sampler::new(&source, params);
+    let area = TileArea::from_params(params, width, height);
+
+    // Where each channel of an output pixel is read from
+    let offsets = OFFSET_KEYS.map(|(x, y)| (params.float(x) as f32, params.float(y) as f32));
+    let amounts = AMOUNT_KEYS.map(|key| params.float(key) as f32 / 100.0);
+    let center = (
+        params.float("center_x") as f32 * (width as f32 - 1.0),
+        params.float("center_y") as f32 * (height as f32 - 1.0),
+    );
+    let radial = radial(params);
+    let position = |channel: usize, x: f32, y: f32| {
+        if radial {
+            // Sampling closer to the center spreads the channel outwards
+            let scale = 1.0 / (1.0 + amounts[channel]);
+            (
+                center.0 + (x - center.0) * scale,
+                center.1 + (y - center.1) * scale,
+            )
+        } else {
+            (x - offsets[channel].0, y - offsets[channel].1)
+        }
+    };
+
+    let mut output = RgbaImage::new(width, height);
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let mut alpha = 0.0f32;
+        for channel in 0..3 {
+            let (sx, sy) = position(channel, (x + area.x) as f32, (y + area.y) as f32);
+            let sample = sampler.sample(sx, sy);
+            pixel.0[channel] = sample[channel].round().clamp(0.0, 255.0) as u8;
+            alpha = alpha.max(sample[3]);
+        }
+        pixel.0[3] = alpha.round().clamp(0.0, 255.0) as u8;
+    }
+    Ok(output)
+}
+
+fn rgb_split_tiling(params: &Params) -> Tiling {
+    if radial(params) || Edges::from_params(params).needs_whole_image() {
+        return Tiling::Whole;
+    }
+    let reach = OFFSET_KEYS
+        .iter()
+        .flat_map(|(x, y)| [params.float(x), params.float(y)])
+        .fold(0.0f64, |reach, offset| reach.max(offset.abs()));
+    // One more pixel for bilinear sampling
+    Tiling::Neighborhood {
+        halo: reach.ceil() as u32 + 1,
+    }
+}
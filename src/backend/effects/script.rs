@@ -0,0 +1,190 @@
+//! Script effect: runs a user-written Rhai script over the pixel buffer.
+//!
+//! The engine is sandboxed: scripts can't import modules, `eval` code or
+//! print, and run under limits on operations, call depth and data sizes so a
+//! runaway loop ends with an error instead of hanging the app.
+
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, Rgb, RgbaImage};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Blob, Dynamic, Engine, Map, Scope, FLOAT, INT};
+use std::time::Duration;
+use web_time::Instant;
+
+use super::mark_error;
+use crate::backend::{ImageEffect, ParamSpec, Params};
+
+const DEFAULT_SCRIPT: &str = "\
+// Blend each pixel towards its negative by params.amount
+let strength = params.amount;
+for i in 0..width * height {
+    let o = i * 4;
+    for c in 0..3 {
+        let v = pixels[o + c];
+        pixels[o + c] = byte(v + (255 - 2 * v) * strength);
+    }
+}";
+
+const HELP: &str = "Rhai script. `pixels` is the image as RGBA bytes, row by row, with \
+`width` and `height`; modify it in place or return a new buffer of the same size. \
+`params.amount` (0-1), `params.value` (0-255) and `params.color` ([r, g, b]) come from the \
+inputs below. `byte(v)` rounds and clamps to 0-255. Scripts that run too long are stopped.";
+
+const PARAMS: &[ParamSpec] = &[
+    ParamSpec::text("script", "Script", 10, DEFAULT_SCRIPT)
+        .with_help(HELP)
+        .saveable(),
+    ParamSpec::float("amount", "Amount", 0.0, 1.0, 0.01, 1.0),
+    ParamSpec::int("value", "Value", 0, 255, 128),
+    ParamSpec::color("color", "Color", Rgb([255, 0, 0])),
+    ParamSpec::int("operations", "Operations per Pixel", 10, 100_000, 1_000),
+];
+
+/// Operations allowed on top of the per-pixel budget, for setup code
+const BASE_OPERATIONS: u64 = 1_000_000;
+
+/// Wall-clock limit. The operation budget grows with the image, so on its
+/// own it can't keep a runaway script from freezing the browser tab, where
+/// effects run on the UI thread; the limit is shorter there.
+#[cfg(not(target_family = "wasm"))]
+const TIME_LIMIT: Duration = Duration::from_secs(30);
+#[cfg(target_family = "wasm")]
+const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+pub struct ScriptEffect;
+
+impl ImageEffect for ScriptEffect {
+    fn id(&self) -> &'static str {
+        "script"
+    }
+
+    fn name(&self) -> &'static str {
+        "Script"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        PARAMS
+    }
+
+    fn apply(&self, image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+        let image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        let pixel_count = width as u64 * height as u64;
+        let engine = sandboxed_engine(
+            pixel_count,
+            params.int("operations").max(1) as u64 * pixel_count + BASE_OPERATIONS,
+        );
+
+        let source = params.text("script");
+        let ast = engine
+            .compile(source)
+            .map_err(|e| ServerFnError::new(format!("Script error: {}", e)))?;
+
+        let [r, g, b] = params.color("color").0;
+        let mut script_params = Map::new();
+        script_params.insert("amount".into(), Dynamic::from(params.float("amount")));
+        script_params.insert("value".into(), Dynamic::from(params.int("value")));
+        script_params.insert(
+            "color".into(),
+            Dynamic::from(Array::from([r, g, b].map(|c| Dynamic::from(c as INT)))),
+        );
+
+        let mut scope = Scope::new();
+        scope.push("pixels", image.into_raw());
+        scope.push_constant("width", width as INT);
+        scope.push_constant("height", height as INT);
+        scope.push_constant("params", script_params);
+
+        let result = engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &ast)
+            .map_err(|e| ServerFnError::new(format!("Script error: {}", e)))?;
+        let pixels = if result.is_blob() {
+            result.cast::<Blob>()
+        } else {
+            scope
+                .get_value::<Blob>("pixels")
+                .ok_or_else(|| ServerFnError::new("Script error: `pixels` is no longer a buffer"))?
+        };
+
+        let expected = pixel_count as usize * 4;
+        if pixels.len() != expected {
+            return Err(ServerFnError::new(format!(
+                "Script error: returned {} bytes, expected {} ({}×{} RGBA)",
+                pixels.len(),
+                expected,
+                width,
+                height
+            )));
+        }
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| ServerFnError::new("Script error: invalid pixel buffer"))
+    }
+
+    fn check_param(&self, key: &str, params: &Params) -> Option<String> {
+        if key != "script" {
+            return None;
+        }
+        let source = params.text(key);
+        let error = sandboxed_engine(0, 0).compile(source).err()?;
+        let position = error.1;
+        Some(mark_error(
+            source,
+            position.line().unwrap_or(1),
+            position.position().unwrap_or(1),
+            &error.0.to_string(),
+        ))
+    }
+}
+
+/// Engine without access to anything outside the script, with limits sized
+/// for an image of `pixel_count` pixels
+fn sandboxed_engine(pixel_count: u64, max_operations: u64) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .on_print(|_| {})
+        .on_debug(|_, _, _| {})
+        .set_max_operations(max_operations)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(1 << 20)
+        // Room for a few copies of the pixel buffer, no more
+        .set_max_array_size((pixel_count as usize * 4 * 4).max(1 << 16))
+        .set_max_map_size(1 << 16);
+
+    let start = Instant::now();
+    engine.on_progress(move |operations| {
+        // Checking the clock on every operation would slow scripts down
+        if operations % 10_000 == 0 && start.elapsed() > TIME_LIMIT {
+            Some(format!("Script ran longer than {} seconds", TIME_LIMIT.as_secs()).into())
+        } else {
+            None
+        }
+    });
+
+    engine.register_fn("byte", |value: INT| value.clamp(0, 255));
+    engine.register_fn("byte", |value: FLOAT| {
+        if value.is_nan() {
+            0
+        } else {
+            value.round().clamp(0.0, 255.0) as INT
+        }
+    });
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::ParamValue;
+
+    #[test]
+    fn runaway_scripts_end_with_an_error() {
+        let mut params = Params::defaults(PARAMS);
+        params.set("script", ParamValue::Text("loop {}".into()));
+        let image = DynamicImage::new_rgba8(4, 4);
+        let error = ScriptEffect.apply(image, &params).unwrap_err();
+        assert!(error.to_string().contains("Script error"));
+    }
+}
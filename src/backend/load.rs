@@ -1,9 +1,351 @@
-use dioxus::{
-    logger::tracing::{info, Event},
-    prelude::*,
-};
+use dioxus::prelude::*;
 
+/// Download a source image from a URL.
+///
+/// Returns a file name taken from the URL path together with the raw bytes;
+/// the format is detected by the caller like any other upload.
 #[server]
-pub async fn load_image() -> Result<(), ServerFnError> {
-    Ok(())
+pub async fn fetch_image_from_url(url: String) -> Result<(String, Vec<u8>), ServerFnError> {
+    download::Download::PUBLIC.fetch(&url).await
+}
+
+/// The download behind [`fetch_image_from_url`], which runs on the server
+#[cfg(any(feature = "server", test))]
+mod download {
+    use dioxus::prelude::ServerFnError;
+    use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+    use reqwest::redirect::{self, Policy};
+    use reqwest::Url;
+    use std::error::Error;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Redirects followed before giving up
+    const MAX_REDIRECTS: usize = 10;
+
+    /// Limits of one download
+    pub struct Download {
+        pub max_bytes: usize,
+        /// Time allowed to open the connection
+        pub connect_timeout: Duration,
+        /// Time allowed for the whole download, body included
+        pub timeout: Duration,
+        /// Whether hosts on loopback, link-local and private networks may be
+        /// fetched. Off for user URLs, so the server cannot be made to reach
+        /// services that are only visible from inside its network.
+        pub allow_private: bool,
+    }
+
+    impl Download {
+        pub const PUBLIC: Download = Download {
+            max_bytes: 64 * 1024 * 1024,
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(60),
+            allow_private: false,
+        };
+
+        pub async fn fetch(&self, url: &str) -> Result<(String, Vec<u8>), ServerFnError> {
+            let url = Url::parse(url.trim())
+                .map_err(|e| ServerFnError::new(format!("Invalid URL: {}", e)))?;
+            check_url(&url, self.allow_private).map_err(ServerFnError::new)?;
+
+            let download_failed = |e: reqwest::Error| {
+                ServerFnError::new(format!("Download failed: {}", describe(&e)))
+            };
+
+            // Hosts given by name are checked when they are resolved; the
+            // redirect policy checks every new URL the same way as the first
+            let allow_private = self.allow_private;
+            let mut client = reqwest::Client::builder()
+                .connect_timeout(self.connect_timeout)
+                .timeout(self.timeout)
+                .redirect(Policy::custom(move |attempt| {
+                    follow(attempt, allow_private)
+                }));
+            if !allow_private {
+                // A proxy would resolve hosts itself, out of reach of the check
+                client = client.no_proxy().dns_resolver(Arc::new(PublicResolver));
+            }
+            let client = client.build().map_err(download_failed)?;
+
+            let mut response = client
+                .get(url.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(download_failed)?;
+
+            let too_large = || {
+                ServerFnError::new(format!(
+                    "Image is larger than {} MB",
+                    self.max_bytes / 1024 / 1024
+                ))
+            };
+            if response
+                .content_length()
+                .is_some_and(|length| length > self.max_bytes as u64)
+            {
+                return Err(too_large());
+            }
+
+            // Read in chunks so a missing or wrong Content-Length cannot bypass the limit
+            let mut bytes = Vec::new();
+            while let Some(chunk) = response.chunk().await.map_err(download_failed)? {
+                if bytes.len() + chunk.len() > self.max_bytes {
+                    return Err(too_large());
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+
+            let file_name = url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|name| !name.is_empty())
+                .unwrap_or("download")
+                .to_string();
+
+            Ok((file_name, bytes))
+        }
+    }
+
+    /// Check the scheme of a URL, and its host if that is an IP address
+    fn check_url(url: &Url, allow_private: bool) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("Only http and https URLs are supported".to_string());
+        }
+        let host = url.host_str().unwrap_or_default();
+        let literal = host.trim_start_matches('[').trim_end_matches(']');
+        match literal.parse::<IpAddr>() {
+            Ok(address) if !allow_private && !is_public(address) => Err(format!(
+                "{} is a private network address and cannot be fetched",
+                host
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn follow(attempt: redirect::Attempt, allow_private: bool) -> redirect::Action {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("Too many redirects");
+        }
+        match check_url(attempt.url(), allow_private) {
+            Ok(()) => attempt.follow(),
+            Err(message) => attempt.error(message),
+        }
+    }
+
+    /// Resolves host names, keeping only addresses on the public internet
+    struct PublicResolver;
+
+    impl Resolve for PublicResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            Box::pin(async move {
+                let addresses: Vec<_> = tokio::net::lookup_host((name.as_str(), 0))
+                    .await?
+                    .filter(|address| is_public(address.ip()))
+                    .collect();
+                if addresses.is_empty() {
+                    return Err(format!(
+                        "{} is on a private network and cannot be fetched",
+                        name.as_str()
+                    )
+                    .into());
+                }
+                Ok(Box::new(addresses.into_iter()) as Addrs)
+            })
+        }
+    }
+
+    /// Whether an address is reachable on the public internet, i.e. not
+    /// loopback, link-local, private, shared or otherwise reserved
+    pub fn is_public(address: IpAddr) -> bool {
+        match address {
+            IpAddr::V4(address) => is_public_v4(address),
+            IpAddr::V6(address) => match address.to_ipv4_mapped() {
+                Some(mapped) => is_public_v4(mapped),
+                None => is_public_v6(address),
+            },
+        }
+    }
+
+    fn is_public_v4(address: Ipv4Addr) -> bool {
+        let [a, b, ..] = address.octets();
+        !(address.is_unspecified()
+            || address.is_loopback()
+            || address.is_private()
+            || address.is_link_local()
+            || address.is_broadcast()
+            || address.is_documentation()
+            || address.is_multicast()
+            // "This network", carrier-grade NAT, IETF protocol assignments,
+            // benchmarking and the reserved 240.0.0.0/4
+            || a == 0
+            || (a == 100 && (64..128).contains(&b))
+            || (a == 192 && b == 0 && address.octets()[2] == 0)
+            || (a == 198 && (18..20).contains(&b))
+            || a >= 240)
+    }
+
+    fn is_public_v6(address: Ipv6Addr) -> bool {
+        let first = address.segments()[0];
+        !(address.is_unspecified()
+            || address.is_loopback()
+            || address.is_multicast()
+            // Unique local fc00::/7, link-local fe80::/10 and documentation
+            // 2001:db8::/32
+            || (first & 0xfe00) == 0xfc00
+            || (first & 0xffc0) == 0xfe80
+            || (first == 0x2001 && address.segments()[1] == 0x0db8))
+    }
+
+    /// Error message including the causes, which reqwest keeps out of its
+    /// own message
+    fn describe(error: &reqwest::Error) -> String {
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        message
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::thread;
+
+        /// Serve `response` to every connection on a local port and return
+        /// the URL of `path` on it
+        fn serve(path: &str, response: Vec<u8>) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    // Read the request headers before answering
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buffer) {
+                            Ok(0) | Err(_) => break,
+                            Ok(read) => request.extend_from_slice(&buffer[..read]),
+                        }
+                    }
+                    let _ = stream.write_all(&response);
+                }
+            });
+            format!("http://{}/{}", address, path)
+        }
+
+        fn ok_response(body: &[u8], content_length: bool) -> Vec<u8> {
+            let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n".to_vec();
+            if content_length {
+                response
+                    .extend_from_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+            }
+            response.extend_from_slice(b"\r\n");
+            response.extend_from_slice(body);
+            response
+        }
+
+        /// Message of a failed download
+        async fn error(download: &Download, url: &str) -> String {
+            match download.fetch(url).await {
+                Ok(_) => panic!("{} was fetched", url),
+                Err(ServerFnError::ServerError(message)) => message,
+                Err(e) => e.to_string(),
+            }
+        }
+
+        const LOCAL: Download = Download {
+            max_bytes: 1024,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(5),
+            allow_private: true,
+        };
+
+        #[tokio::test]
+        async fn downloads_bytes_and_file_name() {
+            let body = b"\x89PNG\r\n\x1a\nnot really a png";
+            let url = serve("images/cat.png", ok_response(body, true));
+            let (file_name, bytes) = LOCAL.fetch(&url).await.unwrap();
+            assert_eq!(file_name, "cat.png");
+            assert_eq!(bytes, body);
+        }
+
+        #[tokio::test]
+        async fn rejects_downloads_over_the_limit() {
+            let body = vec![0; LOCAL.max_bytes + 1];
+            let declared = serve("big.png", ok_response(&body, true));
+            assert!(error(&LOCAL, &declared).await.contains("larger than"));
+            // Without a Content-Length the limit is enforced while reading
+            let streamed = serve("big.png", ok_response(&body, false));
+            assert!(error(&LOCAL, &streamed).await.contains("larger than"));
+
+            let body = vec![0; LOCAL.max_bytes];
+            let url = serve("exact.png", ok_response(&body, false));
+            assert_eq!(LOCAL.fetch(&url).await.unwrap().1.len(), LOCAL.max_bytes);
+        }
+
+        #[tokio::test]
+        async fn rejects_other_schemes() {
+            for url in [
+                "ftp://example.com/cat.png",
+                "file:///etc/passwd",
+                "data:,cat",
+            ] {
+                assert!(error(&LOCAL, url).await.contains("Only http and https"));
+            }
+        }
+
+        #[tokio::test]
+        async fn rejects_private_hosts() {
+            let url = serve("cat.png", ok_response(b"cat", true));
+            assert!(error(&Download::PUBLIC, &url)
+                .await
+                .contains("private network"));
+            let localhost = url.replace("127.0.0.1", "localhost");
+            assert!(error(&Download::PUBLIC, &localhost)
+                .await
+                .contains("private network"));
+        }
+
+        #[tokio::test]
+        async fn gives_up_on_servers_that_never_answer() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/slow.png", listener.local_addr().unwrap());
+            let download = Download {
+                timeout: Duration::from_millis(200),
+                ..LOCAL
+            };
+            assert!(error(&download, &url).await.contains("timed out"));
+            drop(listener);
+        }
+
+        #[test]
+        fn checks_redirect_targets_like_the_first_url() {
+            let check = |url: &str| check_url(&Url::parse(url).unwrap(), false);
+            assert!(check("https://93.184.215.14/cat.png").is_ok());
+            assert!(check("https://[2606:4700::1111]/cat.png").is_ok());
+            for url in [
+                "http://127.0.0.1/",
+                "http://10.1.2.3/",
+                "http://172.16.0.1/",
+                "http://192.168.1.1/",
+                "http://169.254.169.254/latest/meta-data/",
+                "http://100.64.0.1/",
+                "http://0.0.0.0/",
+                "http://[::1]/",
+                "http://[fe80::1]/",
+                "http://[fd00::1]/",
+                "http://[::ffff:127.0.0.1]/",
+                "gopher://93.184.215.14/",
+            ] {
+                assert!(check(url).is_err(), "{} was allowed", url);
+            }
+        }
+    }
 }
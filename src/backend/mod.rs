@@ -1,8 +1,19 @@
 mod load;
 pub use load::*;
 
-mod imgfx;
-pub use imgfx::*;
+mod channels;
+pub use channels::*;
+
+mod params;
+pub use params::*;
+
+mod registry;
+pub use registry::*;
+
+mod effects;
 
 mod effect;
 pub use effect::*;
+
+mod chain;
+pub use chain::*;
@@ -0,0 +1,528 @@
+use base64::Engine;
+use image::{ImageFormat, Rgb, RgbaImage};
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use super::{Channel, Channels};
+use crate::utils::{bytes_to_data_url, parse_hex_color, to_hex};
+
+/// Most colors a palette parameter holds
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// Most control points a curve parameter holds
+pub const MAX_CURVE_POINTS: usize = 16;
+
+/// Straight line from black to white, the curve that changes nothing
+pub const IDENTITY_CURVE: &[[f64; 2]] = &[[0.0, 0.0], [1.0, 1.0]];
+
+/// Type and range of an effect parameter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParamKind {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+        step: f64,
+    },
+    Color,
+    /// lhs/rhs channel selectors
+    Channels,
+    /// One of a fixed list of options, stored as the option index
+    Choice(&'static [&'static str]),
+    /// Integer that can be switched off
+    OptionalInt {
+        min: i64,
+        max: i64,
+    },
+    /// Free text, e.g. a formula, edited in a box this many lines high
+    Text {
+        rows: u32,
+    },
+    /// List of colors, e.g. to dither or quantize to
+    Palette,
+    /// An extra image uploaded for the effect, e.g. a displacement map
+    Image,
+    /// Tone curve through `[x, y]` control points in 0-1, sorted by x
+    Curve,
+}
+
+/// Value of an effect parameter
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Color(Rgb<u8>),
+    Channels(Channels),
+    Choice(usize),
+    OptionalInt(Option<i64>),
+    Text(Cow<'static, str>),
+    Palette(Cow<'static, [Rgb<u8>]>),
+    Image(Option<ImageParam>),
+    Curve(Cow<'static, [[f64; 2]]>),
+}
+
+/// An image used as an effect parameter, with the name of the file it came
+/// from
+#[derive(Clone, Debug)]
+pub struct ImageParam {
+    pub name: String,
+    pub image: Arc<RgbaImage>,
+}
+
+impl PartialEq for ImageParam {
+    fn eq(&self, other: &ImageParam) -> bool {
+        self.name == other.name
+            && (Arc::ptr_eq(&self.image, &other.image) || self.image == other.image)
+    }
+}
+
+impl ImageParam {
+    /// Serialized as `{"name": ..., "data": "data:image/png;base64,..."}`
+    fn to_json(&self) -> Result<serde_json::Value, String> {
+        let mut png = Cursor::new(Vec::new());
+        self.image
+            .write_to(&mut png, ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode '{}': {}", self.name, e))?;
+        Ok(serde_json::json!({
+            "name": self.name,
+            "data": bytes_to_data_url(png.get_ref(), "image/png"),
+        }))
+    }
+
+    fn from_json(json: &serde_json::Value) -> Option<ImageParam> {
+        let name = json.get("name")?.as_str()?.to_string();
+        let (_, encoded) = json.get("data")?.as_str()?.split_once(";base64,")?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .ok()?;
+        let image = image::load_from_memory(&bytes).ok()?.into_rgba8();
+        Some(ImageParam {
+            name,
+            image: Arc::new(image),
+        })
+    }
+}
+
+/// Declaration of one effect parameter; the form, the editors and
+/// serialization are generated from these
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParamSpec {
+    /// Name used in serialized chains
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: ParamKind,
+    pub default: ParamValue,
+    /// Measured in pixels, so scaled along with the image for previews
+    pub in_pixels: bool,
+    /// An amount that grows with the image area, like a number of corrupted
+    /// bytes, so scaled with the square of the image size for previews
+    pub per_area: bool,
+    /// Short usage note shown under the editor
+    pub help: Option<&'static str>,
+    /// Values can be saved under a name and reused, e.g. scripts
+    pub saveable: bool,
+}
+
+impl ParamSpec {
+    pub const fn bool(key: &'static str, label: &'static str, default: bool) -> ParamSpec {
+        ParamSpec::new(key, label, ParamKind::Bool, ParamValue::Bool(default))
+    }
+
+    pub const fn int(
+        key: &'static str,
+        label: &'static str,
+        min: i64,
+        max: i64,
+        default: i64,
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Int { min, max },
+            ParamValue::Int(default),
+        )
+    }
+
+    pub const fn float(
+        key: &'static str,
+        label: &'static str,
+        min: f64,
+        max: f64,
+        step: f64,
+        default: f64,
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Float { min, max, step },
+            ParamValue::Float(default),
+        )
+    }
+
+    pub const fn color(key: &'static str, label: &'static str, default: Rgb<u8>) -> ParamSpec {
+        ParamSpec::new(key, label, ParamKind::Color, ParamValue::Color(default))
+    }
+
+    pub const fn channels(key: &'static str, label: &'static str, default: Channels) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Channels,
+            ParamValue::Channels(default),
+        )
+    }
+
+    pub const fn choice(
+        key: &'static str,
+        label: &'static str,
+        options: &'static [&'static str],
+        default: usize,
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Choice(options),
+            ParamValue::Choice(default),
+        )
+    }
+
+    pub const fn optional_int(
+        key: &'static str,
+        label: &'static str,
+        min: i64,
+        max: i64,
+        default: Option<i64>,
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::OptionalInt { min, max },
+            ParamValue::OptionalInt(default),
+        )
+    }
+
+    pub const fn text(
+        key: &'static str,
+        label: &'static str,
+        rows: u32,
+        default: &'static str,
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Text { rows },
+            ParamValue::Text(Cow::Borrowed(default)),
+        )
+    }
+
+    pub const fn palette(
+        key: &'static str,
+        label: &'static str,
+        default: &'static [Rgb<u8>],
+    ) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Palette,
+            ParamValue::Palette(Cow::Borrowed(default)),
+        )
+    }
+
+    /// Optional image, empty by default
+    pub const fn image(key: &'static str, label: &'static str) -> ParamSpec {
+        ParamSpec::new(key, label, ParamKind::Image, ParamValue::Image(None))
+    }
+
+    /// Tone curve, a straight line by default
+    pub const fn curve(key: &'static str, label: &'static str) -> ParamSpec {
+        ParamSpec::new(
+            key,
+            label,
+            ParamKind::Curve,
+            ParamValue::Curve(Cow::Borrowed(IDENTITY_CURVE)),
+        )
+    }
+
+    /// Mark the parameter as a distance in pixels
+    pub const fn in_pixels(mut self) -> ParamSpec {
+        self.in_pixels = true;
+        self
+    }
+
+    /// Mark the parameter as an amount proportional to the image area
+    pub const fn per_area(mut self) -> ParamSpec {
+        self.per_area = true;
+        self
+    }
+
+    const fn new(
+        key: &'static str,
+        label: &'static str,
+        kind: ParamKind,
+        default: ParamValue,
+    ) -> ParamSpec {
+        ParamSpec {
+            key,
+            label,
+            kind,
+            default,
+            in_pixels: false,
+            per_area: false,
+            help: None,
+            saveable: false,
+        }
+    }
+
+    /// Let users save values of the parameter and load them again
+    pub const fn saveable(mut self) -> ParamSpec {
+        self.saveable = true;
+        self
+    }
+
+    /// Add a usage note for the editor
+    pub const fn with_help(mut self, help: &'static str) -> ParamSpec {
+        self.help = Some(help);
+        self
+    }
+
+    /// Bring a value of the right type into range; `None` if the type is wrong
+    pub fn validate(&self, value: ParamValue) -> Option<ParamValue> {
+        match (self.kind, value) {
+            (ParamKind::Bool, value @ ParamValue::Bool(_)) => Some(value),
+            (ParamKind::Int { min, max }, ParamValue::Int(value)) => {
+                Some(ParamValue::Int(value.clamp(min, max)))
+            }
+            (ParamKind::Float { min, max, .. }, ParamValue::Float(value)) if value.is_finite() => {
+                Some(ParamValue::Float(value.clamp(min, max)))
+            }
+            (ParamKind::Color, value @ ParamValue::Color(_)) => Some(value),
+            (ParamKind::Channels, value @ ParamValue::Channels(_)) => Some(value),
+            (ParamKind::Choice(options), ParamValue::Choice(index)) if index < options.len() => {
+                Some(ParamValue::Choice(index))
+            }
+            (ParamKind::OptionalInt { min, max }, ParamValue::OptionalInt(value)) => Some(
+                ParamValue::OptionalInt(value.map(|value| value.clamp(min, max))),
+            ),
+            (ParamKind::Text { .. }, value @ ParamValue::Text(_)) => Some(value),
+            (ParamKind::Palette, ParamValue::Palette(colors)) => {
+                let mut colors = colors.into_owned();
+                colors.truncate(MAX_PALETTE_COLORS);
+                Some(ParamValue::Palette(Cow::Owned(colors)))
+            }
+            (ParamKind::Image, value @ ParamValue::Image(_)) => Some(value),
+            (ParamKind::Curve, ParamValue::Curve(points)) => {
+                // Sorted by x, with one point per x at most
+                let mut points: Vec<[f64; 2]> = points
+                    .iter()
+                    .filter(|point| point.iter().all(|value| value.is_finite()))
+                    .map(|point| point.map(|value| value.clamp(0.0, 1.0)))
+                    .collect();
+                points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                points.dedup_by(|a, b| a[0] == b[0]);
+                points.truncate(MAX_CURVE_POINTS);
+                (points.len() >= 2).then_some(ParamValue::Curve(Cow::Owned(points)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Serialized form of a value of this parameter
+    pub fn serialize_value(&self, value: &ParamValue) -> serde_json::Value {
+        match (self.kind, value) {
+            (ParamKind::Choice(options), ParamValue::Choice(index)) => {
+                options.get(*index).copied().unwrap_or_default().into()
+            }
+            (_, ParamValue::Bool(value)) => (*value).into(),
+            (_, ParamValue::Int(value)) => (*value).into(),
+            (_, ParamValue::Float(value)) => (*value).into(),
+            (_, ParamValue::Color(color)) => to_hex(*color).into(),
+            (_, ParamValue::Channels(channels)) => channels.to_string().into(),
+            (_, ParamValue::Choice(index)) => (*index).into(),
+            (_, ParamValue::OptionalInt(value)) => (*value).into(),
+            (_, ParamValue::Text(text)) => text.as_ref().into(),
+            (_, ParamValue::Palette(colors)) => colors
+                .iter()
+                .map(|color| serde_json::Value::from(to_hex(*color)))
+                .collect(),
+            (_, ParamValue::Image(image)) => image
+                .as_ref()
+                .and_then(|image| image.to_json().ok())
+                .unwrap_or_default(),
+            (_, ParamValue::Curve(points)) => points
+                .iter()
+                .map(|point| serde_json::json!(point))
+                .collect(),
+        }
+    }
+
+    /// Read a serialized value of this parameter
+    pub fn deserialize_value(&self, json: &serde_json::Value) -> Result<ParamValue, String> {
+        let invalid = || format!("Invalid value for '{}': {}", self.key, json);
+
+        let value = match self.kind {
+            ParamKind::Bool => ParamValue::Bool(json.as_bool().ok_or_else(invalid)?),
+            ParamKind::Int { .. } => ParamValue::Int(json.as_i64().ok_or_else(invalid)?),
+            ParamKind::Float { .. } => ParamValue::Float(json.as_f64().ok_or_else(invalid)?),
+            ParamKind::Color => ParamValue::Color(
+                json.as_str()
+                    .and_then(parse_hex_color)
+                    .ok_or_else(invalid)?,
+            ),
+            ParamKind::Channels => ParamValue::Channels(
+                json.as_str()
+                    .ok_or_else(invalid)?
+                    .parse()
+                    .map_err(|e| format!("Invalid value for '{}': {}", self.key, e))?,
+            ),
+            ParamKind::Choice(options) => ParamValue::Choice(
+                json.as_str()
+                    .and_then(|name| options.iter().position(|option| *option == name))
+                    .ok_or_else(invalid)?,
+            ),
+            ParamKind::OptionalInt { .. } => match json {
+                serde_json::Value::Null => ParamValue::OptionalInt(None),
+                json => ParamValue::OptionalInt(Some(json.as_i64().ok_or_else(invalid)?)),
+            },
+            ParamKind::Text { .. } => {
+                ParamValue::Text(Cow::Owned(json.as_str().ok_or_else(invalid)?.to_string()))
+            }
+            ParamKind::Palette => ParamValue::Palette(Cow::Owned(
+                json.as_array()
+                    .ok_or_else(invalid)?
+                    .iter()
+                    .map(|color| color.as_str().and_then(parse_hex_color))
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+            )),
+            ParamKind::Image => match json {
+                serde_json::Value::Null => ParamValue::Image(None),
+                json => ParamValue::Image(Some(ImageParam::from_json(json).ok_or_else(invalid)?)),
+            },
+            ParamKind::Curve => ParamValue::Curve(Cow::Owned(
+                json.as_array()
+                    .ok_or_else(invalid)?
+                    .iter()
+                    .map(|point| match point.as_array()?.as_slice() {
+                        [x, y] => Some([x.as_f64()?, y.as_f64()?]),
+                        _ => None,
+                    })
+                    .collect::<Option<_>>()
+                    .ok_or_else(invalid)?,
+            )),
+        };
+        self.validate(value).ok_or_else(invalid)
+    }
+}
+
+/// Parameter values of one effect, by key
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Params(Vec<(&'static str, ParamValue)>);
+
+impl Params {
+    /// Defaults for every declared parameter
+    pub fn defaults(specs: &[ParamSpec]) -> Params {
+        Params(
+            specs
+                .iter()
+                .map(|spec| (spec.key, spec.default.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ParamValue> {
+        self.0
+            .iter()
+            .find(|(param_key, _)| *param_key == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn set(&mut self, key: &'static str, value: ParamValue) {
+        match self.0.iter_mut().find(|(param_key, _)| *param_key == key) {
+            Some((_, current)) => *current = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    // Typed getters. Values are checked against the declarations when they
+    // are set, so a mismatch can only mean a missing key; fall back to zero.
+
+    pub fn bool(&self, key: &str) -> bool {
+        matches!(self.get(key), Some(ParamValue::Bool(true)))
+    }
+
+    pub fn int(&self, key: &str) -> i64 {
+        match self.get(key) {
+            Some(ParamValue::Int(value)) => *value,
+            _ => 0,
+        }
+    }
+
+    pub fn float(&self, key: &str) -> f64 {
+        match self.get(key) {
+            Some(ParamValue::Float(value)) => *value,
+            _ => 0.0,
+        }
+    }
+
+    pub fn color(&self, key: &str) -> Rgb<u8> {
+        match self.get(key) {
+            Some(ParamValue::Color(color)) => *color,
+            _ => Rgb([0, 0, 0]),
+        }
+    }
+
+    pub fn channels(&self, key: &str) -> Channels {
+        match self.get(key) {
+            Some(ParamValue::Channels(channels)) => *channels,
+            _ => Channels([Channel::R, Channel::G, Channel::B]),
+        }
+    }
+
+    pub fn choice(&self, key: &str) -> usize {
+        match self.get(key) {
+            Some(ParamValue::Choice(index)) => *index,
+            _ => 0,
+        }
+    }
+
+    pub fn optional_int(&self, key: &str) -> Option<i64> {
+        match self.get(key) {
+            Some(ParamValue::OptionalInt(value)) => *value,
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, key: &str) -> &str {
+        match self.get(key) {
+            Some(ParamValue::Text(text)) => text,
+            _ => "",
+        }
+    }
+
+    pub fn image(&self, key: &str) -> Option<&ImageParam> {
+        match self.get(key) {
+            Some(ParamValue::Image(image)) => image.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn curve(&self, key: &str) -> &[[f64; 2]] {
+        match self.get(key) {
+            Some(ParamValue::Curve(points)) => points,
+            _ => IDENTITY_CURVE,
+        }
+    }
+
+    pub fn palette(&self, key: &str) -> &[Rgb<u8>] {
+        match self.get(key) {
+            Some(ParamValue::Palette(colors)) => colors,
+            _ => &[],
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use dioxus::prelude::ServerFnError;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::fmt;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::effects::builtin_effects;
+use super::{ParamSpec, Params, Tiling};
+
+/// Whether a pixel falls within an effect's thresholds
+pub type ThresholdTest = Box<dyn Fn(&Rgba<u8>) -> bool>;
+
+/// An effect that can be added to effect chains.
+///
+/// Pass an implementation to [`register_effect`] before launching the app to
+/// make it available in the effect form, saved chains, previews and exports
+/// like the built-in effects.
+pub trait ImageEffect: Send + Sync {
+    /// Stable identifier used in saved chains; must be unique
+    fn id(&self) -> &'static str;
+
+    /// Name shown in the UI
+    fn name(&self) -> &'static str;
+
+    /// Parameters the effect takes; the UI editors are generated from these
+    fn params(&self) -> &'static [ParamSpec];
+
+    /// Run the effect. `params` holds a value for every declared parameter,
+    /// already clamped to its range.
+    fn apply(&self, image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError>;
+
+    /// How much of the image the effect needs per output pixel. The default,
+    /// the whole image, is always correct but rules out tiled rendering.
+    /// Effects that depend on where a pixel is, e.g. seeded by its position,
+    /// find the place of a tile with [`super::TileArea::from_params`].
+    fn tiling(&self, _params: &Params) -> Tiling {
+        Tiling::Whole
+    }
+
+    /// Adjust `params` for rendering on a copy of the image resized by
+    /// `scale`, for pixel distances that aren't plain
+    /// [`ParamSpec::in_pixels`] values, e.g. offsets inside a formula. Those
+    /// are already scaled when this is called.
+    fn scale_params(&self, _params: &mut Params, _scale: f64) {}
+
+    /// Problem with the value of parameter `key` to show next to its editor,
+    /// e.g. a syntax error. `None` if the value is usable.
+    fn check_param(&self, _key: &str, _params: &Params) -> Option<String> {
+        None
+    }
+
+    /// For effects that only change pixels within thresholds, like Sort,
+    /// the test they use on their input; the UI shows it as a mask while the
+    /// thresholds are tuned
+    fn threshold(&self, _params: &Params) -> Option<ThresholdTest> {
+        None
+    }
+}
+
+/// An effect declared as plain data, which is how the built-in effects are
+/// written
+#[derive(Clone, Copy)]
+pub struct EffectDefinition {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub params: &'static [ParamSpec],
+    pub apply: fn(DynamicImage, &Params) -> Result<RgbaImage, ServerFnError>,
+    pub tiling: fn(&Params) -> Tiling,
+    pub threshold: Option<fn(&Params) -> ThresholdTest>,
+}
+
+impl ImageEffect for EffectDefinition {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        self.params
+    }
+
+    fn apply(&self, image: DynamicImage, params: &Params) -> Result<RgbaImage, ServerFnError> {
+        (self.apply)(image, params)
+    }
+
+    fn tiling(&self, params: &Params) -> Tiling {
+        (self.tiling)(params)
+    }
+
+    fn threshold(&self, params: &Params) -> Option<ThresholdTest> {
+        self.threshold.map(|threshold| threshold(params))
+    }
+}
+
+/// Tiling for effects that only look at the pixel itself
+pub fn per_pixel(_params: &Params) -> Tiling {
+    Tiling::PerPixel
+}
+
+/// Tiling for effects that need the whole image for every pixel
+pub fn whole_image(_params: &Params) -> Tiling {
+    Tiling::Whole
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RegistryError {
+    /// An effect with this id is already registered
+    DuplicateId(String),
+    /// The effect declares two parameters with this key
+    DuplicateParam(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::DuplicateId(id) => {
+                write!(f, "An effect with id '{}' is already registered", id)
+            }
+            RegistryError::DuplicateParam(key) => {
+                write!(f, "Parameter '{}' is declared more than once", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Registered effects, in the order they are offered in the UI
+static REGISTRY: LazyLock<RwLock<Vec<Arc<dyn ImageEffect>>>> =
+    LazyLock::new(|| RwLock::new(builtin_effects()));
+
+/// Make an effect available after the built-in ones
+pub fn register_effect(effect: impl ImageEffect + 'static) -> Result<(), RegistryError> {
+    let params = effect.params();
+    for (index, spec) in params.iter().enumerate() {
+        if params[..index].iter().any(|other| other.key == spec.key) {
+            return Err(RegistryError::DuplicateParam(spec.key.to_string()));
+        }
+    }
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if registry
+        .iter()
+        .any(|registered| registered.id() == effect.id())
+    {
+        return Err(RegistryError::DuplicateId(effect.id().to_string()));
+    }
+    registry.push(Arc::new(effect));
+    Ok(())
+}
+
+/// All registered effects, built-in ones first
+pub fn registered_effects() -> Vec<Arc<dyn ImageEffect>> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn find_effect(id: &str) -> Option<Arc<dyn ImageEffect>> {
+    REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|effect| effect.id() == id)
+        .cloned()
+}
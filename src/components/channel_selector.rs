@@ -0,0 +1,75 @@
+use dioxus::prelude::*;
+
+use crate::backend::{Channel, Channels};
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ChannelSelectorProps {
+    label: String,
+    value: Channels,
+    onchange: EventHandler<Channels>,
+}
+
+/// Edits an lhs/rhs operand with one dropdown per output channel, or by
+/// typing it (e.g. `bgr`)
+#[component]
+pub fn ChannelSelector(props: ChannelSelectorProps) -> Element {
+    // Text being typed that doesn't parse yet, and why
+    let mut draft = use_signal(|| None::<String>);
+    let mut error = use_signal(|| None::<String>);
+
+    let value = props.value;
+
+    rsx! {
+        div {
+            style: "margin: 10px 0;",
+            label { "{props.label}:" }
+            div {
+                style: "display: flex; align-items: center; gap: 6px; flex-wrap: wrap;",
+
+                for (index, output) in ["R", "G", "B"].into_iter().enumerate() {
+                    label {
+                        key: "{output}",
+                        style: "display: flex; align-items: center; gap: 3px; font-size: 12px;",
+                        "{output} ←"
+                        select {
+                            value: "{value.0[index].symbol()}",
+                            onchange: move |evt| {
+                                let selected = Channel::ALL
+                                    .into_iter()
+                                    .find(|channel| channel.symbol().to_string() == evt.value());
+                                if let Some(channel) = selected {
+                                    draft.set(None);
+                                    error.set(None);
+                                    props.onchange.call(value.with(index, channel));
+                                }
+                            },
+                            for channel in Channel::ALL {
+                                option { value: "{channel.symbol()}", "{channel.name()}" }
+                            }
+                        }
+                    }
+                }
+
+                input {
+                    r#type: "text",
+                    value: draft.read().clone().unwrap_or_else(|| value.to_string()),
+                    style: if error.read().is_some() { "width: 50px; border: 1px solid #d32f2f;" } else { "width: 50px;" },
+                    oninput: move |evt| match evt.value().parse::<Channels>() {
+                        Ok(channels) => {
+                            draft.set(None);
+                            error.set(None);
+                            props.onchange.call(channels);
+                        }
+                        Err(e) => {
+                            draft.set(Some(evt.value()));
+                            error.set(Some(e.to_string()));
+                        }
+                    },
+                }
+            }
+            if let Some(error) = error.read().as_ref() {
+                p { style: "font-size: 12px; color: #d32f2f; margin: 4px 0;", "{error}" }
+            }
+        }
+    }
+}
@@ -1,17 +1,137 @@
 use dioxus::prelude::*;
 use image::Rgb;
 
+use super::PaletteLibrary;
+use crate::utils::{
+    hsl_to_rgb, hsv_to_rgb, parse_hex_color, rgb_to_hsl, rgb_to_hsv, to_hex, Palette,
+};
+
+/// Diameter of the hue wheel in pixels
+const WHEEL_SIZE: f64 = 140.0;
+
+/// Number of recently used colors kept
+const MAX_RECENT_COLORS: usize = 10;
+
+/// State shared by the color pickers on a page, provided by `Home`
+#[derive(Clone, Copy, PartialEq)]
+pub struct ColorPickerContext {
+    /// Picker waiting for an eyedropper sample from one of the previews, and
+    /// where the sample goes
+    pub eyedropper: Signal<Option<(ScopeId, EventHandler<Rgb<u8>>)>>,
+    /// Recently used colors, newest first
+    pub recent: Signal<Vec<Rgb<u8>>>,
+    /// Dominant colors of the current image
+    pub palette: Memo<Vec<Rgb<u8>>>,
+    /// Palettes imported from theme files
+    pub palettes: Signal<Vec<Palette>>,
+}
+
+impl ColorPickerContext {
+    /// Add a color to the front of the recent colors
+    pub fn remember(&mut self, color: Rgb<u8>) {
+        let mut recent = self.recent.write();
+        recent.retain(|recent_color| *recent_color != color);
+        recent.insert(0, color);
+        recent.truncate(MAX_RECENT_COLORS);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SliderMode {
+    Rgb,
+    Hsv,
+    Hsl,
+}
+
 #[derive(PartialEq, Props, Clone)]
 pub struct ColorPickerProps {
-    color: Signal<Rgb<u8>>,
+    value: Rgb<u8>,
+    onchange: EventHandler<Rgb<u8>>,
 }
 
 #[component]
-pub fn ColorPicker(mut props: ColorPickerProps) -> Element {
-    let current_color = *props.color.read();
-    let r = current_color.0[0].clone();
-    let g = current_color.0[1].clone();
-    let b = current_color.0[2].clone();
+pub fn ColorPicker(props: ColorPickerProps) -> Element {
+    let context = try_use_context::<ColorPickerContext>();
+    let scope = current_scope_id().ok();
+    let mut mode = use_signal(|| SliderMode::Rgb);
+    let mut hex_draft = use_signal(|| None::<String>);
+    // Hue is undefined for grays; remember the last one so the sliders don't jump
+    let mut last_hue = use_signal(|| 0.0f32);
+
+    let current_color = props.value;
+    let [r, g, b] = current_color.0;
+    let hex = to_hex(current_color);
+
+    let (hsv_hue, saturation, value) = rgb_to_hsv(current_color);
+    let hue = if saturation > 0.0 && value > 0.0 {
+        hsv_hue
+    } else {
+        *last_hue.peek()
+    };
+    let (_, hsl_saturation, lightness) = rgb_to_hsl(current_color);
+
+    let marker_radius = saturation as f64 * WHEEL_SIZE / 2.0;
+    let marker_x = WHEEL_SIZE / 2.0 + marker_radius * (hue as f64).to_radians().sin();
+    let marker_y = WHEEL_SIZE / 2.0 - marker_radius * (hue as f64).to_radians().cos();
+    let wheel_shade = 1.0 - value;
+
+    let mut set_hsv = move |hue: f32, saturation: f32, value: f32| {
+        last_hue.set(hue);
+        props.onchange.call(hsv_to_rgb(hue, saturation, value));
+    };
+    let mut set_hsl = move |hue: f32, saturation: f32, lightness: f32| {
+        last_hue.set(hue);
+        props.onchange.call(hsl_to_rgb(hue, saturation, lightness));
+    };
+
+    // Angle from the top is the hue, distance from the center the saturation
+    let pick_from_wheel = move |evt: MouseEvent| {
+        let point = evt.element_coordinates();
+        let radius = WHEEL_SIZE / 2.0;
+        let dx = point.x - radius;
+        let dy = point.y - radius;
+        let hue = dx.atan2(-dy).to_degrees().rem_euclid(360.0) as f32;
+        let saturation = ((dx * dx + dy * dy).sqrt() / radius).min(1.0) as f32;
+        set_hsv(hue, saturation, value.max(0.01));
+    };
+
+    let eyedropper_active = context.is_some_and(|context| {
+        context
+            .eyedropper
+            .read()
+            .is_some_and(|(target, _)| Some(target) == scope)
+    });
+
+    // Don't leave the eyedropper pointing at a picker that is gone
+    use_drop(move || {
+        if let Some(mut context) = context {
+            let waiting = context
+                .eyedropper
+                .peek()
+                .is_some_and(|(target, _)| Some(target) == scope);
+            if waiting {
+                context.eyedropper.set(None);
+            }
+        }
+    });
+
+    let sliders: [(&str, &str, f32, f32); 3] = match *mode.read() {
+        SliderMode::Rgb => [
+            ("R:", "#d32f2f", r as f32, 255.0),
+            ("G:", "#388e3c", g as f32, 255.0),
+            ("B:", "#1976d2", b as f32, 255.0),
+        ],
+        SliderMode::Hsv => [
+            ("H:", "inherit", hue.round(), 359.0),
+            ("S:", "inherit", (saturation * 100.0).round(), 100.0),
+            ("V:", "inherit", (value * 100.0).round(), 100.0),
+        ],
+        SliderMode::Hsl => [
+            ("H:", "inherit", hue.round(), 359.0),
+            ("S:", "inherit", (hsl_saturation * 100.0).round(), 100.0),
+            ("L:", "inherit", (lightness * 100.0).round(), 100.0),
+        ],
+    };
 
     rsx! {
         div {
@@ -23,82 +143,97 @@ pub fn ColorPicker(mut props: ColorPickerProps) -> Element {
                 style: "width: 100%; height: 50px; border: 2px solid #333; border-radius: 4px; background-color: rgb({r}, {g}, {b});",
             }
 
-            // RGB sliders
+            // Hue wheel: hue around the circle, saturation outwards, darkened by the value
             div {
-                style: "display: flex; flex-direction: column; gap: 8px;",
-
-                // Red slider
+                class: "hue-wheel",
+                style: "width: {WHEEL_SIZE}px; height: {WHEEL_SIZE}px;",
+                onclick: pick_from_wheel,
                 div {
-                    style: "display: flex; align-items: center; gap: 10px;",
-                    label {
-                        style: "min-width: 30px; font-weight: bold; color: #d32f2f;",
-                        "R:"
-                    }
-                    input {
-                        r#type: "range",
-                        min: "0",
-                        max: "255",
-                        value: "{r}",
-                        style: "flex: 1;",
-                        oninput: move |evt| {
-                            if let Ok(r) = evt.value().parse::<u8>() {
-                                props.color.set(image::Rgb([r, current_color.0[1], current_color.0[2]]));
-                            }
-                        }
-                    }
-                    span {
-                        style: "min-width: 35px; text-align: right; font-mono;",
-                        "{r}"
-                    }
+                    class: "hue-wheel-shade",
+                    style: "opacity: {wheel_shade};",
                 }
-
-                // Green slider
                 div {
-                    style: "display: flex; align-items: center; gap: 10px;",
-                    label {
-                        style: "min-width: 30px; font-weight: bold; color: #388e3c;",
-                        "G:"
-                    }
-                    input {
-                        r#type: "range",
-                        min: "0",
-                        max: "255",
-                        value: "{g}",
-                        style: "flex: 1;",
-                        oninput: move |evt| {
-                            if let Ok(g) = evt.value().parse::<u8>() {
-                                props.color.set(image::Rgb([current_color.0[0], g, current_color.0[2]]));
-                            }
+                    class: "hue-wheel-marker",
+                    style: "left: {marker_x}px; top: {marker_y}px; background-color: {hex};",
+                }
+            }
+
+            // Hex entry
+            div {
+                style: "display: flex; align-items: center; gap: 10px;",
+                label { style: "min-width: 30px; font-weight: bold;", "Hex:" }
+                input {
+                    r#type: "text",
+                    value: hex_draft.read().clone().unwrap_or(hex.clone()),
+                    style: if hex_draft.read().is_some() { "flex: 1; min-width: 0; border: 1px solid #d32f2f;" } else { "flex: 1; min-width: 0;" },
+                    oninput: move |evt| hex_draft.set(Some(evt.value())),
+                    onchange: move |evt| {
+                        if let Some(color) = parse_hex_color(&evt.value()) {
+                            props.onchange.call(color);
+                            hex_draft.set(None);
                         }
-                    }
-                    span {
-                        style: "min-width: 35px; text-align: right; font-mono;",
-                        "{g}"
-                    }
+                    },
                 }
+            }
 
-                // Blue slider
-                div {
-                    style: "display: flex; align-items: center; gap: 10px;",
-                    label {
-                        style: "min-width: 30px; font-weight: bold; color: #1976d2;",
-                        "B:"
+            // Slider mode
+            div {
+                style: "display: flex; gap: 4px;",
+                for (slider_mode, name) in [(SliderMode::Rgb, "RGB"), (SliderMode::Hsv, "HSV"), (SliderMode::Hsl, "HSL")] {
+                    button {
+                        r#type: "button",
+                        class: if *mode.read() == slider_mode { "color-mode-button active" } else { "color-mode-button" },
+                        onclick: move |_| mode.set(slider_mode),
+                        "{name}"
                     }
-                    input {
-                        r#type: "range",
-                        min: "0",
-                        max: "255",
-                        value: "{b}",
-                        style: "flex: 1;",
-                        oninput: move |evt| {
-                            if let Ok(b) = evt.value().parse::<u8>() {
-                                props.color.set(image::Rgb([current_color.0[0], current_color.0[1], b]));
+                }
+            }
+
+            // Channel sliders
+            div {
+                style: "display: flex; flex-direction: column; gap: 8px;",
+
+                for (index, (label, label_color, slider_value, max)) in sliders.into_iter().enumerate() {
+                    div {
+                        key: "{label}{index}",
+                        style: "display: flex; align-items: center; gap: 10px;",
+                        label {
+                            style: "min-width: 30px; font-weight: bold; color: {label_color};",
+                            "{label}"
+                        }
+                        input {
+                            r#type: "range",
+                            min: "0",
+                            max: "{max}",
+                            value: "{slider_value}",
+                            style: "flex: 1;",
+                            oninput: move |evt| {
+                                let Ok(new_value) = evt.value().parse::<f32>() else {
+                                    return;
+                                };
+                                match *mode.read() {
+                                    SliderMode::Rgb => {
+                                        let mut channels = current_color.0;
+                                        channels[index] = new_value as u8;
+                                        props.onchange.call(Rgb(channels));
+                                    }
+                                    SliderMode::Hsv => {
+                                        let mut hsv = [hue, saturation, value];
+                                        hsv[index] = if index == 0 { new_value } else { new_value / 100.0 };
+                                        set_hsv(hsv[0], hsv[1], hsv[2]);
+                                    }
+                                    SliderMode::Hsl => {
+                                        let mut hsl = [hue, hsl_saturation, lightness];
+                                        hsl[index] = if index == 0 { new_value } else { new_value / 100.0 };
+                                        set_hsl(hsl[0], hsl[1], hsl[2]);
+                                    }
+                                }
                             }
                         }
-                    }
-                    span {
-                        style: "min-width: 35px; text-align: right; font-mono;",
-                        "{b}"
+                        span {
+                            style: "min-width: 35px; text-align: right; font-mono;",
+                            "{slider_value}"
+                        }
                     }
                 }
             }
@@ -108,6 +243,60 @@ pub fn ColorPicker(mut props: ColorPickerProps) -> Element {
                 style: "text-align: center; font-mono; color: #666; font-size: 12px;",
                 "rgb({r}, {g}, {b})"
             }
+
+            if let Some(mut context) = context {
+                button {
+                    r#type: "button",
+                    class: if eyedropper_active { "color-mode-button active" } else { "color-mode-button" },
+                    onclick: move |_| {
+                        context.eyedropper.set(if eyedropper_active { None } else { scope.map(|scope| (scope, props.onchange)) });
+                    },
+                    if eyedropper_active { "Click a preview to sample (cancel)" } else { "Eyedropper" }
+                }
+
+                if !context.recent.read().is_empty() {
+                    SwatchRow { title: "Recent", colors: context.recent.read().clone(), onpick: props.onchange }
+                }
+
+                if !context.palette.read().is_empty() {
+                    SwatchRow { title: "From image", colors: context.palette.read().clone(), onpick: props.onchange }
+                }
+
+                PaletteLibrary {
+                    palettes: context.palettes,
+                    onpick: props.onchange,
+                }
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Props, Clone)]
+struct SwatchRowProps {
+    title: String,
+    colors: Vec<Rgb<u8>>,
+    onpick: EventHandler<Rgb<u8>>,
+}
+
+/// A row of clickable color swatches
+#[component]
+fn SwatchRow(props: SwatchRowProps) -> Element {
+    rsx! {
+        div {
+            span { style: "font-size: 12px; color: #666;", "{props.title}" }
+            div {
+                class: "swatch-row",
+                for (index, swatch) in props.colors.iter().copied().enumerate() {
+                    button {
+                        key: "{index}",
+                        r#type: "button",
+                        class: "color-swatch",
+                        title: "{to_hex(swatch)}",
+                        style: "background-color: {to_hex(swatch)};",
+                        onclick: move |_| props.onpick.call(swatch),
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,180 @@
+use dioxus::prelude::*;
+
+use crate::backend::{IDENTITY_CURVE, MAX_CURVE_POINTS};
+use crate::utils::interpolate_curve;
+
+/// Width and height of the editing area in pixels
+const SIZE: f64 = 200.0;
+
+/// How close in pixels a click has to be to grab a point
+const GRAB_DISTANCE: f64 = 8.0;
+
+/// Closest two points may get along x, so their order stays fixed
+const MIN_GAP: f64 = 1.0 / 255.0;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct CurveEditorProps {
+    label: &'static str,
+    value: Vec<[f64; 2]>,
+    onchange: EventHandler<Vec<[f64; 2]>>,
+}
+
+/// Tone curve with draggable control points: input runs left to right and
+/// output bottom to top
+#[component]
+pub fn CurveEditor(props: CurveEditorProps) -> Element {
+    let mut dragging = use_signal(|| None::<usize>);
+
+    let points = props.value;
+    let onchange = props.onchange;
+
+    // Control point under the pointer, if any
+    let point_at = {
+        let points = points.clone();
+        move |evt: &MouseEvent| {
+            let (x, y) = position(evt);
+            points
+                .iter()
+                .map(|[px, py]| ((px - x) * SIZE).hypot((py - y) * SIZE))
+                .enumerate()
+                .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(index, _)| index)
+        }
+    };
+
+    let grab = {
+        let points = points.clone();
+        let point_at = point_at.clone();
+        move |evt: MouseEvent| {
+            if let Some(index) = point_at(&evt) {
+                dragging.set(Some(index));
+                return;
+            }
+            if points.len() >= MAX_CURVE_POINTS {
+                return;
+            }
+            let (x, y) = position(&evt);
+            let index = points.partition_point(|point| point[0] < x);
+            let mut points = points.clone();
+            points.insert(index, [x, y]);
+            dragging.set(Some(index));
+            onchange.call(points);
+        }
+    };
+
+    let drag = {
+        let points = points.clone();
+        move |evt: MouseEvent| {
+            let Some(index) = dragging() else {
+                return;
+            };
+            if index >= points.len() {
+                return;
+            }
+            // Keep the point between its neighbors so the order holds
+            let (x, y) = position(&evt);
+            let low = index
+                .checked_sub(1)
+                .map_or(0.0, |before| points[before][0] + MIN_GAP);
+            let high = points
+                .get(index + 1)
+                .map_or(1.0, |after| after[0] - MIN_GAP);
+            let mut points = points.clone();
+            points[index] = [x.clamp(low, high.max(low)), y];
+            onchange.call(points);
+        }
+    };
+
+    let remove = {
+        let points = points.clone();
+        move |evt: MouseEvent| {
+            dragging.set(None);
+            if points.len() <= 2 {
+                return;
+            }
+            if let Some(index) = point_at(&evt) {
+                let mut points = points.clone();
+                points.remove(index);
+                onchange.call(points);
+            }
+        }
+    };
+
+    let curve: Vec<String> = (0..=64)
+        .map(|step| {
+            let x = step as f64 / 64.0;
+            let y = interpolate_curve(&points, x);
+            format!("{:.1},{:.1}", x * SIZE, (1.0 - y) * SIZE)
+        })
+        .collect();
+    let curve = curve.join(" ");
+
+    rsx! {
+        div {
+            style: "margin: 10px 0;",
+            label { "{props.label}:" }
+            div {
+                class: "curve-editor",
+                style: "width: {SIZE}px; height: {SIZE}px;",
+                onmousedown: grab,
+                onmousemove: drag,
+                onmouseup: move |_| dragging.set(None),
+                onmouseleave: move |_| dragging.set(None),
+                ondoubleclick: remove,
+                svg {
+                    width: "{SIZE}",
+                    height: "{SIZE}",
+                    view_box: "0 0 {SIZE} {SIZE}",
+                    for quarter in 1..4 {
+                        line {
+                            class: "curve-grid",
+                            x1: "{SIZE * quarter as f64 / 4.0}",
+                            y1: "0",
+                            x2: "{SIZE * quarter as f64 / 4.0}",
+                            y2: "{SIZE}",
+                        }
+                        line {
+                            class: "curve-grid",
+                            x1: "0",
+                            y1: "{SIZE * quarter as f64 / 4.0}",
+                            x2: "{SIZE}",
+                            y2: "{SIZE * quarter as f64 / 4.0}",
+                        }
+                    }
+                    line {
+                        class: "curve-grid",
+                        x1: "0",
+                        y1: "{SIZE}",
+                        x2: "{SIZE}",
+                        y2: "0",
+                    }
+                    polyline { class: "curve-line", points: "{curve}" }
+                    for (index, [x, y]) in points.iter().copied().enumerate() {
+                        circle {
+                            key: "{index}",
+                            class: if dragging() == Some(index) { "curve-point active" } else { "curve-point" },
+                            cx: "{x * SIZE}",
+                            cy: "{(1.0 - y) * SIZE}",
+                            r: "4",
+                        }
+                    }
+                }
+            }
+            button {
+                r#type: "button",
+                onclick: move |_| onchange.call(IDENTITY_CURVE.to_vec()),
+                "Reset Curve"
+            }
+        }
+    }
+}
+
+/// Curve coordinates in 0-1 of the pointer
+fn position(evt: &MouseEvent) -> (f64, f64) {
+    let point = evt.element_coordinates();
+    (
+        (point.x / SIZE).clamp(0.0, 1.0),
+        (1.0 - point.y / SIZE).clamp(0.0, 1.0),
+    )
+}
@@ -1,9 +1,14 @@
 #![allow(non_snake_case)]
 
 use dioxus::prelude::*;
-use image::Rgb;
 
-use crate::components::ColorPicker;
+use super::ParamEditor;
+
+/// Step of the effect list whose threshold mask is shown over the preview
+#[derive(Clone, Copy)]
+pub struct ThresholdMaskContext {
+    pub step: Signal<Option<usize>>,
+}
 
 #[derive(Clone, Props, PartialEq)]
 pub struct EffectItemProps {
@@ -13,29 +18,26 @@ pub struct EffectItemProps {
     effect_list: Signal<Vec<crate::backend::Effect>>,
 }
 
-#[derive(Clone, Props, PartialEq)]
-struct BitwiseEffectProps {
-    color: Signal<Rgb<u8>>,
-    negate: bool,
-}
-
-/// This needs to have its own inputs that will be checked when processing this effect
-#[component]
-fn BitwiseEffect(props: BitwiseEffectProps) -> Element {
-    rsx! {}
-}
-
 #[component]
 pub fn EffectItem(mut props: EffectItemProps) -> Element {
-    let color = use_signal(|| Rgb([0, 0, 0]));
+    let mask_context = try_use_context::<ThresholdMaskContext>();
+
+    // Steps change places, so a shown mask would jump to another step
+    let hide_mask = move || {
+        if let Some(mut context) = mask_context {
+            context.step.set(None);
+        }
+    };
 
     let remove_effect = move |_| {
+        hide_mask();
         let mut current_list = props.effect_list.read().clone();
         current_list.remove(props.index);
         props.effect_list.set(current_list);
     };
 
     let move_up = move |_| {
+        hide_mask();
         let mut current_list = props.effect_list.read().clone();
         if props.index > 0 {
             current_list.swap(props.index, props.index - 1);
@@ -44,6 +46,7 @@ pub fn EffectItem(mut props: EffectItemProps) -> Element {
     };
 
     let move_down = move |_| {
+        hide_mask();
         let mut current_list = props.effect_list.read().clone();
         if props.index < current_list.len() - 1 {
             current_list.swap(props.index, props.index + 1);
@@ -51,6 +54,9 @@ pub fn EffectItem(mut props: EffectItemProps) -> Element {
         }
     };
 
+    let effect = props.effect.clone();
+    let mask_shown = mask_context.is_some_and(|context| (context.step)() == Some(props.index));
+
     rsx! {
         ul {
             class: "effect-item",
@@ -66,6 +72,42 @@ pub fn EffectItem(mut props: EffectItemProps) -> Element {
                 }
                 button {class: "effect-control-button", onclick: remove_effect, "-" }
             }
+
+            if let (Some(mut context), true) = (mask_context, effect.has_threshold()) {
+                label {
+                    class: "mask-toggle",
+                    input {
+                        r#type: "checkbox",
+                        checked: mask_shown,
+                        onchange: move |evt| {
+                            context.step.set(evt.checked().then_some(props.index));
+                        },
+                    }
+                    "Show threshold mask"
+                }
+            }
+
+            if !effect.specs().is_empty() {
+                details {
+                    class: "effect-params",
+                    summary { "Parameters" }
+                    for spec in effect.specs() {
+                        if let Some(value) = effect.params().get(spec.key) {
+                            ParamEditor {
+                                key: "{spec.key}",
+                                spec,
+                                value: value.clone(),
+                                error: effect.param_error(spec.key),
+                                onchange: move |value| {
+                                    if let Some(effect) = props.effect_list.write().get_mut(props.index) {
+                                        effect.set_param(spec.key, value);
+                                    }
+                                },
+                            }
+                        }
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,373 @@
+use dioxus::prelude::*;
+use image::DynamicImage;
+
+use crate::backend::{apply_effect_chain, apply_effect_chain_tiled};
+
+use crate::utils::{
+    bytes_to_data_url, encode_image, format_file_size, render_filename, scaled_dimensions,
+    ExportFormat, ExportOptions, ImageMetadata,
+};
+
+/// Tile edge lengths offered for tiled rendering
+const TILE_SIZES: [u32; 4] = [256, 512, 1024, 2048];
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ExportDialogProps {
+    /// Full-resolution source image; the chain is rendered on it at export time
+    source: Signal<Option<DynamicImage>>,
+    /// Metadata of the source file, used for keeping EXIF/ICC data
+    metadata: Signal<Option<ImageMetadata>>,
+    source_name: String,
+    effect_list: Signal<Vec<crate::backend::Effect>>,
+    open: Signal<bool>,
+}
+
+/// Encoded output ready for download
+#[derive(Clone, PartialEq)]
+struct EncodedExport {
+    data_url: String,
+    size: usize,
+}
+
+#[component]
+pub fn ExportDialog(mut props: ExportDialogProps) -> Element {
+    let mut format = use_signal(|| ExportFormat::Png);
+    let mut quality = use_signal(|| 90u8);
+    let mut resize_enabled = use_signal(|| false);
+    let mut keep_aspect = use_signal(|| true);
+    let mut resize_width = use_signal(|| 0u32);
+    let mut resize_height = use_signal(|| 0u32);
+    let mut tiled = use_signal(|| true);
+    let mut tile_size = use_signal(|| 512u32);
+    let mut keep_exif = use_signal(|| false);
+    let mut keep_icc = use_signal(|| true);
+    let mut filename_template = use_signal(|| "{name}_{chain}".to_string());
+    let mut encoded = use_signal(|| None::<EncodedExport>);
+    let mut export_status = use_signal(String::new);
+    let mut is_encoding = use_signal(|| false);
+
+    let source_dimensions = props
+        .source
+        .read()
+        .as_ref()
+        .map(|image| (image.width(), image.height()))
+        .unwrap_or((0, 0));
+
+    // Start the resize inputs from the current image size
+    use_effect(move || {
+        if let Some(image) = props.source.read().as_ref() {
+            resize_width.set(image.width());
+            resize_height.set(image.height());
+        }
+    });
+
+    let options = use_memo(move || ExportOptions {
+        format: *format.read(),
+        quality: *quality.read(),
+        resize: if *resize_enabled.read() {
+            Some((*resize_width.read(), *resize_height.read()))
+        } else {
+            None
+        },
+        keep_exif: *keep_exif.read(),
+        keep_icc: *keep_icc.read(),
+    });
+
+    // Any change to the options invalidates the previous encode
+    use_effect(move || {
+        options.read();
+        encoded.set(None);
+        export_status.set(String::new());
+    });
+
+    let output_dimensions = options.read().resize.unwrap_or(source_dimensions);
+    let filename = {
+        let effects = props.effect_list.read();
+        let chain: Vec<&str> = effects.iter().map(|effect| effect.name()).collect();
+        render_filename(
+            &filename_template.read(),
+            &props.source_name,
+            &chain,
+            output_dimensions,
+            *format.read(),
+        )
+    };
+
+    let encode = move |_| {
+        let Some(source) = props.source.read().clone() else {
+            export_status.set("Nothing to export yet".to_string());
+            return;
+        };
+        let effects = props.effect_list.read().clone();
+        let options = options.read().clone();
+        let metadata = props.metadata.read().clone();
+        let tile_size = tiled.read().then(|| *tile_size.read());
+        is_encoding.set(true);
+        export_status.set("Rendering at full resolution...".to_string());
+        spawn(async move {
+            let rendered = match tile_size {
+                Some(tile_size) => apply_effect_chain_tiled(source, &effects, 1.0, tile_size),
+                None => apply_effect_chain(source, &effects, 1.0),
+            };
+            let image = match rendered {
+                Ok(image) => image,
+                Err(e) => {
+                    export_status.set(e.to_string());
+                    is_encoding.set(false);
+                    return;
+                }
+            };
+            match encode_image(&image, &options, metadata.as_ref()) {
+                Ok(bytes) => {
+                    encoded.set(Some(EncodedExport {
+                        data_url: bytes_to_data_url(&bytes, options.format.mime_type()),
+                        size: bytes.len(),
+                    }));
+                    export_status.set(String::new());
+                }
+                Err(e) => {
+                    export_status.set(format!("Error encoding image: {}", e));
+                }
+            }
+            is_encoding.set(false);
+        });
+    };
+
+    let (has_exif, has_icc) = props
+        .metadata
+        .read()
+        .as_ref()
+        .map(|metadata| (metadata.exif.is_some(), metadata.icc_profile.is_some()))
+        .unwrap_or((false, false));
+    let metadata_supported = format.read().supports_metadata();
+
+    if !*props.open.read() {
+        return rsx! {};
+    }
+
+    rsx! {
+        div {
+            class: "modal-backdrop",
+            onclick: move |_| props.open.set(false),
+
+            div {
+                class: "modal",
+                onclick: move |evt| evt.stop_propagation(),
+
+                h2 { style: "margin-top: 0;", "Export" }
+
+                div {
+                    style: "margin: 10px 0;",
+                    label { "Format:" }
+                    select {
+                        value: "{ExportFormat::ALL.iter().position(|f| *f == *format.read()).unwrap_or(0)}",
+                        onchange: move |evt| {
+                            if let Ok(index) = evt.value().parse::<usize>() {
+                                if let Some(selected) = ExportFormat::ALL.get(index) {
+                                    format.set(*selected);
+                                }
+                            }
+                        },
+                        for (i, export_format) in ExportFormat::ALL.iter().enumerate() {
+                            option { value: "{i}", "{export_format.name()}" }
+                        }
+                    }
+                }
+
+                if format.read().has_quality() {
+                    div {
+                        style: "margin: 10px 0; display: flex; align-items: center; gap: 10px;",
+                        label { "Quality:" }
+                        input {
+                            r#type: "range",
+                            min: "1",
+                            max: "100",
+                            value: "{quality.read()}",
+                            style: "flex: 1;",
+                            oninput: move |evt| {
+                                if let Ok(val) = evt.value().parse::<u8>() {
+                                    quality.set(val);
+                                }
+                            }
+                        }
+                        span { style: "min-width: 35px; text-align: right;", "{quality.read()}" }
+                    }
+                }
+
+                div {
+                    style: "margin: 10px 0;",
+                    label {
+                        style: "display: flex; align-items: center; gap: 5px;",
+                        input {
+                            r#type: "checkbox",
+                            checked: *resize_enabled.read(),
+                            onchange: move |evt| resize_enabled.set(evt.checked()),
+                        }
+                        "Resize"
+                    }
+                }
+
+                if *resize_enabled.read() {
+                    div {
+                        style: "margin: 10px 0; display: flex; align-items: center; gap: 5px;",
+                        input {
+                            r#type: "number",
+                            min: "1",
+                            value: "{resize_width.read()}",
+                            style: "width: 90px;",
+                            onchange: move |evt| {
+                                if let Ok(val) = evt.value().parse::<u32>() {
+                                    resize_width.set(val);
+                                    if *keep_aspect.read() {
+                                        resize_height.set(scaled_dimensions(source_dimensions, val).1);
+                                    }
+                                }
+                            }
+                        }
+                        "×"
+                        input {
+                            r#type: "number",
+                            min: "1",
+                            value: "{resize_height.read()}",
+                            disabled: *keep_aspect.read(),
+                            style: "width: 90px;",
+                            onchange: move |evt| {
+                                if let Ok(val) = evt.value().parse::<u32>() {
+                                    resize_height.set(val);
+                                }
+                            }
+                        }
+                        label {
+                            style: "display: flex; align-items: center; gap: 5px;",
+                            input {
+                                r#type: "checkbox",
+                                checked: *keep_aspect.read(),
+                                onchange: move |evt| {
+                                    keep_aspect.set(evt.checked());
+                                    if evt.checked() {
+                                        let width = *resize_width.read();
+                                        resize_height.set(scaled_dimensions(source_dimensions, width).1);
+                                    }
+                                }
+                            }
+                            "Keep aspect"
+                        }
+                    }
+                }
+
+                div {
+                    style: "margin: 10px 0; display: flex; align-items: center; gap: 10px;",
+                    label {
+                        style: "display: flex; align-items: center; gap: 5px;",
+                        input {
+                            r#type: "checkbox",
+                            checked: *tiled.read(),
+                            onchange: move |evt| tiled.set(evt.checked()),
+                        }
+                        "Render in tiles (lower memory)"
+                    }
+                    if *tiled.read() {
+                        select {
+                            value: "{tile_size.read()}",
+                            onchange: move |evt| {
+                                if let Ok(val) = evt.value().parse::<u32>() {
+                                    tile_size.set(val);
+                                }
+                            },
+                            for size in TILE_SIZES {
+                                option { value: "{size}", "{size}px" }
+                            }
+                        }
+                    }
+                }
+
+                if has_exif || has_icc {
+                    div {
+                        style: "margin: 10px 0;",
+                        if has_exif {
+                            label {
+                                style: "display: flex; align-items: center; gap: 5px;",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: *keep_exif.read(),
+                                    disabled: !metadata_supported,
+                                    onchange: move |evt| keep_exif.set(evt.checked()),
+                                }
+                                "Keep EXIF data"
+                            }
+                        }
+                        if has_icc {
+                            label {
+                                style: "display: flex; align-items: center; gap: 5px;",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: *keep_icc.read(),
+                                    disabled: !metadata_supported,
+                                    onchange: move |evt| keep_icc.set(evt.checked()),
+                                }
+                                "Keep ICC profile"
+                            }
+                        }
+                        if !metadata_supported {
+                            p {
+                                style: "font-size: 12px; color: #666; margin: 4px 0;",
+                                "{format.read().name()} files are exported without metadata"
+                            }
+                        }
+                    }
+                }
+
+                div {
+                    style: "margin: 10px 0;",
+                    label { "Filename:" }
+                    input {
+                        r#type: "text",
+                        value: "{filename_template.read()}",
+                        style: "width: 100%;",
+                        oninput: move |evt| filename_template.set(evt.value()),
+                    }
+                    p {
+                        style: "font-size: 12px; color: #666; margin: 4px 0;",
+                        "Placeholders: {{name}}, {{chain}}, {{width}}, {{height}}"
+                    }
+                    p { style: "font-size: 12px; margin: 4px 0;", "{filename}" }
+                }
+
+                if !export_status.read().is_empty() {
+                    p { "{export_status.read()}" }
+                }
+
+                if let Some(export) = encoded.read().as_ref() {
+                    p { "Estimated file size: {format_file_size(export.size)}" }
+                }
+
+                div {
+                    style: "display: flex; gap: 8px; margin-top: 20px;",
+
+                    if let Some(export) = encoded.read().as_ref() {
+                        a {
+                            class: "control-button",
+                            style: "text-align: center; text-decoration: none;",
+                            href: "{export.data_url}",
+                            download: "{filename}",
+                            "Download"
+                        }
+                    } else {
+                        button {
+                            class: "control-button",
+                            disabled: *is_encoding.read(),
+                            onclick: encode,
+                            if *is_encoding.read() { "Rendering..." } else { "Render & Estimate Size" }
+                        }
+                    }
+
+                    button {
+                        class: "control-button",
+                        onclick: move |_| props.open.set(false),
+                        "Close"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use dioxus::prelude::*;
+use image::RgbaImage;
+
+use crate::utils::{Histogram, HISTOGRAM_CHANNELS};
+
+/// Height of the chart in SVG units; the width is one unit per value
+const CHART_HEIGHT: f64 = 100.0;
+
+/// CSS class of each channel's area, in the order of `HISTOGRAM_CHANNELS`
+const CHANNEL_CLASSES: [&str; 4] = [
+    "histogram-red",
+    "histogram-green",
+    "histogram-blue",
+    "histogram-luminance",
+];
+
+#[derive(PartialEq, Props, Clone)]
+pub struct HistogramChartProps {
+    label: &'static str,
+    image: Signal<Option<RgbaImage>>,
+}
+
+/// RGB and luminance histogram of an image with per-channel statistics,
+/// recomputed whenever the image changes
+#[component]
+pub fn HistogramChart(props: HistogramChartProps) -> Element {
+    let image = props.image;
+    let histogram = use_memo(move || image.read().as_ref().map(Histogram::new));
+
+    let Some(histogram) = histogram.read().clone() else {
+        return rsx! {};
+    };
+
+    // Shared scale so the channels can be compared
+    let peak = histogram
+        .counts
+        .iter()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let paths = histogram.counts.map(|counts| {
+        let mut path = format!("M0,{CHART_HEIGHT}");
+        for (value, count) in counts.iter().enumerate() {
+            let y = CHART_HEIGHT * (1.0 - *count as f64 / peak);
+            path.push_str(&format!(" L{value},{y:.2} L{},{y:.2}", value + 1));
+        }
+        path.push_str(&format!(" L256,{CHART_HEIGHT} Z"));
+        path
+    });
+
+    rsx! {
+        div {
+            class: "histogram",
+            div { class: "histogram-label", "{props.label}" }
+            svg {
+                class: "histogram-chart",
+                view_box: "0 0 256 {CHART_HEIGHT}",
+                preserve_aspect_ratio: "none",
+                for (path, class) in paths.iter().zip(CHANNEL_CLASSES) {
+                    path { class, d: "{path}" }
+                }
+            }
+            table {
+                tr {
+                    th { "" }
+                    th { "Min" }
+                    th { "Max" }
+                    th { "Mean" }
+                    th { "Median" }
+                }
+                for (channel, name) in HISTOGRAM_CHANNELS.iter().enumerate() {
+                    if let Some(stats) = histogram.stats(channel) {
+                        tr {
+                            td { "{name}" }
+                            td { "{stats.min}" }
+                            td { "{stats.max}" }
+                            td { "{stats.mean:.1}" }
+                            td { "{stats.median}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use dioxus::prelude::*;
+use std::sync::Arc;
+
+use crate::backend::ImageParam;
+use crate::utils::create_proxy_image;
+
+/// Long edge uploaded parameter images are scaled down to; they are stored
+/// in chain files, so this keeps those reasonably small
+const MAX_EDGE: u32 = 2048;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ImageInputProps {
+    label: &'static str,
+    value: Option<ImageParam>,
+    onchange: EventHandler<Option<ImageParam>>,
+}
+
+/// Upload slot for an image an effect reads from, like a displacement map
+#[component]
+pub fn ImageInput(props: ImageInputProps) -> Element {
+    let mut status = use_signal(String::new);
+    let onchange = props.onchange;
+
+    let upload = move |evt: FormEvent| {
+        let Some(file_engine) = evt.files() else {
+            return;
+        };
+        let Some(file_name) = file_engine.files().first().cloned() else {
+            return;
+        };
+        status.set(format!("Loading '{}'...", file_name));
+        spawn(async move {
+            let Some(bytes) = file_engine.read_file(&file_name).await else {
+                status.set("Failed to read image file".to_string());
+                return;
+            };
+            match image::load_from_memory(&bytes) {
+                Ok(image) => {
+                    let (image, _) = create_proxy_image(&image, MAX_EDGE);
+                    status.set(String::new());
+                    onchange.call(Some(ImageParam {
+                        name: file_name,
+                        image: Arc::new(image.into_rgba8()),
+                    }));
+                }
+                Err(e) => status.set(format!("Error loading '{}': {}", file_name, e)),
+            }
+        });
+    };
+
+    rsx! {
+        div {
+            style: "margin: 10px 0;",
+            label { "{props.label}:" }
+            if let Some(image) = &props.value {
+                div {
+                    class: "image-input-current",
+                    span { "{image.name} ({image.image.width()}×{image.image.height()})" }
+                    button {
+                        class: "image-input-clear",
+                        title: "Remove image",
+                        onclick: move |_| onchange.call(None),
+                        "×"
+                    }
+                }
+            }
+            input {
+                r#type: "file",
+                accept: "image/*",
+                onchange: upload,
+            }
+            if !status.read().is_empty() {
+                p { class: "param-help", "{status}" }
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Displays an `RgbaImage` without PNG/base64 encoding it first.
+//!
+//! - web: the raw RGBA buffer is put straight onto a `canvas`
+//! - desktop: the webview loads an uncompressed BMP from a custom asset handler
+//! - other builds fall back to a BMP data URL
+//!
+//! While a color picker's eyedropper is active, clicking a preview samples the
+//! pixel under the cursor.
+
+use dioxus::prelude::*;
+use image::{Rgb, RgbaImage};
+use std::rc::Rc;
+
+use super::ColorPickerContext;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ImagePreviewProps {
+    /// Unique on the page; also names the desktop asset route
+    id: String,
+    image: Signal<Option<RgbaImage>>,
+    alt: String,
+}
+
+/// Eyedropper support shared by the preview variants
+#[derive(Clone, Copy)]
+struct PreviewSampler {
+    context: Option<ColorPickerContext>,
+    element: Signal<Option<Rc<MountedData>>>,
+    image: Signal<Option<RgbaImage>>,
+}
+
+fn use_preview_sampler(image: Signal<Option<RgbaImage>>) -> PreviewSampler {
+    PreviewSampler {
+        context: try_use_context::<ColorPickerContext>(),
+        element: use_signal(|| None),
+        image,
+    }
+}
+
+impl PreviewSampler {
+    fn active(&self) -> bool {
+        self.context
+            .is_some_and(|context| context.eyedropper.read().is_some())
+    }
+
+    fn class(&self) -> &'static str {
+        if self.active() {
+            "picking"
+        } else {
+            ""
+        }
+    }
+
+    fn mounted(&mut self, element: Rc<MountedData>) {
+        self.element.set(Some(element));
+    }
+
+    /// Sample the clicked pixel into the color waiting on the eyedropper
+    fn click(&self, evt: MouseEvent) {
+        let Some(mut context) = self.context else {
+            return;
+        };
+        let (Some((_, target)), Some(element)) =
+            (*context.eyedropper.read(), self.element.read().clone())
+        else {
+            return;
+        };
+        let point = evt.element_coordinates();
+        let image = self.image;
+
+        spawn(async move {
+            // The preview is scaled to fit, so map the click through its displayed size
+            let Ok(rect) = element.get_client_rect().await else {
+                return;
+            };
+            let color = image.read().as_ref().and_then(|image| {
+                sample_pixel(image, point.x / rect.width(), point.y / rect.height())
+            });
+            if let Some(color) = color {
+                target.call(color);
+                context.remember(color);
+                context.eyedropper.set(None);
+            }
+        });
+    }
+}
+
+/// Pixel at a position given as fractions of the image size
+fn sample_pixel(image: &RgbaImage, x: f64, y: f64) -> Option<Rgb<u8>> {
+    if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+        return None;
+    }
+    let px = ((x * image.width() as f64) as u32).min(image.width().checked_sub(1)?);
+    let py = ((y * image.height() as f64) as u32).min(image.height().checked_sub(1)?);
+    let pixel = image.get_pixel(px, py).0;
+    Some(Rgb([pixel[0], pixel[1], pixel[2]]))
+}
+
+#[cfg(feature = "web")]
+#[component]
+pub fn ImagePreview(props: ImagePreviewProps) -> Element {
+    use wasm_bindgen::JsCast;
+
+    let mut canvas = use_signal(|| None::<web_sys::HtmlCanvasElement>);
+    let mut sampler = use_preview_sampler(props.image);
+
+    // Redraw whenever the image changes or the canvas is (re)mounted
+    use_effect(move || {
+        if let (Some(image), Some(canvas)) = (props.image.read().as_ref(), canvas.read().as_ref()) {
+            if let Err(e) = draw_to_canvas(canvas, image) {
+                dioxus::logger::tracing::error!("Failed to draw preview: {:?}", e);
+            }
+        }
+    });
+
+    if props.image.read().is_none() {
+        return rsx! {};
+    }
+
+    rsx! {
+        canvas {
+            id: "{props.id}",
+            aria_label: "{props.alt}",
+            class: sampler.class(),
+            onclick: move |evt| sampler.click(evt),
+            onmounted: move |evt| {
+                sampler.mounted(evt.data());
+                let element = evt
+                    .data()
+                    .downcast::<web_sys::Element>()
+                    .and_then(|element| element.clone().dyn_into::<web_sys::HtmlCanvasElement>().ok());
+                canvas.set(element);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "web")]
+fn draw_to_canvas(
+    canvas: &web_sys::HtmlCanvasElement,
+    image: &RgbaImage,
+) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::{Clamped, JsCast};
+
+    canvas.set_width(image.width());
+    canvas.set_height(image.height());
+
+    let context = canvas
+        .get_context("2d")?
+        .ok_or("Canvas has no 2d context")?
+        .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+    let image_data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(image.as_raw()),
+        image.width(),
+        image.height(),
+    )?;
+    context.put_image_data(&image_data, 0.0, 0.0)
+}
+
+#[cfg(all(feature = "desktop", not(feature = "web")))]
+#[component]
+pub fn ImagePreview(props: ImagePreviewProps) -> Element {
+    use dioxus::desktop::wry::http::Response;
+
+    let image = props.image;
+    let route = format!("preview-{}", props.id);
+    let mut sampler = use_preview_sampler(image);
+
+    dioxus::desktop::use_asset_handler(&route, move |_request, responder| {
+        let body = image
+            .read()
+            .as_ref()
+            .and_then(|image| crate::utils::rgba_image_to_bmp(image).ok())
+            .unwrap_or_default();
+        let response = Response::builder()
+            .header("Content-Type", "image/bmp")
+            .header("Cache-Control", "no-store")
+            .body(body);
+        if let Ok(response) = response {
+            responder.respond(response);
+        }
+    });
+
+    // Changes the URL on every update so the webview does not reuse the old image
+    let mut generation = use_signal(|| 0u64);
+    use_effect(move || {
+        image.read();
+        *generation.write() += 1;
+    });
+
+    if image.read().is_none() {
+        return rsx! {};
+    }
+
+    rsx! {
+        img {
+            id: "{props.id}",
+            src: "/{route}/{generation}",
+            alt: "{props.alt}",
+            class: sampler.class(),
+            onclick: move |evt| sampler.click(evt),
+            onmounted: move |evt| sampler.mounted(evt.data()),
+        }
+    }
+}
+
+#[cfg(not(any(feature = "web", feature = "desktop")))]
+#[component]
+pub fn ImagePreview(props: ImagePreviewProps) -> Element {
+    let mut sampler = use_preview_sampler(props.image);
+    let src = props
+        .image
+        .read()
+        .as_ref()
+        .and_then(|image| crate::utils::rgba_image_to_bmp(image).ok())
+        .map(|bmp| crate::utils::bytes_to_data_url(&bmp, "image/bmp"));
+
+    match src {
+        Some(src) => rsx! {
+            img {
+                id: "{props.id}",
+                src: "{src}",
+                alt: "{props.alt}",
+                class: sampler.class(),
+                onclick: move |evt| sampler.click(evt),
+                onmounted: move |evt| sampler.mounted(evt.data()),
+            }
+        },
+        None => rsx! {},
+    }
+}
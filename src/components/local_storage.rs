@@ -0,0 +1,37 @@
+use dioxus::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Value kept in local storage under `key`. Starts out as the default, is
+/// replaced by the saved value once that has been read back, and is saved
+/// again on every change after that.
+pub fn use_local_storage<T>(key: &'static str) -> Signal<T>
+where
+    T: Serialize + DeserializeOwned + Default + 'static,
+{
+    let mut value = use_signal(T::default);
+    let mut restored = use_signal(|| false);
+
+    use_future(move || async move {
+        let script = format!("return localStorage.getItem({:?});", key);
+        if let Ok(Some(json)) = document::eval(&script).join::<Option<String>>().await {
+            match serde_json::from_str(&json) {
+                Ok(saved) => value.set(saved),
+                Err(e) => dioxus::logger::tracing::warn!("Ignoring saved '{}': {}", key, e),
+            }
+        }
+        restored.set(true);
+    });
+
+    // Don't overwrite the saved value before it has been read back
+    use_effect(move || {
+        let value = value.read();
+        if !*restored.read() {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(&*value) {
+            document::eval(&format!("localStorage.setItem({:?}, {:?});", key, json));
+        }
+    });
+
+    value
+}
@@ -0,0 +1,40 @@
+use dioxus::prelude::*;
+
+use crate::utils::ImageMetadata;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct MetadataPanelProps {
+    metadata: ImageMetadata,
+}
+
+/// Collapsible summary of the source image's properties and camera info
+#[component]
+pub fn MetadataPanel(props: MetadataPanelProps) -> Element {
+    let metadata = &props.metadata;
+
+    rsx! {
+        details {
+            class: "metadata-panel",
+            summary { "Image info" }
+            table {
+                tr { td { "Dimensions" } td { "{metadata.width} × {metadata.height}" } }
+                tr { td { "Format" } td { "{metadata.format_name()}" } }
+                tr { td { "Color type" } td { "{metadata.color_type_name()}" } }
+                tr { td { "Orientation" } td { "{metadata.orientation_name()}" } }
+                tr {
+                    td { "ICC profile" }
+                    td {
+                        if let Some(icc_profile) = metadata.icc_profile.as_ref() {
+                            "{icc_profile.len()} bytes"
+                        } else {
+                            "None"
+                        }
+                    }
+                }
+                for (label, value) in metadata.camera.iter() {
+                    tr { td { "{label}" } td { "{value}" } }
+                }
+            }
+        }
+    }
+}
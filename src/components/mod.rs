@@ -5,4 +5,28 @@
 mod effect_item;
 pub use effect_item::*;
 mod color_picker;
-pub use color_picker::ColorPicker;
+pub use color_picker::{ColorPicker, ColorPickerContext};
+mod export_dialog;
+pub use export_dialog::ExportDialog;
+mod metadata_panel;
+pub use metadata_panel::MetadataPanel;
+mod histogram_chart;
+pub use histogram_chart::HistogramChart;
+mod image_preview;
+pub use image_preview::ImagePreview;
+mod image_input;
+pub use image_input::ImageInput;
+mod local_storage;
+pub use local_storage::use_local_storage;
+mod palette_library;
+pub use palette_library::{use_saved_palettes, PaletteLibrary};
+mod channel_selector;
+pub use channel_selector::ChannelSelector;
+mod palette_editor;
+pub use palette_editor::PaletteEditor;
+mod curve_editor;
+pub use curve_editor::CurveEditor;
+mod param_editor;
+pub use param_editor::ParamEditor;
+mod snippet_library;
+pub use snippet_library::{use_saved_snippets, SnippetContext, SnippetLibrary};
@@ -0,0 +1,137 @@
+use dioxus::prelude::*;
+use image::Rgb;
+
+use super::{ColorPicker, ColorPickerContext};
+use crate::backend::MAX_PALETTE_COLORS;
+use crate::utils::to_hex;
+
+#[derive(PartialEq, Props, Clone)]
+pub struct PaletteEditorProps {
+    label: &'static str,
+    value: Vec<Rgb<u8>>,
+    onchange: EventHandler<Vec<Rgb<u8>>>,
+}
+
+/// Editable list of colors: click a swatch to change or remove it, add
+/// colors one by one, or load the image's colors or a saved palette
+#[component]
+pub fn PaletteEditor(props: PaletteEditorProps) -> Element {
+    let context = try_use_context::<ColorPickerContext>();
+    let mut selected = use_signal(|| None::<usize>);
+
+    let colors = props.value;
+    let onchange = props.onchange;
+    let selected_color = selected().and_then(|index| Some((index, *colors.get(index)?)));
+
+    // Palettes the colors can be replaced with, by name
+    let mut sources: Vec<(String, Vec<Rgb<u8>>)> = Vec::new();
+    if let Some(context) = context {
+        let image_colors = context.palette.read().clone();
+        if !image_colors.is_empty() {
+            sources.push(("Image Colors".to_string(), image_colors));
+        }
+        for palette in context.palettes.read().iter() {
+            sources.push((palette.name.clone(), palette.colors.clone()));
+        }
+    }
+
+    let add_color = {
+        let colors = colors.clone();
+        move |_| {
+            let mut colors = colors.clone();
+            let color = selected_color
+                .map(|(_, color)| color)
+                .or(colors.last().copied())
+                .unwrap_or(Rgb([255, 255, 255]));
+            colors.push(color);
+            selected.set(Some(colors.len() - 1));
+            onchange.call(colors);
+        }
+    };
+
+    rsx! {
+        div {
+            style: "margin: 10px 0;",
+            label { "{props.label}:" }
+            div {
+                class: "swatch-row",
+                for (index, swatch) in colors.iter().copied().enumerate() {
+                    button {
+                        key: "{index}",
+                        r#type: "button",
+                        class: if selected() == Some(index) { "color-swatch selected" } else { "color-swatch" },
+                        title: "{to_hex(swatch)}",
+                        style: "background-color: {to_hex(swatch)};",
+                        onclick: move |_| {
+                            let index = (selected() != Some(index)).then_some(index);
+                            selected.set(index);
+                        },
+                    }
+                }
+                if colors.len() < MAX_PALETTE_COLORS {
+                    button {
+                        r#type: "button",
+                        class: "color-swatch palette-add",
+                        title: "Add a color",
+                        onclick: add_color,
+                        "+"
+                    }
+                }
+            }
+
+            if let Some((index, color)) = selected_color {
+                div {
+                    style: "display: flex; flex-direction: column; align-items: center; margin-top: 6px;",
+                    ColorPicker {
+                        value: color,
+                        onchange: {
+                            let colors = colors.clone();
+                            move |color| {
+                                let mut colors = colors.clone();
+                                colors[index] = color;
+                                onchange.call(colors);
+                            }
+                        },
+                    }
+                    button {
+                        r#type: "button",
+                        disabled: colors.len() <= 1,
+                        onclick: {
+                            let colors = colors.clone();
+                            move |_| {
+                                let mut colors = colors.clone();
+                                colors.remove(index);
+                                selected.set(None);
+                                onchange.call(colors);
+                            }
+                        },
+                        "Remove Color"
+                    }
+                }
+            }
+
+            if !sources.is_empty() {
+                select {
+                    style: "margin-top: 6px;",
+                    value: "",
+                    onchange: {
+                        let sources = sources.clone();
+                        move |evt: FormEvent| {
+                            let source = evt.value().parse::<usize>().ok().and_then(|index| sources.get(index));
+                            if let Some((_, colors)) = source {
+                                let mut colors = colors.clone();
+                                colors.truncate(MAX_PALETTE_COLORS);
+                                selected.set(None);
+                                onchange.call(colors);
+                            }
+                        }
+                    },
+                    option { value: "", disabled: true, selected: true, "Load colors from…" }
+                    for (index, (name, _)) in sources.iter().enumerate() {
+                        option { value: "{index}", "{name}" }
+                    }
+                }
+            }
+        }
+    }
+}
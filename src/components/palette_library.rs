@@ -0,0 +1,111 @@
+use dioxus::prelude::*;
+use image::Rgb;
+
+use super::use_local_storage;
+use crate::utils::{parse_palette, to_hex, Palette};
+
+/// Local storage key the imported palettes are saved under
+const STORAGE_KEY: &str = "imgfxdx.palettes";
+
+/// Palettes imported so far, restored from and saved to local storage
+pub fn use_saved_palettes() -> Signal<Vec<Palette>> {
+    use_local_storage(STORAGE_KEY)
+}
+
+#[derive(PartialEq, Props, Clone)]
+pub struct PaletteLibraryProps {
+    palettes: Signal<Vec<Palette>>,
+    /// Called with a color clicked in one of the palettes
+    onpick: EventHandler<Rgb<u8>>,
+}
+
+/// Imports palettes from theme files and lists the saved ones
+#[component]
+pub fn PaletteLibrary(mut props: PaletteLibraryProps) -> Element {
+    let mut import_status = use_signal(String::new);
+
+    let import_palette = move |evt: FormEvent| {
+        let Some(file_engine) = evt.files() else {
+            return;
+        };
+        let Some(file_name) = file_engine.files().first().cloned() else {
+            return;
+        };
+        spawn(async move {
+            let Some(contents) = file_engine.read_file_to_string(&file_name).await else {
+                import_status.set("Failed to read palette file".to_string());
+                return;
+            };
+            match parse_palette(&file_name, &contents) {
+                Ok(palette) => {
+                    import_status.set(format!(
+                        "Imported '{}' ({} colors)",
+                        palette.name,
+                        palette.colors.len()
+                    ));
+                    // Importing a palette again replaces the saved copy
+                    let mut palettes = props.palettes.write();
+                    match palettes.iter_mut().find(|saved| saved.name == palette.name) {
+                        Some(saved) => *saved = palette,
+                        None => palettes.push(palette),
+                    }
+                }
+                Err(e) => import_status.set(format!("Error importing '{}': {}", file_name, e)),
+            }
+        });
+    };
+
+    rsx! {
+        details {
+            class: "palette-library",
+            summary { "Palettes ({props.palettes.read().len()})" }
+
+            label {
+                style: "display: block; font-size: 12px; margin: 6px 0;",
+                "Import pywal, base16/base24, Xresources, GIMP, Kitty or Alacritty colors:"
+                input {
+                    r#type: "file",
+                    style: "width: 100%;",
+                    onchange: import_palette,
+                }
+            }
+
+            if !import_status.read().is_empty() {
+                p { style: "font-size: 12px; margin: 4px 0;", "{import_status.read()}" }
+            }
+
+            for (index, palette) in props.palettes.read().iter().enumerate() {
+                div {
+                    key: "{palette.name}",
+                    style: "margin-top: 6px;",
+                    div {
+                        style: "display: flex; justify-content: space-between; align-items: center; font-size: 12px;",
+                        span { "{palette.name}" }
+                        button {
+                            r#type: "button",
+                            class: "palette-remove",
+                            title: "Remove palette",
+                            onclick: move |_| {
+                                props.palettes.write().remove(index);
+                            },
+                            "×"
+                        }
+                    }
+                    div {
+                        class: "swatch-row",
+                        for (color_index, swatch) in palette.colors.iter().copied().enumerate() {
+                            button {
+                                key: "{color_index}",
+                                r#type: "button",
+                                class: "color-swatch",
+                                title: "{to_hex(swatch)}",
+                                style: "background-color: {to_hex(swatch)};",
+                                onclick: move |_| props.onpick.call(swatch),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
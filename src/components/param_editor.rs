@@ -0,0 +1,196 @@
+use dioxus::prelude::*;
+
+use super::{ChannelSelector, ColorPicker, CurveEditor, ImageInput, PaletteEditor, SnippetLibrary};
+use crate::backend::{ParamKind, ParamSpec, ParamValue};
+
+#[derive(PartialEq, Props, Clone)]
+pub struct ParamEditorProps {
+    spec: &'static ParamSpec,
+    value: ParamValue,
+    onchange: EventHandler<ParamValue>,
+    /// Problem with the current value, shown under the input
+    error: Option<String>,
+}
+
+/// Input for one effect parameter, chosen from its declared kind, with its
+/// usage note and any error underneath
+#[component]
+pub fn ParamEditor(props: ParamEditorProps) -> Element {
+    let spec = props.spec;
+
+    let onchange = props.onchange;
+
+    rsx! {
+        {param_input(spec, props.value.clone(), onchange)}
+        if let (true, ParamValue::Text(text)) = (spec.saveable, &props.value) {
+            SnippetLibrary {
+                library: spec.key,
+                value: text.to_string(),
+                onload: move |text: String| onchange.call(ParamValue::Text(text.into())),
+            }
+        }
+        if let Some(help) = spec.help {
+            p { class: "param-help", "{help}" }
+        }
+        if let Some(error) = props.error {
+            pre { class: "param-error", "{error}" }
+        }
+    }
+}
+
+fn param_input(
+    spec: &'static ParamSpec,
+    value: ParamValue,
+    onchange: EventHandler<ParamValue>,
+) -> Element {
+    match (spec.kind, value) {
+        (ParamKind::Bool, ParamValue::Bool(checked)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label {
+                    style: "display: flex; align-items: center; gap: 5px;",
+                    input {
+                        r#type: "checkbox",
+                        checked,
+                        onchange: move |evt| onchange.call(ParamValue::Bool(evt.checked())),
+                    }
+                    "{spec.label}"
+                }
+            }
+        },
+        (ParamKind::Int { min, max }, ParamValue::Int(value)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label { "{spec.label}:" }
+                input {
+                    r#type: "number",
+                    min: "{min}",
+                    max: "{max}",
+                    value: "{value}",
+                    onchange: move |evt| {
+                        if let Ok(value) = evt.value().parse::<i64>() {
+                            onchange.call(ParamValue::Int(value));
+                        }
+                    }
+                }
+            }
+        },
+        (ParamKind::Float { min, max, step }, ParamValue::Float(value)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label { "{spec.label}:" }
+                input {
+                    r#type: "number",
+                    min: "{min}",
+                    max: "{max}",
+                    step: "{step}",
+                    value: "{value}",
+                    onchange: move |evt| {
+                        if let Ok(value) = evt.value().parse::<f64>() {
+                            onchange.call(ParamValue::Float(value));
+                        }
+                    }
+                }
+            }
+        },
+        (ParamKind::Color, ParamValue::Color(color)) => rsx! {
+            div {
+                style: "display: flex; align-items: center; justify-content: center; width: 100%; margin: 10px 0;",
+                ColorPicker {
+                    value: color,
+                    onchange: move |color| onchange.call(ParamValue::Color(color)),
+                }
+            }
+        },
+        (ParamKind::Channels, ParamValue::Channels(channels)) => rsx! {
+            ChannelSelector {
+                label: spec.label,
+                value: channels,
+                onchange: move |channels| onchange.call(ParamValue::Channels(channels)),
+            }
+        },
+        (ParamKind::Choice(options), ParamValue::Choice(selected)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label { "{spec.label}:" }
+                select {
+                    value: "{selected}",
+                    onchange: move |evt| {
+                        if let Ok(index) = evt.value().parse::<usize>() {
+                            onchange.call(ParamValue::Choice(index));
+                        }
+                    },
+                    for (index, option) in options.iter().enumerate() {
+                        option { value: "{index}", selected: index == selected, "{option}" }
+                    }
+                }
+            }
+        },
+        (ParamKind::OptionalInt { min, max }, ParamValue::OptionalInt(value)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label {
+                    style: "display: flex; align-items: center; gap: 5px;",
+                    input {
+                        r#type: "checkbox",
+                        checked: value.is_some(),
+                        onchange: move |evt| {
+                            let enabled = evt.checked().then_some(max);
+                            onchange.call(ParamValue::OptionalInt(enabled));
+                        }
+                    }
+                    "Use {spec.label}"
+                }
+                if let Some(value) = value {
+                    input {
+                        r#type: "number",
+                        min: "{min}",
+                        max: "{max}",
+                        value: "{value}",
+                        onchange: move |evt| {
+                            if let Ok(value) = evt.value().parse::<i64>() {
+                                onchange.call(ParamValue::OptionalInt(Some(value)));
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        (ParamKind::Text { rows }, ParamValue::Text(text)) => rsx! {
+            div {
+                style: "margin: 10px 0;",
+                label { "{spec.label}:" }
+                textarea {
+                    class: "param-text",
+                    rows: "{rows}",
+                    spellcheck: false,
+                    value: "{text}",
+                    oninput: move |evt| onchange.call(ParamValue::Text(evt.value().into())),
+                }
+            }
+        },
+        (ParamKind::Palette, ParamValue::Palette(colors)) => rsx! {
+            PaletteEditor {
+                label: spec.label,
+                value: colors.into_owned(),
+                onchange: move |colors: Vec<_>| onchange.call(ParamValue::Palette(colors.into())),
+            }
+        },
+        (ParamKind::Image, ParamValue::Image(image)) => rsx! {
+            ImageInput {
+                label: spec.label,
+                value: image,
+                onchange: move |image| onchange.call(ParamValue::Image(image)),
+            }
+        },
+        (ParamKind::Curve, ParamValue::Curve(points)) => rsx! {
+            CurveEditor {
+                label: spec.label,
+                value: points.into_owned(),
+                onchange: move |points: Vec<_>| onchange.call(ParamValue::Curve(points.into())),
+            }
+        },
+        // Values are checked against their declaration when set
+        _ => rsx! {},
+    }
+}
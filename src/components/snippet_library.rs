@@ -0,0 +1,132 @@
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::use_local_storage;
+
+/// Local storage key the saved snippets are kept under
+const STORAGE_KEY: &str = "imgfxdx.snippets";
+
+/// A named text parameter value, e.g. a script, saved for reuse
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Snippet {
+    /// Key of the parameter the snippet was saved from
+    pub library: String,
+    pub name: String,
+    pub text: String,
+}
+
+/// Saved snippets of every library, restored from and saved to local storage
+pub fn use_saved_snippets() -> Signal<Vec<Snippet>> {
+    use_local_storage(STORAGE_KEY)
+}
+
+/// Saved snippets shared by the parameter editors on a page, provided by `Home`
+#[derive(Clone, Copy, PartialEq)]
+pub struct SnippetContext {
+    pub snippets: Signal<Vec<Snippet>>,
+}
+
+#[derive(PartialEq, Props, Clone)]
+pub struct SnippetLibraryProps {
+    /// Which snippets to list; saving adds to this library
+    library: &'static str,
+    /// Text that "Save" stores
+    value: String,
+    /// Called with the text of a snippet clicked in the list
+    onload: EventHandler<String>,
+}
+
+/// Saves the current text under a name and lists the saved snippets to load
+/// or remove
+#[component]
+pub fn SnippetLibrary(props: SnippetLibraryProps) -> Element {
+    let mut name = use_signal(String::new);
+    let Some(SnippetContext { mut snippets }) = try_use_context::<SnippetContext>() else {
+        return rsx! {};
+    };
+    let library = props.library;
+    let value = props.value;
+    let onload = props.onload;
+
+    let save = move |_| {
+        let name = name.read().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        // Saving under an existing name replaces that snippet
+        let mut snippets = snippets.write();
+        let snippet = Snippet {
+            library: library.to_string(),
+            name,
+            text: value.clone(),
+        };
+        match snippets
+            .iter_mut()
+            .find(|saved| saved.library == snippet.library && saved.name == snippet.name)
+        {
+            Some(saved) => *saved = snippet,
+            None => snippets.push(snippet),
+        }
+    };
+
+    let saved: Vec<(usize, Snippet)> = snippets
+        .read()
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|(_, snippet)| snippet.library == library)
+        .collect();
+
+    rsx! {
+        details {
+            class: "snippet-library",
+            summary { "Saved ({saved.len()})" }
+
+            div {
+                style: "display: flex; gap: 4px; margin: 6px 0;",
+                input {
+                    r#type: "text",
+                    placeholder: "Name",
+                    style: "flex: 1; min-width: 0;",
+                    value: "{name}",
+                    oninput: move |evt| name.set(evt.value()),
+                }
+                button {
+                    r#type: "button",
+                    disabled: name.read().trim().is_empty(),
+                    onclick: save,
+                    "Save"
+                }
+            }
+
+            for (index, snippet) in saved {
+                div {
+                    key: "{snippet.name}",
+                    style: "display: flex; justify-content: space-between; align-items: center; font-size: 12px;",
+                    button {
+                        r#type: "button",
+                        class: "snippet-load",
+                        title: "Load '{snippet.name}'",
+                        onclick: {
+                            let snippet = snippet.clone();
+                            move |_| {
+                                name.set(snippet.name.clone());
+                                onload.call(snippet.text.clone());
+                            }
+                        },
+                        "{snippet.name}"
+                    }
+                    button {
+                        r#type: "button",
+                        class: "palette-remove",
+                        title: "Remove '{snippet.name}'",
+                        onclick: move |_| {
+                            snippets.write().remove(index);
+                        },
+                        "×"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! imgfxdx as a library, so other crates can add their own effects.
+//!
+//! ```ignore
+//! fn main() {
+//!     imgfxdx::backend::register_effect(MyEffect).unwrap();
+//!     imgfxdx::launch();
+//! }
+//! ```
+//!
+//! See [`backend::ImageEffect`] for what an effect implements.
+
+use dioxus::prelude::*;
+use routes::Home;
+
+pub mod backend;
+mod components;
+mod routes;
+pub mod utils;
+
+const MAIN_CSS: Asset = asset!("/assets/styling/main.css");
+
+#[derive(Routable, Clone, Debug, PartialEq)]
+enum Route {
+    #[layout(Layout)]
+    #[route("/")]
+    Home,
+}
+
+#[component]
+fn Layout() -> Element {
+    rsx! {
+        Outlet::<Route> {  }
+    }
+}
+
+/// Start the app with the built-in effects and any registered so far
+pub fn launch() {
+    dioxus::launch(App);
+}
+
+#[component]
+fn App() -> Element {
+    rsx! {
+        document::Stylesheet { href: MAIN_CSS }
+        Router::<Route>{}
+    }
+}
@@ -1,368 +1,71 @@
+use base64::Engine;
+use dioxus::html::{FileEngine, HasFileData};
 use dioxus::prelude::*;
-use image::{DynamicImage, Rgb, RgbaImage};
-use imgfx::{Direction, SortBy};
+use image::{DynamicImage, GrayImage, RgbaImage};
+use std::sync::Arc;
 
 use crate::backend::*;
-use crate::components::{ColorPicker, EffectItem};
-use crate::utils::{create_preview_data_url, is_image_file, rgba_image_to_data_url};
+use crate::components::{
+    use_saved_palettes, use_saved_snippets, ColorPickerContext, EffectItem, ExportDialog,
+    HistogramChart, ImagePreview, MetadataPanel, ParamEditor, SnippetContext, ThresholdMaskContext,
+};
+use crate::utils::{
+    bytes_to_data_url, create_proxy_image, detect_image_format, extract_palette, format_name,
+    is_supported_format, load_image_with_metadata, ImageMetadata, SUPPORTED_FORMATS,
+};
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
 const MAIN_CSS: Asset = asset!("/assets/styling/main.css");
 
-#[derive(Props, Clone, PartialEq)]
-pub struct EffectFormProps {
-    effect_list: Signal<Vec<crate::backend::Effect>>,
-}
+/// Long edge of the downscaled copy used for interactive previews
+const PREVIEW_MAX_EDGE: u32 = 1280;
 
-#[derive(Clone, Copy, PartialEq)]
-enum EffectType {
-    Or,
-    And,
-    Xor,
-    Left,
-    Right,
-    Add,
-    Sub,
-    Mult,
-    Pow,
-    Div,
-    Average,
-    Screen,
-    Overlay,
-    Bloom,
-    Sort,
-}
+/// Number of colors extracted from the image for the color picker
+const PALETTE_SIZE: usize = 8;
 
-impl EffectType {
-    fn name(&self) -> &'static str {
-        match self {
-            EffectType::Or => "OR",
-            EffectType::And => "AND",
-            EffectType::Xor => "XOR",
-            EffectType::Left => "Left",
-            EffectType::Right => "Right",
-            EffectType::Add => "Add",
-            EffectType::Sub => "Subtract",
-            EffectType::Mult => "Multiply",
-            EffectType::Pow => "Power",
-            EffectType::Div => "Divide",
-            EffectType::Average => "Average",
-            EffectType::Screen => "Screen",
-            EffectType::Overlay => "Overlay",
-            EffectType::Bloom => "Bloom",
-            EffectType::Sort => "Sort",
+/// Forwards images pasted anywhere on the page, leaving text pastes alone
+const PASTE_LISTENER_JS: &str = r#"
+    window.addEventListener("paste", (event) => {
+        const items = event.clipboardData ? Array.from(event.clipboardData.items) : [];
+        const item = items.find((item) => item.kind === "file" && item.type.startsWith("image/"));
+        if (!item) {
+            return;
         }
-    }
-
-    fn accepts_color(&self) -> bool {
-        matches!(
-            self,
-            EffectType::Or
-                | EffectType::And
-                | EffectType::Xor
-                | EffectType::Add
-                | EffectType::Sub
-                | EffectType::Mult
-                | EffectType::Pow
-                | EffectType::Div
-                | EffectType::Average
-                | EffectType::Screen
-                | EffectType::Overlay
-        )
-    }
-
-    fn accepts_bits(&self) -> bool {
-        matches!(self, EffectType::Left | EffectType::Right)
-    }
-
-    fn accepts_sort_by(&self) -> bool {
-        matches!(self, EffectType::Sort)
-    }
-
-    fn accepts_direction(&self) -> bool {
-        matches!(self, EffectType::Sort)
-    }
-
-    fn accepts_threshold(&self) -> bool {
-        matches!(self, EffectType::Sort | EffectType::Bloom)
-    }
+        event.preventDefault();
+        const file = item.getAsFile();
+        const reader = new FileReader();
+        reader.onload = () => {
+            const dataUrl = reader.result;
+            dioxus.send([file.name || "pasted-image", dataUrl.slice(dataUrl.indexOf(",") + 1)]);
+        };
+        reader.readAsDataURL(file);
+    });
+"#;
 
-    fn accepts_negate(&self) -> bool {
-        matches!(
-            self,
-            EffectType::Or
-                | EffectType::And
-                | EffectType::Xor
-                | EffectType::Left
-                | EffectType::Right
-        )
-    }
-
-    fn accepts_blur_radius(&self) -> bool {
-        matches!(self, EffectType::Bloom)
-    }
-
-    fn accepts_intensity(&self) -> bool {
-        matches!(self, EffectType::Bloom)
-    }
-
-    fn accepts_reversed(&self) -> bool {
-        matches!(self, EffectType::Sort)
-    }
-
-    fn accepts_lhs(&self) -> bool {
-        matches!(
-            self,
-            EffectType::Or
-                | EffectType::Add
-                | EffectType::Sub
-                | EffectType::And
-                | EffectType::Xor
-                | EffectType::Mult
-                | EffectType::Div
-                | EffectType::Pow
-                | EffectType::Average
-                | EffectType::Overlay
-                | EffectType::Screen
-        )
-    }
-
-    fn accepts_rhs(&self) -> bool {
-        matches!(
-            self,
-            EffectType::Or
-                | EffectType::Add
-                | EffectType::Sub
-                | EffectType::And
-                | EffectType::Xor
-                | EffectType::Mult
-                | EffectType::Div
-                | EffectType::Pow
-                | EffectType::Average
-                | EffectType::Overlay
-                | EffectType::Screen
-                | EffectType::Left
-                | EffectType::Right
-        )
-    }
-
-    fn to_effect(
-        &self,
-        color: Option<Rgb<u8>>,
-        lhs: Option<Vec<String>>,
-        rhs: Option<Vec<String>>,
-        negate: Option<bool>,
-    ) -> crate::backend::Effect {
-        match self {
-            EffectType::Or => crate::backend::Effect::Or {
-                color: color.unwrap(),
-                negate: negate.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::And => crate::backend::Effect::And {
-                color: color.unwrap(),
-                negate: false,
-                lhs,
-                rhs,
-            },
-            EffectType::Xor => crate::backend::Effect::Xor {
-                color: color.unwrap(),
-                negate: false,
-                lhs,
-                rhs,
-            },
-            EffectType::Add => crate::backend::Effect::Add {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Sub => crate::backend::Effect::Sub {
-                color: color.unwrap(),
-                negate: false,
-                lhs,
-                rhs,
-            },
-            EffectType::Mult => crate::backend::Effect::Mult {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Pow => crate::backend::Effect::Pow {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Div => crate::backend::Effect::Div {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Average => crate::backend::Effect::Average {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Screen => crate::backend::Effect::Screen {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Overlay => crate::backend::Effect::Overlay {
-                color: color.unwrap(),
-                lhs,
-                rhs,
-            },
-            EffectType::Left => crate::backend::Effect::Left {
-                bits: 1,
-                negate: false,
-                lhs,
-            },
-            EffectType::Right => crate::backend::Effect::Right {
-                bits: 1,
-                negate: false,
-                lhs,
-            },
-            EffectType::Bloom => crate::backend::Effect::Bloom {
-                intensity: 1.0,
-                radius: 5.0,
-                min_threshold: 128,
-                max_threshold: Some(255),
-            },
-            EffectType::Sort => crate::backend::Effect::Sort {
-                direction: Direction::Horizontal,
-                sort_by: SortBy::Red,
-                min_threshold: 0.0,
-                max_threshold: 255.0,
-                reversed: false,
-            },
-        }
-    }
+#[derive(Props, Clone, PartialEq)]
+pub struct EffectFormProps {
+    effect_list: Signal<Vec<crate::backend::Effect>>,
 }
 
-/// Form to add new effects to the list
+/// Form to add new effects to the list, generated from the effect registry
 #[component]
 fn EffectForm(mut props: EffectFormProps) -> Element {
-    let mut selected_effect_type = use_signal(|| None::<EffectType>);
-    let selected_color = use_signal(|| Rgb([255u8, 0u8, 0u8]));
-    let mut negated = use_signal(|| false);
-    let mut bits = use_signal(|| 1u8);
-    let mut intensity = use_signal(|| 1.0f64);
-    let mut blur_radius = use_signal(|| 5.0f64);
-    let mut min_threshold = use_signal(|| 128u8);
-    let mut max_threshold = use_signal(|| Some(255u8));
-    let mut sort_by = use_signal(|| SortBy::Red);
-    let mut direction = use_signal(|| Direction::Horizontal);
-    let mut sort_min_threshold = use_signal(|| 0.0f64);
-    let mut sort_max_threshold = use_signal(|| 255.0f64);
-    let mut reversed = use_signal(|| false);
-    let mut lhs = use_signal(|| vec!["b".to_string(), "b".to_string(), "b".to_string()]);
-    let mut rhs = use_signal(|| vec!["g".to_string(), "g".to_string(), "b".to_string()]);
+    // Effect being configured; its parameters carry over when switching type
+    let mut draft = use_signal(|| None::<crate::backend::Effect>);
 
-    let effect_types = vec![
-        EffectType::Or,
-        EffectType::And,
-        EffectType::Xor,
-        EffectType::Left,
-        EffectType::Right,
-        EffectType::Add,
-        EffectType::Sub,
-        EffectType::Mult,
-        EffectType::Pow,
-        EffectType::Div,
-        EffectType::Average,
-        EffectType::Screen,
-        EffectType::Overlay,
-        EffectType::Bloom,
-        EffectType::Sort,
-    ];
+    let picker_context = try_use_context::<ColorPickerContext>();
 
-    let add_effect = move |_| {
-        if let Some(effect_type) = *selected_effect_type.read() {
-            let new_effect = match effect_type {
-                EffectType::Or => crate::backend::Effect::Or {
-                    color: *selected_color.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::And => crate::backend::Effect::And {
-                    color: *selected_color.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Xor => crate::backend::Effect::Xor {
-                    color: *selected_color.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Left => crate::backend::Effect::Left {
-                    bits: *bits.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                },
-                EffectType::Right => crate::backend::Effect::Right {
-                    bits: *bits.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                },
-                EffectType::Add => crate::backend::Effect::Add {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Sub => crate::backend::Effect::Sub {
-                    color: *selected_color.read(),
-                    negate: *negated.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Mult => crate::backend::Effect::Mult {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Pow => crate::backend::Effect::Pow {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Div => crate::backend::Effect::Div {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Average => crate::backend::Effect::Average {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Screen => crate::backend::Effect::Screen {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Overlay => crate::backend::Effect::Overlay {
-                    color: *selected_color.read(),
-                    lhs: Some(lhs.read().clone()),
-                    rhs: Some(rhs.read().clone()),
-                },
-                EffectType::Bloom => crate::backend::Effect::Bloom {
-                    intensity: *intensity.read(),
-                    radius: *blur_radius.read(),
-                    min_threshold: *min_threshold.read(),
-                    max_threshold: *max_threshold.read(),
-                },
-                EffectType::Sort => crate::backend::Effect::Sort {
-                    direction: *direction.read(),
-                    sort_by: *sort_by.read(),
-                    min_threshold: *sort_min_threshold.read(),
-                    max_threshold: *sort_max_threshold.read(),
-                    reversed: *reversed.read(),
-                },
-            };
+    let add_effect = move |evt: FormEvent| {
+        evt.prevent_default();
+
+        if let Some(new_effect) = draft.read().clone() {
+            if let Some(mut picker_context) = picker_context {
+                for spec in new_effect.specs() {
+                    if let Some(ParamValue::Color(color)) = new_effect.params().get(spec.key) {
+                        picker_context.remember(*color);
+                    }
+                }
+            }
 
             let mut current_list = props.effect_list.read().clone();
             current_list.push(new_effect);
@@ -370,533 +73,323 @@ fn EffectForm(mut props: EffectFormProps) -> Element {
         }
     };
 
+    let selected_id = draft.read().as_ref().map(|effect| effect.id());
+
     rsx! {
     form {
-        prevent_default: "onsubmit",
         onsubmit: add_effect,
 
         div {
             label { "Effect Type:" }
             select {
-                value: if let Some(effect_type) = selected_effect_type.read().as_ref() {
-                    format!("{}", effect_types.iter().position(|e| e == effect_type).unwrap_or(0))
-                } else {
-                    String::new()
-                },
+                value: selected_id.unwrap_or_default(),
                 onchange: move |evt| {
-                    if let Ok(index) = evt.value().parse::<usize>() {
-                        if let Some(effect_type) = effect_types.get(index) {
-                            selected_effect_type.set(Some(*effect_type));
-                        }
+                    let Some(registered) = find_effect(&evt.value()) else {
+                        return;
+                    };
+                    let mut effect = crate::backend::Effect::new(registered);
+                    if let Some(previous) = draft.read().as_ref() {
+                        effect.inherit_params(previous);
                     }
+                    draft.set(Some(effect));
                 },
                 option { value: "", "Select an effect..." }
-                for (i, effect_type) in effect_types.iter().enumerate() {
+                for registered in registered_effects() {
                     option {
-                        value: "{i}",
-                        "{effect_type.name()}"
+                        value: "{registered.id()}",
+                        "{registered.name()}"
                     }
                 }
             }
         }
 
-        // Show inputs based on what the selected effect accepts
-        if let Some(effect_type) = selected_effect_type.read().as_ref() {
-
-            // Color picker
-            if effect_type.accepts_color() {
-                div {
-                    style: "display: flex; align-items: center; justify-content: center; width: 100%; margin: 10px 0;",
-                    ColorPicker { color: selected_color }
-                }
-            }
-
-            // Negate checkbox
-            if effect_type.accepts_negate() {
-                div {
-                    style: "margin: 10px 0;",
-                    label {
-                        style: "display: flex; align-items: center; gap: 5px;",
-                        input {
-                            r#type: "checkbox",
-                            checked: *negated.read(),
-                            onchange: move |evt| {
-                                negated.set(evt.checked());
-                            }
-                        }
-                        "Negate"
-                    }
-                }
-            }
-
-            // Bits input for Left/Right shift
-            if effect_type.accepts_bits() {
-                div {
-                    style: "margin: 10px 0;",
-                    label { "Bits:" }
-                    input {
-                        r#type: "number",
-                        min: "1",
-                        max: "8",
-                        value: "{bits.read()}",
-                        onchange: move |evt| {
-                            if let Ok(val) = evt.value().parse::<u8>() {
-                                bits.set(val);
-                            }
-                        }
-                    }
-                }
-            }
-
-            if effect_type.accepts_lhs() {
-                div {
-                    style: "margin: 10px 0",
-                    label { "Left-Hand Side:" }
-                    input {
-                        r#type: "text",
-                        value: "{lhs.read().join(\"\")}",
-                        oninput: move |evt| {
-                            let val = evt.value();
-                            if val.len() >= 3 {
-                                let chars: Vec<String> = val.chars()
-                                                            .take(3)
-                                                            .map(|c| c.to_string())
-                                                            .collect();
-                                lhs.set(chars);
-                            }
-                        }
-                    }
-                }
-            }
-
-
-            if effect_type.accepts_rhs() {
-                div {
-                    style: "margin: 10px 0",
-                    label { "Left-Hand Side:" }
-                    input {
-                        r#type: "text",
-                        value: "{rhs.read().join(\"\")}",
-                        oninput: move |evt| {
-                            let val = evt.value();
-                            if val.len() >= 3 {
-                                let chars: Vec<String> = val.chars()
-                                                            .take(3)
-                                                            .map(|c| c.to_string())
-                                                            .collect();
-                                rhs.set(chars);
-                            }
-                        }
-                    }
-                }
-            }
-
-                // Bloom-specific inputs
-                if *effect_type == EffectType::Bloom {
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Intensity:" }
-                        input {
-                            r#type: "number",
-                            step: "0.1",
-                            min: "0.1",
-                            value: "{intensity.read()}",
-                            onchange: move |evt| {
-                                if let Ok(val) = evt.value().parse::<f64>() {
-                                    intensity.set(val);
-                                }
-                            }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Blur Radius:" }
-                        input {
-                            r#type: "number",
-                            step: "0.1",
-                            min: "0.1",
-                            value: "{blur_radius.read()}",
-                            onchange: move |evt| {
-                                if let Ok(val) = evt.value().parse::<f64>() {
-                                    blur_radius.set(val);
-                                }
-                            }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Min Threshold:" }
-                        input {
-                            r#type: "number",
-                            min: "0",
-                            max: "255",
-                            value: "{min_threshold.read()}",
-                            onchange: move |evt| {
-                                if let Ok(val) = evt.value().parse::<u8>() {
-                                    min_threshold.set(val);
-                                }
-                            }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label {
-                            style: "display: flex; align-items: center; gap: 5px;",
-                            input {
-                                r#type: "checkbox",
-                                checked: max_threshold.read().is_some(),
-                                onchange: move |evt| {
-                                    if evt.checked() {
-                                        max_threshold.set(Some(255));
-                                    } else {
-                                        max_threshold.set(None);
-                                    }
-                                }
-                            }
-                            "Use Max Threshold"
-                        }
-                        if max_threshold.read().is_some() {
-                            input {
-                                r#type: "number",
-                                min: "0",
-                                max: "255",
-                                value: "{max_threshold.read().unwrap_or(255)}",
-                                onchange: move |evt| {
-                                    if let Ok(val) = evt.value().parse::<u8>() {
-                                        max_threshold.set(Some(val));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // Sort-specific inputs
-                if *effect_type == EffectType::Sort {
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Sort By:" }
-                        select {
-                            value: match *sort_by.read() {
-                                SortBy::Red=>"0",
-                                SortBy::Green=>"1",
-                                SortBy::Blue=>"2",
-                                SortBy::Hue=>"3",
-                                SortBy::Saturation=>"4",
-                                SortBy::Luminance=>"5",
-                                SortBy::Value => "6"
-                            },
-                            onchange: move |evt| {
-                                match evt.value().as_str() {
-                                    "0" => sort_by.set(SortBy::Red),
-                                    "1" => sort_by.set(SortBy::Green),
-                                    "2" => sort_by.set(SortBy::Blue),
-                                    "3" => sort_by.set(SortBy::Hue),
-                                    "4" => sort_by.set(SortBy::Saturation),
-                                    "5" => sort_by.set(SortBy::Luminance),
-                                    "6" => sort_by.set(SortBy::Value),
-                                    _ => {}
-                                }
-                            },
-                            option { value: "0", "Red" }
-                            option { value: "1", "Green" }
-                            option { value: "2", "Blue" }
-                            option { value: "3", "Hue" }
-                            option { value: "4", "Saturation" }
-                            option { value: "5", "Luminance" }
-                            option { value: "6", "Value" }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Direction:" }
-                        select {
-                            value: match *direction.read() {
-                                Direction::Horizontal => "0",
-                                Direction::Vertical => "1",
-                            },
-                            onchange: move |evt| {
-                                match evt.value().as_str() {
-                                    "0" => direction.set(Direction::Horizontal),
-                                    "1" => direction.set(Direction::Vertical),
-                                    _ => {}
-                                }
-                            },
-                            option { value: "0", "Horizontal" }
-                            option { value: "1", "Vertical" }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Min Threshold:" }
-                        input {
-                            r#type: "number",
-                            step: "0.1",
-                            min: "0",
-                            max: "255",
-                            value: "{sort_min_threshold.read()}",
-                            onchange: move |evt| {
-                                if let Ok(val) = evt.value().parse::<f64>() {
-                                    sort_min_threshold.set(val);
-                                }
+        // Show inputs for the parameters the selected effect declares
+        if let Some(effect) = draft.read().as_ref() {
+            for spec in effect.specs() {
+                if let Some(value) = effect.params().get(spec.key) {
+                    ParamEditor {
+                        key: "{effect.id()}-{spec.key}",
+                        spec,
+                        value: value.clone(),
+                        error: effect.param_error(spec.key),
+                        onchange: move |value| {
+                            if let Some(effect) = draft.write().as_mut() {
+                                effect.set_param(spec.key, value);
                             }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label { "Max Threshold:" }
-                        input {
-                            r#type: "number",
-                            step: "0.1",
-                            min: "0",
-                            max: "255",
-                            value: "{sort_max_threshold.read()}",
-                            onchange: move |evt| {
-                                if let Ok(val) = evt.value().parse::<f64>() {
-                                    sort_max_threshold.set(val);
-                                }
-                            }
-                        }
-                    }
-
-                    div {
-                        style: "margin: 10px 0;",
-                        label {
-                            style: "display: flex; align-items: center; gap: 5px;",
-                            input {
-                                r#type: "checkbox",
-                                checked: *reversed.read(),
-                                onchange: move |evt| {
-                                    reversed.set(evt.checked());
-                                }
-                            }
-                            "Reversed"
-                        }
+                        },
                     }
                 }
             }
+        }
 
-            div {
-                style: "margin: 20px 0;",
-                button {
-                    r#type: "submit",
-                    disabled: selected_effect_type.read().is_none(),
-                    style: "padding: 10px 20px; background-color: #000000; color: white; border: none; border-radius: 4px; cursor: pointer;",
-                    "Add Effect"
-                }
+        div {
+            style: "margin: 20px 0;",
+            button {
+                r#type: "submit",
+                disabled: !draft.read().as_ref().is_some_and(|effect| {
+                    effect.specs().iter().all(|spec| effect.param_error(spec.key).is_none())
+                }),
+                style: "padding: 10px 20px; background-color: #000000; color: white; border: none; border-radius: 4px; cursor: pointer;",
+                "Add Effect"
             }
         }
     }
+    }
 }
 
 #[component]
 pub fn Home() -> Element {
     let mut uploaded_image = use_signal(|| None::<DynamicImage>);
-    let mut original_image_url = use_signal(|| None::<String>);
-    let mut processed_image_url = use_signal(|| None::<String>);
-    let mut upload_status = use_signal(|| String::new());
+    // Downscaled copy of the upload and its scale, used for previews
+    let mut preview_image = use_signal(|| None::<DynamicImage>);
+    let mut preview_scale = use_signal(|| 1.0f64);
+    let mut original_preview = use_signal(|| None::<RgbaImage>);
+    let mut processed_image = use_signal(|| None::<RgbaImage>);
+    let mut source_name = use_signal(String::new);
+    let mut image_metadata = use_signal(|| None::<ImageMetadata>);
+    let mut upload_status = use_signal(String::new);
     let mut is_processing = use_signal(|| false);
-    let color = use_signal(|| Rgb([0, 0, 0]));
-    let effect_list = use_signal(|| vec![]);
+    let mut effect_list = use_signal(Vec::new);
     let mut side_by_side_layout = use_signal(|| false);
+    let mut export_open = use_signal(|| false);
+
+    let mut image_url = use_signal(String::new);
+
+    // Shared with the color pickers for the eyedropper, recent colors and image palette
+    let palette = use_memo(move || {
+        original_preview
+            .read()
+            .as_ref()
+            .map(|image| extract_palette(image, PALETTE_SIZE))
+            .unwrap_or_default()
+    });
+    let palettes = use_saved_palettes();
+    use_context_provider(|| ColorPickerContext {
+        eyedropper: Signal::new(None),
+        recent: Signal::new(Vec::new()),
+        palette,
+        palettes,
+    });
+    let snippets = use_saved_snippets();
+    use_context_provider(|| SnippetContext { snippets });
+    let mut drag_over = use_signal(|| false);
+
+    // Threshold mask of one step over the preview, updated as its
+    // parameters change. The steps before it only rerun when they change.
+    let mask_step = use_signal(|| None::<usize>);
+    use_context_provider(|| ThresholdMaskContext { step: mask_step });
+    let mask_preceding = use_memo(move || {
+        let step = mask_step()?;
+        effect_list.read().get(..step).map(<[_]>::to_vec)
+    });
+    let mask_input = use_memo(move || {
+        let effects = mask_preceding()?;
+        let image = preview_image.read().clone()?;
+        apply_effect_chain(image, &effects, preview_scale()).ok()
+    });
+    let mut mask_overlay = use_signal(|| None::<RgbaImage>);
+    use_effect(move || {
+        let overlay = mask_step().and_then(|step| {
+            let effect = effect_list.read().get(step)?.clone();
+            let input = mask_input.read();
+            let input = input.as_ref()?;
+            Some(highlight_mask(input, &effect.threshold_mask(input)?))
+        });
+        mask_overlay.set(overlay);
+    });
+
+    // Decode an image from any source (file picker, drop, paste or URL)
+    let mut load_source_image = move |file_name: String, file_data: Vec<u8>| {
+        // Detect the format from the file contents rather than its extension
+        let Some(format) = detect_image_format(&file_data, &file_name) else {
+            upload_status.set(format!(
+                "'{}' is not a recognised image file (supported: {})",
+                file_name,
+                SUPPORTED_FORMATS.map(format_name).join(", ")
+            ));
+            return;
+        };
+        if !is_supported_format(format) {
+            upload_status.set(format!(
+                "'{}' is a {} image, which is not supported",
+                file_name,
+                format_name(format)
+            ));
+            return;
+        }
+
+        // Load the image from bytes, applying its EXIF orientation
+        match load_image_with_metadata(&file_data, format) {
+            Ok((dynamic_image, metadata)) => {
+                let (proxy, scale) = create_proxy_image(&dynamic_image, PREVIEW_MAX_EDGE);
+
+                upload_status.set(if scale < 1.0 {
+                    format!(
+                        "{} image '{}' loaded successfully! Previewing at {}×{} (full size {}×{})",
+                        format_name(format),
+                        file_name,
+                        proxy.width(),
+                        proxy.height(),
+                        dynamic_image.width(),
+                        dynamic_image.height()
+                    )
+                } else {
+                    format!(
+                        "{} image '{}' loaded successfully!",
+                        format_name(format),
+                        file_name
+                    )
+                });
+                original_preview.set(Some(proxy.to_rgba8()));
+                processed_image.set(None);
+
+                // Store the original image and its preview proxy
+                uploaded_image.set(Some(dynamic_image));
+                preview_image.set(Some(proxy));
+                preview_scale.set(scale);
+                source_name.set(file_name);
+                image_metadata.set(Some(metadata));
+            }
+            Err(e) => {
+                upload_status.set(format!(
+                    "Error loading {} image: {}",
+                    format_name(format),
+                    e
+                ));
+            }
+        }
+    };
+
+    // Load the first file of a file picker or drop event
+    let mut load_from_files = move |file_engine: Arc<dyn FileEngine>| {
+        if let Some(file_name) = file_engine.files().first().cloned() {
+            upload_status.set("Loading image...".to_string());
+
+            spawn(async move {
+                match file_engine.read_file(&file_name).await {
+                    Some(file_data) => load_source_image(file_name, file_data),
+                    None => upload_status.set("Failed to read file data".to_string()),
+                }
+            });
+        }
+    };
 
     let handle_file_upload = move |evt: Event<FormData>| {
         if let Some(file_engine) = evt.files() {
-            let files = file_engine.files();
-            if let Some(file_name) = files.get(0) {
-                let file_name = file_name.clone();
+            load_from_files(file_engine);
+        }
+    };
 
-                // Check if it's an image file first
-                if is_image_file(&file_name) {
-                    upload_status.set("Loading image...".to_string());
+    let handle_drop = move |evt: DragEvent| {
+        evt.prevent_default();
+        drag_over.set(false);
+        if let Some(file_engine) = evt.files() {
+            load_from_files(file_engine);
+        }
+    };
 
-                    spawn(async move {
-                        if let Some(file_data) = file_engine.read_file(&file_name).await {
-                            // Load the image from bytes
-                            match image::load_from_memory(&file_data) {
-                                Ok(dynamic_image) => {
-                                    // Store the original image
-                                    uploaded_image.set(Some(dynamic_image.clone()));
+    let load_from_url = move |_| {
+        let url = image_url.read().trim().to_string();
+        if url.is_empty() {
+            return;
+        }
+        upload_status.set(format!("Downloading {}...", url));
 
-                                    // Create preview of original image
-                                    match create_preview_data_url(&dynamic_image) {
-                                        Ok(data_url) => {
-                                            original_image_url.set(Some(data_url));
-                                            upload_status.set(format!(
-                                                "Image '{}' loaded successfully!",
-                                                file_name
-                                            ));
-                                            processed_image_url.set(None);
-                                        }
-                                        Err(e) => {
-                                            upload_status
-                                                .set(format!("Error creating preview: {}", e));
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    upload_status.set(format!("Error loading image: {}", e));
-                                }
-                            }
-                        } else {
-                            upload_status.set("Failed to read file data".to_string());
-                        }
-                    });
-                } else {
-                    upload_status.set(
-                        "Please select a valid image file (JPG, PNG, GIF, WebP, BMP)".to_string(),
-                    );
+        spawn(async move {
+            match fetch_image_from_url(url).await {
+                Ok((file_name, file_data)) => load_source_image(file_name, file_data),
+                Err(ServerFnError::ServerError(message)) => {
+                    upload_status.set(format!("Error loading image from URL: {}", message))
                 }
+                Err(e) => upload_status.set(format!("Error loading image from URL: {}", e)),
             }
-        }
+        });
     };
 
-    // Handle processing with effect chain
+    // Ctrl+V anywhere on the page: the script forwards pasted image files as
+    // (name, base64) pairs
+    use_future(move || async move {
+        let mut paste_listener = document::eval(PASTE_LISTENER_JS);
+        while let Ok((file_name, encoded)) = paste_listener.recv::<(String, String)>().await {
+            match base64::engine::general_purpose::STANDARD.decode(encoded) {
+                Ok(file_data) => load_source_image(file_name, file_data),
+                Err(e) => upload_status.set(format!("Error reading pasted image: {}", e)),
+            }
+        }
+    });
+
+    // Handle processing with effect chain, on the preview proxy
     let apply_effects = move |_| {
-        if let Some(image) = uploaded_image.read().clone() {
+        if let Some(image) = preview_image.read().clone() {
             let effects: Vec<crate::backend::Effect> = effect_list.read().clone();
             if effects.is_empty() {
                 upload_status.set("No effects in the list to apply".to_string());
                 return;
             }
+            let scale = *preview_scale.read();
             is_processing.set(true);
             upload_status.set("Processing image with effect chain...".to_string());
             spawn(async move {
-                let mut current_image = image;
-                // Apply each effect in sequence
-                for (index, effect) in effects.iter().enumerate() {
-                    upload_status.set(format!(
-                        "Applying effect {} of {}: {}",
-                        index + 1,
-                        effects.len(),
-                        effect.name()
-                    ));
-                    match apply_single_effect(current_image.clone(), effect) {
-                        Ok(processed) => {
-                            // Convert RgbaImage back to DynamicImage for the next effect
-                            current_image = DynamicImage::ImageRgba8(processed);
-                        }
-                        Err(e) => {
-                            upload_status.set(format!(
-                                "Error applying effect {}: {}",
-                                index + 1,
-                                e
-                            ));
-                            is_processing.set(false);
-                            return;
-                        }
-                    }
-                }
-                // Convert final result to data URL for display
-                let final_rgba = current_image.to_rgba8();
-                match rgba_image_to_data_url(&final_rgba) {
-                    Ok(data_url) => {
-                        processed_image_url.set(Some(data_url));
-                        upload_status
-                            .set(format!("Successfully applied {} effects!", effects.len()));
-                    }
+                let final_rgba = match apply_effect_chain(image, &effects, scale) {
+                    Ok(final_rgba) => final_rgba,
                     Err(e) => {
-                        upload_status.set(format!("Error creating final image: {}", e));
+                        upload_status.set(e.to_string());
+                        is_processing.set(false);
+                        return;
                     }
-                }
+                };
+                processed_image.set(Some(final_rgba));
+                upload_status.set(format!("Successfully applied {} effects!", effects.len()));
                 is_processing.set(false);
             });
         }
     };
 
-    fn apply_single_effect(
-        image: DynamicImage,
-        effect: &crate::backend::Effect,
-    ) -> Result<RgbaImage, ServerFnError> {
-        match effect {
-            crate::backend::Effect::Or {
-                color,
-                negate,
-                lhs,
-                rhs,
-            } => or(image, *color, lhs.clone(), rhs.clone(), *negate),
-            crate::backend::Effect::And {
-                color,
-                negate,
-                lhs,
-                rhs,
-            } => and(image, *color, lhs.clone(), rhs.clone(), *negate),
-            crate::backend::Effect::Xor {
-                color,
-                negate,
-                lhs,
-                rhs,
-            } => xor(image, *color, lhs.clone(), rhs.clone(), *negate),
-            crate::backend::Effect::Left { bits, negate, lhs } => {
-                left(image, bits.clone(), lhs.clone(), *negate)
-            }
-            crate::backend::Effect::Right { bits, negate, lhs } => {
-                right(image, bits.clone(), lhs.clone(), *negate)
-            }
-            crate::backend::Effect::Add { color, lhs, rhs } => {
-                add(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Sub {
-                color,
-                negate,
-                lhs,
-                rhs,
-            } => sub(image, lhs.clone(), rhs.clone(), *color),
-            crate::backend::Effect::Mult { color, lhs, rhs } => {
-                mult(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Pow { color, lhs, rhs } => {
-                pow(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Div { color, lhs, rhs } => {
-                div(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Average { color, lhs, rhs } => {
-                average(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Screen { color, lhs, rhs } => {
-                screen(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Overlay { color, lhs, rhs } => {
-                overlay(image, lhs.clone(), rhs.clone(), *color)
-            }
-            crate::backend::Effect::Bloom {
-                intensity,
-                radius,
-                min_threshold,
-                max_threshold,
-            } => bloom(image, *intensity, *radius, *min_threshold, *max_threshold),
-            crate::backend::Effect::Sort {
-                direction,
-                sort_by,
-                min_threshold,
-                max_threshold,
-                reversed,
-            } => sort(
-                image,
-                *sort_by,
-                *direction,
-                *min_threshold,
-                *max_threshold,
-                *reversed,
-            ),
-        }
-    }
-
     let clear_images = move |_| {
         uploaded_image.set(None);
-        original_image_url.set(None);
-        processed_image_url.set(None);
+        preview_image.set(None);
+        preview_scale.set(1.0);
+        original_preview.set(None);
+        processed_image.set(None);
+        source_name.set(String::new());
+        image_metadata.set(None);
         upload_status.set(String::new());
         is_processing.set(false);
     };
 
+    // Replace the effect list with one saved by "Save Chain"
+    let load_chain = move |evt: FormEvent| {
+        let Some(file_engine) = evt.files() else {
+            return;
+        };
+        let Some(file_name) = file_engine.files().first().cloned() else {
+            return;
+        };
+        spawn(async move {
+            let Some(contents) = file_engine.read_file_to_string(&file_name).await else {
+                upload_status.set(format!("Failed to read '{}'", file_name));
+                return;
+            };
+            match serde_json::from_str::<Vec<crate::backend::Effect>>(&contents) {
+                Ok(effects) => {
+                    upload_status.set(format!(
+                        "Loaded {} effects from '{}'",
+                        effects.len(),
+                        file_name
+                    ));
+                    effect_list.set(effects);
+                }
+                Err(e) => upload_status.set(format!("Error loading '{}': {}", file_name, e)),
+            }
+        });
+    };
+
+    let chain_url = use_memo(move || {
+        serde_json::to_string_pretty(&*effect_list.read())
+            .map(|json| bytes_to_data_url(json.as_bytes(), "application/json"))
+            .unwrap_or_default()
+    });
+
     let toggle_layout = move |_| {
         let current_value = *side_by_side_layout.read();
         side_by_side_layout.set(!current_value);
@@ -920,11 +413,27 @@ pub fn Home() -> Element {
 
                 input {
                     r#type: "file",
-                    accept: "image/*",
                     onchange: handle_file_upload,
                     style: "width: 100%; padding: 10px; border: 1px solid #ccc; border-radius: 4px; box-sizing: border-box;",
                 }
 
+                div {
+                    style: "display: flex; gap: 8px; margin: 10px 0;",
+                    input {
+                        r#type: "url",
+                        placeholder: "https://example.com/image.png",
+                        value: "{image_url.read()}",
+                        style: "flex: 1; min-width: 0; padding: 8px; border: 1px solid #ccc; border-radius: 4px;",
+                        oninput: move |evt| image_url.set(evt.value()),
+                    }
+                    button {
+                        class: "control-button",
+                        disabled: image_url.read().trim().is_empty(),
+                        onclick: load_from_url,
+                        "Load from URL"
+                    }
+                }
+
                 if !upload_status.read().is_empty() {
                     div {
                         style: "padding: 15px; margin-bottom: 20px; border-radius: 5px; background-color: #000000; border: 1px solid #bee5eb;",
@@ -932,11 +441,47 @@ pub fn Home() -> Element {
                     }
                 }
 
+                if let Some(metadata) = image_metadata.read().as_ref() {
+                    MetadataPanel { metadata: metadata.clone() }
+                }
+
+                if original_preview.read().is_some() {
+                    details {
+                        class: "histogram-panel",
+                        summary { "Histograms" }
+                        HistogramChart { label: "Original", image: original_preview }
+                        HistogramChart { label: "Processed", image: processed_image }
+                    }
+                }
+
                 div {
                     style: "display: flex; flex-direction: column; gap: 20px; justify-content:center; align-items: center;",
                     EffectForm { effect_list: effect_list }
                 }
 
+                div {
+                    style: "display: flex; gap: 8px; justify-content: center;",
+                    if !effect_list.read().is_empty() {
+                        a {
+                            class: "control-button",
+                            style: "text-decoration: none;",
+                            href: "{chain_url}",
+                            download: "effect-chain.json",
+                            "Save Chain"
+                        }
+                    }
+                    label {
+                        class: "control-button",
+                        "Load Chain"
+                        input {
+                            r#type: "file",
+                            accept: ".json,application/json",
+                            style: "display: none;",
+                            onchange: load_chain,
+                        }
+                    }
+                }
+
                 div {
                     style: "display: flex; width: 100%;",
                     ul {
@@ -965,6 +510,14 @@ pub fn Home() -> Element {
                             if *is_processing.read() { "Processing..." } else { "Apply Effects" }
                         }
 
+                        if processed_image.read().is_some() {
+                            button {
+                                class: "control-button",
+                                onclick: move |_| export_open.set(true),
+                                "Export"
+                            }
+                        }
+
                         button {
                             class: "control-button",
                             onclick: clear_images,
@@ -973,7 +526,7 @@ pub fn Home() -> Element {
                     }
                 }
 
-                if original_image_url.read().is_some() || processed_image_url.read().is_some() {
+                if original_preview.read().is_some() || processed_image.read().is_some() {
                     button {
                         class: "layout-toggle",
                         onclick: toggle_layout,
@@ -990,26 +543,48 @@ pub fn Home() -> Element {
             // Images display section
             div {
                 class: if *side_by_side_layout.read() { "images-container side-by-side" } else { "images-container" },
+                class: if *drag_over.read() { "drag-over" },
+                ondragover: move |evt| {
+                    evt.prevent_default();
+                    drag_over.set(true);
+                },
+                ondragleave: move |_| drag_over.set(false),
+                ondrop: handle_drop,
 
                 // Layout toggle button - only show when images are present
 
-                // Original image
-                if let Some(original_url) = original_image_url.read().as_ref() {
+                // Threshold mask in place of the original while it is shown
+                if mask_overlay.read().is_some() {
                     div {
                         class: "image-wrapper",
-                        img {
-                            src: "{original_url}",
+                        p {
+                            class: "mask-caption",
+                            "Threshold mask of step {mask_step().unwrap_or_default() + 1}: highlighted pixels are affected"
+                        }
+                        ImagePreview {
+                            id: "threshold-mask",
+                            image: mask_overlay,
+                            alt: "Threshold mask",
+                        }
+                    }
+                } else if original_preview.read().is_some() {
+                    div {
+                        class: "image-wrapper",
+                        ImagePreview {
+                            id: "original",
+                            image: original_preview,
                             alt: "Original image",
                         }
                     }
                 }
 
                 // Processed image
-                if let Some(processed_url) = processed_image_url.read().as_ref() {
+                if processed_image.read().is_some() {
                     div {
                         class: "image-wrapper",
-                        img {
-                            src: "{processed_url}",
+                        ImagePreview {
+                            id: "processed",
+                            image: processed_image,
                             alt: "Processed image",
                         }
                     }
@@ -1019,10 +594,33 @@ pub fn Home() -> Element {
                 if uploaded_image.read().is_none() {
                     div {
                         class: "no-image-message",
-                        "Upload an image to get started!"
+                        "Upload, drop or paste an image to get started!"
                     }
                 }
             }
+
+            ExportDialog {
+                source: uploaded_image,
+                metadata: image_metadata,
+                source_name: source_name.read().clone(),
+                effect_list,
+                open: export_open,
+            }
         }
     }
 }
+
+/// `image` with the pixels set in `mask` tinted magenta and the others
+/// darkened
+fn highlight_mask(image: &RgbaImage, mask: &GrayImage) -> RgbaImage {
+    let mut output = image.clone();
+    for (pixel, inside) in output.pixels_mut().zip(mask.pixels()) {
+        let [r, g, b, _] = pixel.0;
+        pixel.0 = if inside.0[0] > 0 {
+            [r / 2 + 127, g / 2, b / 2 + 127, 255]
+        } else {
+            [r / 3, g / 3, b / 3, 255]
+        };
+    }
+    output
+}
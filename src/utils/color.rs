@@ -0,0 +1,238 @@
+use image::{Rgb, RgbaImage};
+
+/// Convert to HSV: hue in degrees (0-360), saturation and value in 0-1
+pub fn rgb_to_hsv(color: Rgb<u8>) -> (f32, f32, f32) {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    (hue(r, g, b, max, delta), saturation, max)
+}
+
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Rgb<u8> {
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+    let chroma = value * saturation;
+    from_chroma(hue, chroma, value - chroma)
+}
+
+/// Convert to HSL: hue in degrees (0-360), saturation and lightness in 0-1
+pub fn rgb_to_hsl(color: Rgb<u8>) -> (f32, f32, f32) {
+    let [r, g, b] = color.0.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let lightness = (max + min) / 2.0;
+    let saturation = if delta > 0.0 {
+        delta / (1.0 - (2.0 * lightness - 1.0).abs())
+    } else {
+        0.0
+    };
+    (hue(r, g, b, max, delta), saturation.min(1.0), lightness)
+}
+
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> Rgb<u8> {
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    from_chroma(hue, chroma, lightness - chroma / 2.0)
+}
+
+/// Convert to CIELAB under D65: L in 0-100, a and b roughly -128-127.
+///
+/// Takes fractional 0-255 channels so that in-between colors can be compared.
+pub fn rgb_to_lab(color: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = color.map(srgb_to_linear);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Convert to OKLab: L in 0-1, a and b roughly -0.4-0.4. Takes fractional
+/// 0-255 channels like [`rgb_to_lab`].
+pub fn rgb_to_oklab(color: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = color.map(srgb_to_linear);
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// sRGB channel in 0-255 to linear light in 0-1
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    let c = (channel / 255.0).clamp(0.0, 1.0);
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Linear light in 0-1 to an sRGB channel in 0-255, the inverse of
+/// [`srgb_to_linear`]
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    let c = linear.clamp(0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    encoded * 255.0
+}
+
+/// Luminance of a color with Rec. 709 weights, in the units of its channels.
+///
+/// Weighs the gamma-encoded channels like the Sort and Bloom thresholds, so
+/// every effect and the histogram agree on how bright a pixel is.
+pub fn luminance([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn hue(r: f32, g: f32, b: f32, max: f32, delta: f32) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    hue.rem_euclid(360.0)
+}
+
+fn from_chroma(hue: f32, chroma: f32, offset: f32) -> Rgb<u8> {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Rgb([r, g, b].map(|c| ((c + offset) * 255.0).round().clamp(0.0, 255.0) as u8))
+}
+
+/// Parse `#rrggbb` or `#rgb`; the `#` is optional
+pub fn parse_hex_color(text: &str) -> Option<Rgb<u8>> {
+    let hex = text.trim().trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+        }
+        3 => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|c| c * 17);
+            Some(Rgb([channel(0)?, channel(1)?, channel(2)?]))
+        }
+        _ => None,
+    }
+}
+
+/// Format as `#rrggbb`
+pub fn to_hex(color: Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0[0], color.0[1], color.0[2])
+}
+
+/// Dominant colors of an image, most common first.
+///
+/// Uses median cut on a subsample of the opaque pixels.
+pub fn extract_palette(image: &RgbaImage, count: usize) -> Vec<Rgb<u8>> {
+    const MAX_SAMPLES: usize = 20_000;
+
+    let pixel_count = image.width() as usize * image.height() as usize;
+    let step = (pixel_count / MAX_SAMPLES).max(1);
+    let pixels: Vec<[u8; 3]> = image
+        .pixels()
+        .step_by(step)
+        .filter(|pixel| pixel.0[3] > 0)
+        .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]])
+        .collect();
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        // Split the box with the widest channel range at its median
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| {
+                let (channel, range) = widest_channel(pixels);
+                (index, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+        let Some((index, channel, range)) = widest else {
+            break;
+        };
+        if range == 0 {
+            break;
+        }
+
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = lower.split_off(lower.len() / 2);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    // Median splits can leave several boxes of the same color; merge them
+    let mut palette: Vec<(Rgb<u8>, usize)> = Vec::new();
+    for pixels in &boxes {
+        let color = average(pixels);
+        match palette.iter_mut().find(|(existing, _)| *existing == color) {
+            Some((_, weight)) => *weight += pixels.len(),
+            None => palette.push((color, pixels.len())),
+        }
+    }
+    palette.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+    palette.into_iter().map(|(color, _)| color).collect()
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+fn average(pixels: &[[u8; 3]]) -> Rgb<u8> {
+    let mut sums = [0u64; 3];
+    for pixel in pixels {
+        for (sum, value) in sums.iter_mut().zip(pixel) {
+            *sum += *value as u64;
+        }
+    }
+    let len = pixels.len().max(1) as u64;
+    Rgb(sums.map(|sum| (sum / len) as u8))
+}
@@ -0,0 +1,114 @@
+/// Value of a tone curve at `x`, both in 0-1.
+///
+/// `points` are `[x, y]` control points sorted by x. The curve passes through
+/// all of them with a monotone cubic, so it never overshoots between points,
+/// and is flat beyond the first and last one.
+pub fn interpolate_curve(points: &[[f64; 2]], x: f64) -> f64 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return x;
+    };
+    if x <= first[0] {
+        return first[1];
+    }
+    if x >= last[0] {
+        return last[1];
+    }
+
+    let segment = points
+        .windows(2)
+        .position(|pair| x < pair[1][0])
+        .unwrap_or(points.len() - 2);
+    let ([x0, y0], [x1, y1]) = (points[segment], points[segment + 1]);
+    let width = x1 - x0;
+    if width <= 0.0 {
+        return y1;
+    }
+
+    let (m0, m1) = (tangent(points, segment), tangent(points, segment + 1));
+    let t = (x - x0) / width;
+    let (t2, t3) = (t * t, t * t * t);
+    let y = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+        + (t3 - 2.0 * t2 + t) * width * m0
+        + (-2.0 * t3 + 3.0 * t2) * y1
+        + (t3 - t2) * width * m1;
+    y.clamp(0.0, 1.0)
+}
+
+/// Slope of the segment starting at point `i`
+fn secant(points: &[[f64; 2]], i: usize) -> f64 {
+    let ([x0, y0], [x1, y1]) = (points[i], points[i + 1]);
+    if x1 > x0 {
+        (y1 - y0) / (x1 - x0)
+    } else {
+        0.0
+    }
+}
+
+/// Tangent at point `i`, limited as by Fritsch and Carlson so the curve
+/// stays monotone wherever the points are
+fn tangent(points: &[[f64; 2]], i: usize) -> f64 {
+    let last = points.len() - 1;
+    if i == 0 {
+        return secant(points, 0);
+    }
+    if i == last {
+        return secant(points, last - 1);
+    }
+    let (before, after) = (secant(points, i - 1), secant(points, i));
+    if before * after <= 0.0 {
+        return 0.0;
+    }
+    // Weighted harmonic mean of the neighboring slopes
+    let (h0, h1) = (
+        points[i][0] - points[i - 1][0],
+        points[i + 1][0] - points[i][0],
+    );
+    let (w0, w1) = (2.0 * h1 + h0, h1 + 2.0 * h0);
+    (w0 + w1) / (w0 / before + w1 / after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(points: &[[f64; 2]]) -> Vec<f64> {
+        (0..=1000)
+            .map(|i| interpolate_curve(points, i as f64 / 1000.0))
+            .collect()
+    }
+
+    #[test]
+    fn passes_through_points_and_is_flat_beyond_them() {
+        let points = [[0.2, 0.1], [0.5, 0.7], [0.8, 0.9]];
+        for [x, y] in points {
+            assert!((interpolate_curve(&points, x) - y).abs() < 1e-12);
+        }
+        assert_eq!(interpolate_curve(&points, 0.0), 0.1);
+        assert_eq!(interpolate_curve(&points, 1.0), 0.9);
+
+        let identity = [[0.0, 0.0], [1.0, 1.0]];
+        for x in [0.0, 0.25, 0.5, 0.9, 1.0] {
+            assert!((interpolate_curve(&identity, x) - x).abs() < 1e-12);
+        }
+        assert_eq!(interpolate_curve(&[], 0.3), 0.3);
+        assert_eq!(interpolate_curve(&[[0.4, 0.6]], 0.9), 0.6);
+    }
+
+    #[test]
+    fn never_overshoots_between_points() {
+        // Steep jumps next to flat stretches make an unlimited cubic ring
+        let rising = [[0.0, 0.0], [0.3, 0.05], [0.4, 0.9], [0.7, 0.95], [1.0, 1.0]];
+        let values = samples(&rising);
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let falling = [[0.0, 1.0], [0.45, 0.98], [0.55, 0.02], [1.0, 0.0]];
+        let values = samples(&falling);
+        assert!(values.windows(2).all(|pair| pair[0] >= pair[1]));
+
+        // A flat stretch between equal points stays flat
+        let plateau = [[0.0, 0.0], [0.3, 0.5], [0.7, 0.5], [1.0, 1.0]];
+        for x in [0.35, 0.5, 0.65] {
+            assert_eq!(interpolate_curve(&plateau, x), 0.5);
+        }
+    }
+}
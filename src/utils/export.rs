@@ -0,0 +1,266 @@
+use base64::Engine;
+use image::codecs::gif::GifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, RgbaImage};
+use std::io::Cursor;
+
+use super::metadata::{embed_exif, normalize_exif_orientation, ImageMetadata};
+
+/// Output formats offered by the export dialog
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Bmp,
+    Tiff,
+    Qoi,
+    Gif,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 7] = [
+        ExportFormat::Png,
+        ExportFormat::Jpeg,
+        ExportFormat::WebP,
+        ExportFormat::Bmp,
+        ExportFormat::Tiff,
+        ExportFormat::Qoi,
+        ExportFormat::Gif,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "PNG",
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::WebP => "WebP (lossless)",
+            ExportFormat::Bmp => "BMP",
+            ExportFormat::Tiff => "TIFF",
+            ExportFormat::Qoi => "QOI",
+            ExportFormat::Gif => "GIF",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::WebP => "webp",
+            ExportFormat::Bmp => "bmp",
+            ExportFormat::Tiff => "tiff",
+            ExportFormat::Qoi => "qoi",
+            ExportFormat::Gif => "gif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "image/png",
+            ExportFormat::Jpeg => "image/jpeg",
+            ExportFormat::WebP => "image/webp",
+            ExportFormat::Bmp => "image/bmp",
+            ExportFormat::Tiff => "image/tiff",
+            ExportFormat::Qoi => "image/qoi",
+            ExportFormat::Gif => "image/gif",
+        }
+    }
+
+    /// Whether the encoder takes a quality setting
+    pub fn has_quality(&self) -> bool {
+        matches!(self, ExportFormat::Jpeg)
+    }
+
+    /// Whether EXIF and ICC profile data can be written to this format
+    pub fn supports_metadata(&self) -> bool {
+        matches!(
+            self,
+            ExportFormat::Png | ExportFormat::Jpeg | ExportFormat::WebP
+        )
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            ExportFormat::Png => ImageFormat::Png,
+            ExportFormat::Jpeg => ImageFormat::Jpeg,
+            ExportFormat::WebP => ImageFormat::WebP,
+            ExportFormat::Bmp => ImageFormat::Bmp,
+            ExportFormat::Tiff => ImageFormat::Tiff,
+            ExportFormat::Qoi => ImageFormat::Qoi,
+            ExportFormat::Gif => ImageFormat::Gif,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// JPEG quality, 1-100
+    pub quality: u8,
+    /// Target (width, height); `None` keeps the original size
+    pub resize: Option<(u32, u32)>,
+    /// Copy the source EXIF block into the output
+    pub keep_exif: bool,
+    /// Embed the source ICC profile in the output
+    pub keep_icc: bool,
+}
+
+/// Encode the final image with the chosen format, quality and size.
+///
+/// EXIF and ICC data from `metadata` are written when the options ask for
+/// them and the format can hold them; otherwise they are dropped.
+pub fn encode_image(
+    rgba_image: &RgbaImage,
+    options: &ExportOptions,
+    metadata: Option<&ImageMetadata>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut image = DynamicImage::ImageRgba8(rgba_image.clone());
+
+    if let Some((width, height)) = options.resize {
+        if width == 0 || height == 0 {
+            return Err("Export size must be at least 1x1".into());
+        }
+        if (width, height) != (image.width(), image.height()) {
+            image = image.resize_exact(width, height, FilterType::Lanczos3);
+        }
+    }
+
+    let with_metadata = options.format.supports_metadata();
+    let icc_profile = metadata
+        .filter(|_| with_metadata && options.keep_icc)
+        .and_then(|metadata| metadata.icc_profile.clone());
+    let exif = metadata
+        .filter(|_| with_metadata && options.keep_exif)
+        .and_then(|metadata| metadata.exif.as_deref());
+
+    let mut buffer = Cursor::new(Vec::new());
+
+    match options.format {
+        ExportFormat::Png => {
+            let mut encoder = PngEncoder::new(&mut buffer);
+            if let Some(icc_profile) = icc_profile {
+                encoder.set_icc_profile(icc_profile)?;
+            }
+            image.to_rgba8().write_with_encoder(encoder)?;
+        }
+        ExportFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let mut encoder =
+                JpegEncoder::new_with_quality(&mut buffer, options.quality.clamp(1, 100));
+            if let Some(icc_profile) = icc_profile {
+                encoder.set_icc_profile(icc_profile)?;
+            }
+            image.to_rgb8().write_with_encoder(encoder)?;
+        }
+        ExportFormat::WebP => {
+            let mut encoder = WebPEncoder::new_lossless(&mut buffer);
+            if let Some(icc_profile) = icc_profile {
+                encoder.set_icc_profile(icc_profile)?;
+            }
+            image.to_rgba8().write_with_encoder(encoder)?;
+        }
+        ExportFormat::Gif => {
+            let rgba = image.to_rgba8();
+            let mut encoder = GifEncoder::new(&mut buffer);
+            encoder.encode(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                ExtendedColorType::Rgba8,
+            )?;
+        }
+        format => {
+            image.write_to(&mut buffer, format.image_format())?;
+        }
+    }
+
+    let encoded = buffer.into_inner();
+    match exif {
+        Some(exif) => embed_exif(
+            encoded,
+            options.format.image_format(),
+            &normalize_exif_orientation(exif),
+        ),
+        None => Ok(encoded),
+    }
+}
+
+/// Wrap encoded bytes in a data URL usable as a download link
+pub fn bytes_to_data_url(bytes: &[u8], mime_type: &str) -> String {
+    let base64_string = base64::engine::general_purpose::STANDARD.encode(bytes);
+    format!("data:{};base64,{}", mime_type, base64_string)
+}
+
+/// Output size for a target width, keeping the source aspect ratio
+pub fn scaled_dimensions(source: (u32, u32), width: u32) -> (u32, u32) {
+    let (source_width, source_height) = source;
+    if source_width == 0 {
+        return (width, source_height);
+    }
+    let height = (source_height as f64 * width as f64 / source_width as f64).round() as u32;
+    (width, height.max(1))
+}
+
+/// Build an export filename from a template.
+///
+/// Supported placeholders: `{name}` (source file name without extension),
+/// `{chain}` (effect names joined with `-`), `{width}` and `{height}`.
+/// The extension of the chosen format is appended.
+pub fn render_filename(
+    template: &str,
+    source_name: &str,
+    chain: &[&str],
+    dimensions: (u32, u32),
+    format: ExportFormat,
+) -> String {
+    let stem = match source_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => source_name,
+    };
+    let stem = if stem.is_empty() { "image" } else { stem };
+    let chain = chain
+        .iter()
+        .map(|name| name.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    let rendered = template
+        .replace("{name}", stem)
+        .replace("{chain}", &chain)
+        .replace("{width}", &dimensions.0.to_string())
+        .replace("{height}", &dimensions.1.to_string());
+
+    // Strip characters that are not allowed in file names on common platforms
+    let sanitized: String = rendered
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches(|c: char| c == '_' || c == '-' || c.is_whitespace());
+    let sanitized = if sanitized.is_empty() {
+        "image"
+    } else {
+        sanitized
+    };
+
+    format!("{}.{}", sanitized, format.extension())
+}
+
+/// Human readable file size
+pub fn format_file_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    let size = bytes as f64;
+    if size >= MB {
+        format!("{:.2} MB", size / MB)
+    } else if size >= KB {
+        format!("{:.1} KB", size / KB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
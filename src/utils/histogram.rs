@@ -0,0 +1,73 @@
+use image::RgbaImage;
+
+use super::color::luminance;
+
+/// Channels a histogram counts, in order
+pub const HISTOGRAM_CHANNELS: [&str; 4] = ["Red", "Green", "Blue", "Luminance"];
+
+/// Number of pixels at each 0-255 value, per channel
+#[derive(Clone, PartialEq, Debug)]
+pub struct Histogram {
+    pub counts: [[u32; 256]; 4],
+    pub total: u32,
+}
+
+/// Summary of one channel's values
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ChannelStats {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+    pub median: u8,
+}
+
+impl Histogram {
+    /// Counts every pixel, transparent ones included, like the effects see
+    /// them. Luminance uses the same Rec. 709 weights as the Sort and Bloom
+    /// thresholds.
+    pub fn new(image: &RgbaImage) -> Histogram {
+        let mut counts = [[0u32; 256]; 4];
+        for pixel in image.pixels() {
+            let [r, g, b, _] = pixel.0;
+            let luminance = luminance([r, g, b].map(f32::from)).round() as u8;
+            for (channel, value) in [r, g, b, luminance].into_iter().enumerate() {
+                counts[channel][value as usize] += 1;
+            }
+        }
+        Histogram {
+            counts,
+            total: image.width() * image.height(),
+        }
+    }
+
+    /// Statistics of a channel in [`HISTOGRAM_CHANNELS`]; `None` for an
+    /// empty image
+    pub fn stats(&self, channel: usize) -> Option<ChannelStats> {
+        let counts = &self.counts[channel];
+        let min = counts.iter().position(|&count| count > 0)?;
+        let max = counts.iter().rposition(|&count| count > 0)?;
+        let sum: u64 = counts
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| value as u64 * count as u64)
+            .sum();
+
+        // First value with at least half of the pixels at or below it
+        let mut seen = 0u64;
+        let half = (self.total as u64).div_ceil(2);
+        let median = counts
+            .iter()
+            .position(|&count| {
+                seen += count as u64;
+                seen >= half
+            })
+            .unwrap_or(max);
+
+        Some(ChannelStats {
+            min: min as u8,
+            max: max as u8,
+            mean: sum as f64 / self.total as f64,
+            median: median as u8,
+        })
+    }
+}
@@ -1,62 +1,87 @@
-use base64::Engine;
-use image::{DynamicImage, ImageFormat, RgbaImage};
+use image::imageops::FilterType;
+#[cfg(not(feature = "web"))]
+use image::RgbaImage;
+use image::{DynamicImage, ImageFormat};
+#[cfg(not(feature = "web"))]
 use std::io::Cursor;
 
-/// Convert RgbaImage to a data URL that can be used in Dioxus
-pub fn rgba_image_to_data_url(
-    rgba_image: &RgbaImage,
-) -> Result<String, Box<dyn std::error::Error>> {
-    // Create a buffer to hold the encoded image data
-    let mut buffer = Cursor::new(Vec::new());
-
-    // Convert RgbaImage to DynamicImage for encoding
-    let dynamic_image = DynamicImage::ImageRgba8(rgba_image.clone());
-
-    // Encode as PNG (preserves alpha channel)
-    dynamic_image.write_to(&mut buffer, ImageFormat::Png)?;
-
-    // Get the encoded bytes
-    let image_bytes = buffer.into_inner();
-
-    // Encode to base64
-    let base64_string = base64::engine::general_purpose::STANDARD.encode(&image_bytes);
-
-    // Create data URL
-    let data_url = format!("data:image/png;base64,{}", base64_string);
-
-    Ok(data_url)
+/// Encode an RgbaImage as an uncompressed 32-bit BMP.
+///
+/// Used for previews: unlike PNG there is no compression step, so this is
+/// little more than a copy of the pixel buffer. Web builds draw to a canvas instead.
+#[cfg(not(feature = "web"))]
+pub fn rgba_image_to_bmp(rgba_image: &RgbaImage) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut buffer = Cursor::new(Vec::with_capacity(rgba_image.as_raw().len() + 138));
+    rgba_image.write_to(&mut buffer, ImageFormat::Bmp)?;
+    Ok(buffer.into_inner())
 }
 
-/// Create a preview data URL from a DynamicImage (for showing original image)
-pub fn create_preview_data_url(
-    dynamic_image: &DynamicImage,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut buffer = Cursor::new(Vec::new());
+/// Downscale an image so its long edge is at most `max_edge` pixels.
+///
+/// Returns the proxy and its scale relative to the source (1.0 when the
+/// source already fits).
+pub fn create_proxy_image(dynamic_image: &DynamicImage, max_edge: u32) -> (DynamicImage, f64) {
+    let long_edge = dynamic_image.width().max(dynamic_image.height());
+    if long_edge <= max_edge || long_edge == 0 {
+        return (dynamic_image.clone(), 1.0);
+    }
 
-    // Convert to RGB if it's RGBA (JPEG doesn't support alpha channel)
-    let image_for_encoding = match dynamic_image {
-        DynamicImage::ImageRgba8(_) => {
-            // Convert RGBA to RGB
-            DynamicImage::ImageRgb8(dynamic_image.to_rgb8())
-        }
-        _ => dynamic_image.clone(),
-    };
+    let proxy = dynamic_image.resize(max_edge, max_edge, FilterType::Triangle);
+    let scale = proxy.width() as f64 / dynamic_image.width() as f64;
+    (proxy, scale)
+}
 
-    // Encode as JPEG for smaller preview size
-    image_for_encoding.write_to(&mut buffer, ImageFormat::Jpeg)?;
+/// Formats the loader accepts
+pub const SUPPORTED_FORMATS: [ImageFormat; 13] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+    ImageFormat::Tiff,
+    ImageFormat::Tga,
+    ImageFormat::Qoi,
+    ImageFormat::Ico,
+    ImageFormat::Pnm,
+    ImageFormat::Dds,
+    ImageFormat::Hdr,
+    ImageFormat::OpenExr,
+];
 
-    let image_bytes = buffer.into_inner();
-    let base64_string = base64::engine::general_purpose::STANDARD.encode(&image_bytes);
-    let data_url = format!("data:image/jpeg;base64,{}", base64_string);
+/// Detect the format of an image from its magic bytes.
+///
+/// TGA files have no signature, so for those the file extension is used.
+pub fn detect_image_format(bytes: &[u8], filename: &str) -> Option<ImageFormat> {
+    image::guess_format(bytes).ok().or_else(|| {
+        ImageFormat::from_path(filename)
+            .ok()
+            .filter(|format| *format == ImageFormat::Tga)
+    })
+}
 
-    Ok(data_url)
+pub fn is_supported_format(format: ImageFormat) -> bool {
+    SUPPORTED_FORMATS.contains(&format)
 }
 
-/// Check if a filename represents an image file
-pub fn is_image_file(filename: &str) -> bool {
-    let extension = filename.split('.').last().unwrap_or("").to_lowercase();
-    matches!(
-        extension.as_str(),
-        "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp"
-    )
+/// Short display name of an image format
+pub fn format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::WebP => "WebP",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Tiff => "TIFF",
+        ImageFormat::Tga => "TGA",
+        ImageFormat::Qoi => "QOI",
+        ImageFormat::Ico => "ICO",
+        ImageFormat::Pnm => "PNM",
+        ImageFormat::Dds => "DDS",
+        ImageFormat::Hdr => "HDR",
+        ImageFormat::OpenExr => "OpenEXR",
+        ImageFormat::Avif => "AVIF",
+        ImageFormat::Farbfeld => "Farbfeld",
+        ImageFormat::Pcx => "PCX",
+        _ => "unknown",
+    }
 }
@@ -0,0 +1,463 @@
+use exif::{In, Reader, Tag};
+use image::metadata::Orientation;
+use image::{ColorType, DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult};
+use std::io::Cursor;
+
+/// Information read from the source file when it is loaded
+#[derive(Clone, PartialEq, Debug)]
+pub struct ImageMetadata {
+    /// Dimensions after the EXIF orientation has been applied
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub format: ImageFormat,
+    pub orientation: Orientation,
+    /// Human readable camera fields (make, model, exposure...)
+    pub camera: Vec<(&'static str, String)>,
+    /// Raw EXIF block (TIFF structure, without the `Exif\0\0` prefix)
+    pub exif: Option<Vec<u8>>,
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl ImageMetadata {
+    pub fn format_name(&self) -> &'static str {
+        super::format_name(self.format)
+    }
+
+    pub fn color_type_name(&self) -> &'static str {
+        match self.color_type {
+            ColorType::L8 => "Grayscale (8-bit)",
+            ColorType::La8 => "Grayscale + alpha (8-bit)",
+            ColorType::Rgb8 => "RGB (8-bit)",
+            ColorType::Rgba8 => "RGBA (8-bit)",
+            ColorType::L16 => "Grayscale (16-bit)",
+            ColorType::La16 => "Grayscale + alpha (16-bit)",
+            ColorType::Rgb16 => "RGB (16-bit)",
+            ColorType::Rgba16 => "RGBA (16-bit)",
+            ColorType::Rgb32F => "RGB (32-bit float)",
+            ColorType::Rgba32F => "RGBA (32-bit float)",
+            _ => "Other",
+        }
+    }
+
+    pub fn orientation_name(&self) -> &'static str {
+        match self.orientation {
+            Orientation::NoTransforms => "Normal",
+            Orientation::Rotate90 => "Rotated 90°",
+            Orientation::Rotate180 => "Rotated 180°",
+            Orientation::Rotate270 => "Rotated 270°",
+            Orientation::FlipHorizontal => "Mirrored horizontally",
+            Orientation::FlipVertical => "Mirrored vertically",
+            Orientation::Rotate90FlipH => "Rotated 90°, mirrored",
+            Orientation::Rotate270FlipH => "Rotated 270°, mirrored",
+        }
+    }
+}
+
+/// Decode an image of a known format and apply its EXIF orientation,
+/// keeping the metadata around
+pub fn load_image_with_metadata(
+    bytes: &[u8],
+    format: ImageFormat,
+) -> ImageResult<(DynamicImage, ImageMetadata)> {
+    let reader = ImageReader::with_format(Cursor::new(bytes), format);
+    let mut decoder = reader.into_decoder()?;
+
+    let color_type = decoder.color_type();
+    let icc_profile = decoder.icc_profile().unwrap_or(None);
+    let exif = decoder.exif_metadata().unwrap_or(None);
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    // The PNG decoder doesn't read `eXIf` chunks
+    let (exif, orientation) = match (exif, format) {
+        (None, ImageFormat::Png) => {
+            let exif = container_exif(bytes);
+            let orientation = exif
+                .as_deref()
+                .and_then(exif_orientation)
+                .unwrap_or(orientation);
+            (exif, orientation)
+        }
+        (exif, _) => (exif, orientation),
+    };
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let camera = exif.as_deref().map(camera_fields).unwrap_or_default();
+
+    let metadata = ImageMetadata {
+        width: image.width(),
+        height: image.height(),
+        color_type,
+        format,
+        orientation,
+        camera,
+        exif,
+        icc_profile,
+    };
+
+    Ok((image, metadata))
+}
+
+/// Raw EXIF block of an encoded file, found by the EXIF reader itself
+fn container_exif(bytes: &[u8]) -> Option<Vec<u8>> {
+    let exif = Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()?;
+    Some(exif.buf().to_vec())
+}
+
+fn exif_orientation(exif: &[u8]) -> Option<Orientation> {
+    let exif = Reader::new().read_raw(exif.to_vec()).ok()?;
+    let value = exif
+        .get_field(Tag::Orientation, In::PRIMARY)?
+        .value
+        .get_uint(0)?;
+    Orientation::from_exif(u8::try_from(value).ok()?)
+}
+
+fn camera_fields(exif: &[u8]) -> Vec<(&'static str, String)> {
+    const FIELDS: [(Tag, &str); 8] = [
+        (Tag::Make, "Make"),
+        (Tag::Model, "Model"),
+        (Tag::LensModel, "Lens"),
+        (Tag::DateTimeOriginal, "Taken"),
+        (Tag::ExposureTime, "Exposure"),
+        (Tag::FNumber, "Aperture"),
+        (Tag::PhotographicSensitivity, "ISO"),
+        (Tag::FocalLength, "Focal length"),
+    ];
+
+    let Ok(exif) = Reader::new().read_raw(exif.to_vec()) else {
+        return Vec::new();
+    };
+
+    FIELDS
+        .iter()
+        .filter_map(|(tag, label)| {
+            let field = exif.get_field(*tag, In::PRIMARY)?;
+            let value = field.display_value().with_unit(&exif).to_string();
+            let value = value.trim_matches(|c: char| c == '"' || c.is_whitespace());
+            (!value.is_empty()).then(|| (*label, value.to_string()))
+        })
+        .collect()
+}
+
+/// Copy of an EXIF block with the orientation tag reset to "normal".
+///
+/// The orientation is baked into the pixels on load, so keeping the original
+/// tag would make viewers rotate the exported image a second time.
+pub fn normalize_exif_orientation(exif: &[u8]) -> Vec<u8> {
+    let mut exif = exif.to_vec();
+    if exif.len() < 8 {
+        return exif;
+    }
+
+    let little_endian = match &exif[0..4] {
+        [0x49, 0x49, 42, 0] => true,
+        [0x4d, 0x4d, 0, 42] => false,
+        _ => return exif,
+    };
+    let read_u16 = |data: &[u8], at: usize| -> Option<u16> {
+        let bytes = [*data.get(at)?, *data.get(at + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |data: &[u8], at: usize| -> Option<u32> {
+        let bytes = [
+            *data.get(at)?,
+            *data.get(at + 1)?,
+            *data.get(at + 2)?,
+            *data.get(at + 3)?,
+        ];
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let Some(ifd_offset) = read_u32(&exif, 4) else {
+        return exif;
+    };
+    let ifd_offset = ifd_offset as usize;
+    let Some(entries) = read_u16(&exif, ifd_offset) else {
+        return exif;
+    };
+
+    for index in 0..entries as usize {
+        let entry = ifd_offset + 2 + index * 12;
+        let (Some(tag), Some(format)) = (read_u16(&exif, entry), read_u16(&exif, entry + 2)) else {
+            break;
+        };
+        // Orientation is a single SHORT stored inline in the entry
+        if tag == 0x112 && format == 3 && entry + 10 <= exif.len() {
+            let value = if little_endian {
+                1u16.to_le_bytes()
+            } else {
+                1u16.to_be_bytes()
+            };
+            exif[entry + 8..entry + 10].copy_from_slice(&value);
+            break;
+        }
+    }
+
+    exif
+}
+
+/// Insert an EXIF block into an encoded JPEG, PNG or WebP file
+pub fn embed_exif(
+    encoded: Vec<u8>,
+    format: ImageFormat,
+    exif: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match format {
+        ImageFormat::Jpeg => embed_exif_jpeg(encoded, exif),
+        ImageFormat::Png => embed_exif_png(encoded, exif),
+        ImageFormat::WebP => embed_exif_webp(encoded, exif),
+        _ => Err(format!("Cannot store EXIF data in {:?} files", format).into()),
+    }
+}
+
+fn embed_exif_jpeg(jpeg: Vec<u8>, exif: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return Err("Encoded data is not a JPEG file".into());
+    }
+    let segment_length = 2 + EXIF_HEADER.len() + exif.len();
+    if segment_length > u16::MAX as usize {
+        return Err("EXIF data is too large for a JPEG APP1 segment".into());
+    }
+
+    // APP1 goes after SOI and the JFIF APP0 segment, if there is one
+    let mut insert_at = 2;
+    if jpeg.len() >= 6 && jpeg[2] == 0xFF && jpeg[3] == 0xE0 {
+        insert_at = 4 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+    }
+    if insert_at > jpeg.len() {
+        return Err("Malformed JPEG header".into());
+    }
+
+    let mut output = Vec::with_capacity(jpeg.len() + segment_length + 2);
+    output.extend_from_slice(&jpeg[..insert_at]);
+    output.extend_from_slice(&[0xFF, 0xE1]);
+    output.extend_from_slice(&(segment_length as u16).to_be_bytes());
+    output.extend_from_slice(EXIF_HEADER);
+    output.extend_from_slice(exif);
+    output.extend_from_slice(&jpeg[insert_at..]);
+    Ok(output)
+}
+
+fn embed_exif_png(png: Vec<u8>, exif: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    const SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    // Signature + IHDR (length, type, 13 bytes of data, CRC)
+    const AFTER_IHDR: usize = 8 + 4 + 4 + 13 + 4;
+
+    if !png.starts_with(SIGNATURE) || png.len() < AFTER_IHDR || &png[12..16] != b"IHDR" {
+        return Err("Encoded data is not a PNG file".into());
+    }
+
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(b"eXIf");
+    crc.update(exif);
+
+    let mut output = Vec::with_capacity(png.len() + exif.len() + 12);
+    output.extend_from_slice(&png[..AFTER_IHDR]);
+    output.extend_from_slice(&(exif.len() as u32).to_be_bytes());
+    output.extend_from_slice(b"eXIf");
+    output.extend_from_slice(exif);
+    output.extend_from_slice(&crc.finalize().to_be_bytes());
+    output.extend_from_slice(&png[AFTER_IHDR..]);
+    Ok(output)
+}
+
+fn embed_exif_webp(webp: Vec<u8>, exif: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    const VP8X_EXIF_FLAG: u8 = 0x08;
+    const VP8X_ALPHA_FLAG: u8 = 0x10;
+
+    if webp.len() < 21 || &webp[0..4] != b"RIFF" || &webp[8..12] != b"WEBP" {
+        return Err("Encoded data is not a WebP file".into());
+    }
+
+    let mut output = match &webp[12..16] {
+        // Extended layout already (e.g. an ICC profile was written): set the flag
+        b"VP8X" => {
+            let mut output = webp;
+            output[20] |= VP8X_EXIF_FLAG;
+            output
+        }
+        // Simple lossless layout: prepend a VP8X header built from the VP8L header
+        b"VP8L" => {
+            if webp.len() < 25 || webp[20] != 0x2F {
+                return Err("Malformed VP8L header".into());
+            }
+            let bits = u32::from_le_bytes([webp[21], webp[22], webp[23], webp[24]]);
+            let width_minus_one = bits & 0x3FFF;
+            let height_minus_one = (bits >> 14) & 0x3FFF;
+            let has_alpha = (bits >> 28) & 1 == 1;
+
+            let mut flags = VP8X_EXIF_FLAG;
+            if has_alpha {
+                flags |= VP8X_ALPHA_FLAG;
+            }
+
+            let mut output = Vec::with_capacity(webp.len() + exif.len() + 32);
+            output.extend_from_slice(&webp[..12]);
+            output.extend_from_slice(b"VP8X");
+            output.extend_from_slice(&10u32.to_le_bytes());
+            output.extend_from_slice(&[flags, 0, 0, 0]);
+            output.extend_from_slice(&width_minus_one.to_le_bytes()[..3]);
+            output.extend_from_slice(&height_minus_one.to_le_bytes()[..3]);
+            output.extend_from_slice(&webp[12..]);
+            output
+        }
+        _ => return Err("Unsupported WebP layout for EXIF data".into()),
+    };
+
+    output.extend_from_slice(b"EXIF");
+    output.extend_from_slice(&(exif.len() as u32).to_le_bytes());
+    output.extend_from_slice(exif);
+    if exif.len() % 2 == 1 {
+        output.push(0);
+    }
+
+    let riff_size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::Value;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::codecs::png::PngEncoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::{GenericImageView, ImageEncoder, Rgba, RgbaImage};
+
+    /// Little-endian EXIF block with a make of "Cam" and `orientation`
+    fn exif_block(orientation: u16) -> Vec<u8> {
+        let mut exif = b"II*\0".to_vec();
+        exif.extend_from_slice(&8u32.to_le_bytes());
+        exif.extend_from_slice(&2u16.to_le_bytes());
+        // Make: ASCII, 4 bytes stored inline
+        exif.extend_from_slice(&[0x0f, 0x01, 2, 0]);
+        exif.extend_from_slice(&4u32.to_le_bytes());
+        exif.extend_from_slice(b"Cam\0");
+        // Orientation: one SHORT
+        exif.extend_from_slice(&[0x12, 0x01, 3, 0]);
+        exif.extend_from_slice(&1u32.to_le_bytes());
+        exif.extend_from_slice(&orientation.to_le_bytes());
+        exif.extend_from_slice(&[0, 0]);
+        // No next IFD
+        exif.extend_from_slice(&0u32.to_le_bytes());
+        exif
+    }
+
+    /// A red pixel left of a blue one
+    fn test_image() -> RgbaImage {
+        RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 255])
+            }
+        })
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let image = test_image();
+        let (width, height) = image.dimensions();
+        let mut bytes = Vec::new();
+        match format {
+            ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut bytes, 100)
+                .write_image(
+                    &DynamicImage::ImageRgba8(image).into_rgb8(),
+                    width,
+                    height,
+                    image::ExtendedColorType::Rgb8,
+                )
+                .unwrap(),
+            ImageFormat::Png => PngEncoder::new(&mut bytes)
+                .write_image(&image, width, height, image::ExtendedColorType::Rgba8)
+                .unwrap(),
+            _ => WebPEncoder::new_lossless(&mut bytes)
+                .write_image(&image, width, height, image::ExtendedColorType::Rgba8)
+                .unwrap(),
+        }
+        bytes
+    }
+
+    fn orientation(exif: &exif::Exif) -> Option<u32> {
+        exif.get_field(Tag::Orientation, In::PRIMARY)?
+            .value
+            .get_uint(0)
+    }
+
+    #[test]
+    fn embedded_exif_round_trips() {
+        for format in [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP] {
+            let bytes = embed_exif(encode(format), format, &exif_block(1)).unwrap();
+
+            let exif = Reader::new()
+                .read_from_container(&mut Cursor::new(&bytes))
+                .unwrap_or_else(|e| panic!("{:?}: {}", format, e));
+            assert_eq!(orientation(&exif), Some(1), "{:?}", format);
+            let make = exif.get_field(Tag::Make, In::PRIMARY).unwrap();
+            assert!(
+                matches!(&make.value, Value::Ascii(values) if values[0] == b"Cam"),
+                "{:?}",
+                format
+            );
+
+            let (image, metadata) = load_image_with_metadata(&bytes, format).unwrap();
+            assert_eq!(image.dimensions(), (2, 1), "{:?}", format);
+            assert_eq!(metadata.camera, [("Make", "Cam".to_string())]);
+        }
+    }
+
+    #[test]
+    fn orientation_is_applied_on_load() {
+        // 6 means the camera was turned right: the image is shown rotated
+        // 90° clockwise, so the left pixel ends up on top
+        for format in [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP] {
+            let bytes = embed_exif(encode(format), format, &exif_block(6)).unwrap();
+            let (image, metadata) = load_image_with_metadata(&bytes, format).unwrap();
+            assert_eq!(metadata.orientation, Orientation::Rotate90);
+            assert_eq!((metadata.width, metadata.height), (1, 2));
+            let image = image.into_rgba8();
+            // Red over blue, give or take JPEG compression
+            let [top, bottom] = [0, 1].map(|y| image.get_pixel(0, y).0);
+            assert!(top[0] > 200 && top[2] < 50, "{:?}: {:?}", format, top);
+            assert!(
+                bottom[0] < 50 && bottom[2] > 200,
+                "{:?}: {:?}",
+                format,
+                bottom
+            );
+        }
+    }
+
+    #[test]
+    fn normalizing_resets_only_the_orientation() {
+        let normalized = normalize_exif_orientation(&exif_block(6));
+        let exif = Reader::new().read_raw(normalized.clone()).unwrap();
+        assert_eq!(orientation(&exif), Some(1));
+        assert_eq!(normalized, exif_block(1));
+
+        // Not EXIF: left alone
+        assert_eq!(normalize_exif_orientation(b"garbage!"), b"garbage!");
+    }
+
+    #[test]
+    fn rejects_mismatched_containers() {
+        let png = encode(ImageFormat::Png);
+        assert!(embed_exif(png.clone(), ImageFormat::Jpeg, &exif_block(1)).is_err());
+        assert!(embed_exif(png.clone(), ImageFormat::WebP, &exif_block(1)).is_err());
+        assert!(embed_exif(png, ImageFormat::Gif, &exif_block(1)).is_err());
+    }
+}
@@ -1,2 +1,24 @@
 mod image_utils;
-pub use image_utils::{create_preview_data_url, is_image_file, rgba_image_to_data_url};
+#[cfg(not(feature = "web"))]
+pub use image_utils::rgba_image_to_bmp;
+pub use image_utils::{
+    create_proxy_image, detect_image_format, format_name, is_supported_format, SUPPORTED_FORMATS,
+};
+mod export;
+pub use export::{
+    bytes_to_data_url, encode_image, format_file_size, render_filename, scaled_dimensions,
+    ExportFormat, ExportOptions,
+};
+mod metadata;
+pub use metadata::{load_image_with_metadata, ImageMetadata};
+mod color;
+pub use color::{
+    extract_palette, hsl_to_rgb, hsv_to_rgb, linear_to_srgb, luminance, parse_hex_color,
+    rgb_to_hsl, rgb_to_hsv, rgb_to_lab, rgb_to_oklab, srgb_to_linear, to_hex,
+};
+mod curve;
+pub use curve::interpolate_curve;
+mod histogram;
+pub use histogram::{ChannelStats, Histogram, HISTOGRAM_CHANNELS};
+mod palette;
+pub use palette::{parse_palette, Palette};
@@ -0,0 +1,457 @@
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::color::{parse_hex_color, to_hex};
+
+/// A named list of colors, e.g. imported from a desktop theme
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    #[serde(with = "hex_colors")]
+    pub colors: Vec<Rgb<u8>>,
+}
+
+/// Theme files a palette can be imported from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PaletteFormat {
+    /// pywal `colors.json`
+    Pywal,
+    /// base16 or base24 scheme YAML
+    Base16,
+    Xresources,
+    /// GIMP `.gpl` palette
+    Gimp,
+    /// Kitty `kitty.conf` or theme file
+    Kitty,
+    /// Alacritty TOML or YAML config
+    Alacritty,
+}
+
+impl PaletteFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaletteFormat::Pywal => "pywal",
+            PaletteFormat::Base16 => "base16/base24",
+            PaletteFormat::Xresources => "Xresources",
+            PaletteFormat::Gimp => "GIMP palette",
+            PaletteFormat::Kitty => "Kitty",
+            PaletteFormat::Alacritty => "Alacritty",
+        }
+    }
+
+    /// Guess the format of a theme file from its contents
+    pub fn detect(contents: &str) -> Option<PaletteFormat> {
+        // Comments may contain anything, e.g. `# see foo.conf: ...` would
+        // look like an Xresources entry
+        let lines = || {
+            contents.lines().map(str::trim).filter(|line| {
+                !line.is_empty()
+                    && !line.starts_with('!')
+                    && (!line.starts_with('#') || line.starts_with("#define"))
+            })
+        };
+
+        if contents.trim_start().starts_with('{') {
+            return Some(PaletteFormat::Pywal);
+        }
+        if lines().next()?.starts_with("GIMP Palette") {
+            return Some(PaletteFormat::Gimp);
+        }
+        if lines().any(|line| line.starts_with("base00")) {
+            return Some(PaletteFormat::Base16);
+        }
+        if lines().any(|line| line.starts_with("[colors") || line == "colors:") {
+            return Some(PaletteFormat::Alacritty);
+        }
+        if lines().any(|line| {
+            line.starts_with("#define")
+                || line
+                    .split_once(':')
+                    .is_some_and(|(key, _)| key.contains('*') || key.contains('.'))
+        }) {
+            return Some(PaletteFormat::Xresources);
+        }
+        if lines().any(|line| {
+            line.split_whitespace()
+                .next()
+                .is_some_and(|key| color_role(key).is_some())
+        }) {
+            return Some(PaletteFormat::Kitty);
+        }
+        None
+    }
+}
+
+/// Import a palette from a theme file.
+///
+/// The palette is named after the scheme when the file has a name field,
+/// otherwise after the file.
+pub fn parse_palette(
+    file_name: &str,
+    contents: &str,
+) -> Result<Palette, Box<dyn std::error::Error>> {
+    let format = PaletteFormat::detect(contents).ok_or("Unrecognised palette file")?;
+
+    let (name, colors) = match format {
+        PaletteFormat::Pywal => parse_pywal(contents)?,
+        PaletteFormat::Base16 => parse_base16(contents),
+        PaletteFormat::Xresources => parse_xresources(contents),
+        PaletteFormat::Gimp => parse_gimp(contents),
+        PaletteFormat::Kitty => parse_kitty(contents),
+        PaletteFormat::Alacritty => parse_alacritty(contents),
+    };
+
+    let mut unique: Vec<Rgb<u8>> = Vec::with_capacity(colors.len());
+    for color in colors {
+        if !unique.contains(&color) {
+            unique.push(color);
+        }
+    }
+    if unique.is_empty() {
+        return Err(format!("No colors found in {} file", format.name()).into());
+    }
+
+    // `.Xresources` and similar dotfiles are named without the dot
+    let file_name = file_name.trim_start_matches('.');
+    let stem = file_name
+        .rsplit_once('.')
+        .map(|(stem, _)| stem)
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or(file_name);
+    Ok(Palette {
+        name: name.unwrap_or_else(|| stem.to_string()),
+        colors: unique,
+    })
+}
+
+type Parsed = (Option<String>, Vec<Rgb<u8>>);
+
+/// Parse a color value as written in theme files: `#rrggbb`, `rrggbb`,
+/// `0xrrggbb` or `rgb:rr/gg/bb`, optionally quoted
+fn parse_theme_color(value: &str) -> Option<Rgb<u8>> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+    if let Some(channels) = value.strip_prefix("rgb:") {
+        let channels: Vec<u8> = channels
+            .split('/')
+            .map(|channel| u8::from_str_radix(channel.get(..2)?, 16).ok())
+            .collect::<Option<_>>()?;
+        return match channels[..] {
+            [r, g, b] => Some(Rgb([r, g, b])),
+            _ => None,
+        };
+    }
+    let value = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    parse_hex_color(value)
+}
+
+/// Sort position of a terminal color name: background, foreground, then
+/// `color0`, `color1`... Other names are not palette colors.
+fn color_role(key: &str) -> Option<u32> {
+    match key {
+        "background" => Some(0),
+        "foreground" => Some(1),
+        _ => key
+            .strip_prefix("color")?
+            .parse::<u32>()
+            .ok()
+            .map(|index| index + 2),
+    }
+}
+
+fn sorted_by_role(mut entries: Vec<(u32, Rgb<u8>)>) -> Vec<Rgb<u8>> {
+    entries.sort_by_key(|(role, _)| *role);
+    entries.into_iter().map(|(_, color)| color).collect()
+}
+
+fn strip_comment<'a>(line: &'a str, marker: &str) -> &'a str {
+    match line.find(marker) {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+fn parse_pywal(contents: &str) -> Result<Parsed, Box<dyn std::error::Error>> {
+    let json: serde_json::Value = serde_json::from_str(contents)?;
+    let lookup = |section: &str, key: &str| {
+        json.get(section)?
+            .get(key)?
+            .as_str()
+            .and_then(parse_theme_color)
+    };
+
+    let mut colors: Vec<Rgb<u8>> = ["background", "foreground"]
+        .iter()
+        .filter_map(|key| lookup("special", key))
+        .collect();
+    colors.extend((0..16).filter_map(|index| lookup("colors", &format!("color{}", index))));
+    Ok((None, colors))
+}
+
+fn parse_base16(contents: &str) -> Parsed {
+    let mut name = None;
+    let mut entries = Vec::new();
+
+    for line in contents.lines() {
+        let Some((key, value)) = strip_comment(line, " #").split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        if (key == "scheme" || key == "name") && name.is_none() && !value.is_empty() {
+            name = Some(value.to_string());
+        } else if let Some(index) = key.strip_prefix("base") {
+            // base00-base0F for base16, up to base17 for base24
+            if let (Ok(index), Some(color)) =
+                (u32::from_str_radix(index, 16), parse_theme_color(value))
+            {
+                entries.push((index, color));
+            }
+        }
+    }
+
+    (name, sorted_by_role(entries))
+}
+
+fn parse_xresources(contents: &str) -> Parsed {
+    let mut defines: HashMap<&str, &str> = HashMap::new();
+    let mut entries = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('!') {
+            continue;
+        }
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(name, value);
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // `URxvt*color1`, `*.background`... only the last component matters
+        let key = key.rsplit(['*', '.']).next().unwrap_or(key).trim();
+        let value = value.trim();
+        let value = defines.get(value).copied().unwrap_or(value);
+        if let (Some(role), Some(color)) = (color_role(key), parse_theme_color(value)) {
+            entries.push((role, color));
+        }
+    }
+
+    (None, sorted_by_role(entries))
+}
+
+fn parse_gimp(contents: &str) -> Parsed {
+    let mut name = None;
+    let mut colors = Vec::new();
+
+    for line in contents.lines().skip(1) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+            continue;
+        }
+        if let Some(palette_name) = line.strip_prefix("Name:") {
+            name = Some(palette_name.trim().to_string()).filter(|name| !name.is_empty());
+            continue;
+        }
+        let channels: Vec<u8> = line
+            .split_whitespace()
+            .take(3)
+            .filter_map(|channel| channel.parse().ok())
+            .collect();
+        if let [r, g, b] = channels[..] {
+            colors.push(Rgb([r, g, b]));
+        }
+    }
+
+    (name, colors)
+}
+
+fn parse_kitty(contents: &str) -> Parsed {
+    let mut entries = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        if let (Some(role), Some(color)) = (
+            parts.next().and_then(color_role),
+            parts.next().and_then(parse_theme_color),
+        ) {
+            entries.push((role, color));
+        }
+    }
+
+    (None, sorted_by_role(entries))
+}
+
+fn parse_alacritty(contents: &str) -> Parsed {
+    let toml = contents.lines().any(|line| line.trim().starts_with('['));
+    let mut in_colors = false;
+    let mut colors = Vec::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // TOML: `[colors.normal]` tables; YAML: the indented block under `colors:`
+        if toml {
+            if trimmed.starts_with('[') {
+                in_colors = trimmed.starts_with("[colors");
+                continue;
+            }
+        } else if !line.starts_with(char::is_whitespace) {
+            in_colors = trimmed == "colors:";
+            continue;
+        }
+        if !in_colors {
+            continue;
+        }
+
+        let Some((_, value)) = trimmed.split_once(['=', ':']) else {
+            continue;
+        };
+        if let Some(color) = parse_theme_color(strip_comment(value, " #")) {
+            colors.push(color);
+        }
+    }
+
+    (None, colors)
+}
+
+/// Stores colors as `#rrggbb` strings
+mod hex_colors {
+    use image::Rgb;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{parse_hex_color, to_hex};
+
+    pub fn serialize<S: Serializer>(colors: &[Rgb<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(|color| to_hex(*color)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Rgb<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| {
+                parse_hex_color(hex)
+                    .ok_or_else(|| D::Error::custom(format!("invalid color {}", hex)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb<u8> = Rgb([0x1d, 0x1f, 0x21]);
+    const RED: Rgb<u8> = Rgb([0xcc, 0x66, 0x66]);
+    const WHITE: Rgb<u8> = Rgb([0xc5, 0xc8, 0xc6]);
+
+    fn parse(file_name: &str, contents: &str) -> (PaletteFormat, Palette) {
+        let format = PaletteFormat::detect(contents).expect("format detected");
+        (format, parse_palette(file_name, contents).unwrap())
+    }
+
+    #[test]
+    fn parses_base16_and_base24() {
+        let (format, palette) = parse(
+            "tomorrow-night.yaml",
+            "scheme: \"Tomorrow Night\" # the name\n\
+             author: \"Chris Kempson (http://chriskempson.com)\"\n\
+             base01: \"cc6666\"\n\
+             base00: \"1d1f21\" # background\n\
+             base10: 'c5c8c6'\n",
+        );
+        assert_eq!(format, PaletteFormat::Base16);
+        assert_eq!(palette.name, "Tomorrow Night");
+        assert_eq!(palette.colors, [BLACK, RED, WHITE]);
+    }
+
+    #[test]
+    fn parses_alacritty_toml_and_yaml() {
+        let toml = "[window]\nopacity = 0.9\n\n\
+                    [colors.primary]\nbackground = \"#1d1f21\" # dark\nforeground = '0xc5c8c6'\n\n\
+                    [colors.normal]\nred = \"#cc6666\"\n\n\
+                    [font]\nsize = 11.0\n";
+        let (format, palette) = parse("alacritty.toml", toml);
+        assert_eq!(format, PaletteFormat::Alacritty);
+        assert_eq!(palette.name, "alacritty");
+        assert_eq!(palette.colors, [BLACK, WHITE, RED]);
+
+        let yaml = "font:\n  size: 11.0\n\
+                    colors:\n  # Default colors\n  primary:\n    background: '#1d1f21'\n\
+                    \x20   foreground: '0xc5c8c6'  # text\n  normal:\n    red: '#cc6666'\n\
+                    window:\n  opacity: 0.9\n";
+        let (format, palette) = parse("alacritty.yml", yaml);
+        assert_eq!(format, PaletteFormat::Alacritty);
+        assert_eq!(palette.colors, [BLACK, WHITE, RED]);
+    }
+
+    #[test]
+    fn parses_kitty() {
+        let (format, palette) = parse(
+            "Tomorrow Night.conf",
+            "# vim:ft=kitty\n# Port of the scheme, see e.g. base16.conf: same colors\n\
+             foreground #c5c8c6\nbackground   #1d1f21\nselection_background #373b41\n\
+             color1 #cc6666\n",
+        );
+        assert_eq!(format, PaletteFormat::Kitty);
+        assert_eq!(palette.name, "Tomorrow Night");
+        assert_eq!(palette.colors, [BLACK, WHITE, RED]);
+    }
+
+    #[test]
+    fn parses_xresources() {
+        let (format, palette) = parse(
+            ".Xresources",
+            "! Tomorrow Night: colors.\n#define bg #1d1f21\n\
+             URxvt.font: xft:Mono:size=10\n*.background: bg\n\
+             URxvt*color1:  rgb:cc/66/66\n*foreground: #c5c8c6\n",
+        );
+        assert_eq!(format, PaletteFormat::Xresources);
+        assert_eq!(palette.name, "Xresources");
+        assert_eq!(palette.colors, [BLACK, WHITE, RED]);
+    }
+
+    #[test]
+    fn parses_pywal_and_gimp() {
+        let (format, palette) = parse(
+            "colors.json",
+            r##"{"special": {"background": "#1d1f21", "foreground": "#c5c8c6"},
+                "colors": {"color0": "#1d1f21", "color1": "#cc6666"}}"##,
+        );
+        assert_eq!(format, PaletteFormat::Pywal);
+        assert_eq!(palette.colors, [BLACK, WHITE, RED]);
+
+        let (format, palette) = parse(
+            "tomorrow.gpl",
+            "GIMP Palette\nName: Tomorrow\nColumns: 3\n#\n 29  31  33\tBackground\n204 102 102\n",
+        );
+        assert_eq!(format, PaletteFormat::Gimp);
+        assert_eq!(palette.name, "Tomorrow");
+        assert_eq!(palette.colors, [BLACK, RED]);
+    }
+
+    #[test]
+    fn rejects_files_without_colors() {
+        assert_eq!(PaletteFormat::detect("hello world\n"), None);
+        assert!(parse_palette("empty.conf", "").is_err());
+        let error = parse_palette("kitty.conf", "color1 not-a-color\n").unwrap_err();
+        assert_eq!(error.to_string(), "No colors found in Kitty file");
+    }
+}